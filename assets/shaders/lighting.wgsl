@@ -0,0 +1,32 @@
+// Keep in sync with MAX_LIGHTS in lighting.rs
+let MAX_LIGHTS: u32 = 16u;
+
+struct LightingUniform {
+    ambient: vec4<f32>,
+    // xy: position in camera space, z: radius, w: intensity
+    lights: array<vec4<f32>, 16>,
+    colors: array<vec4<f32>, 16>,
+    size: vec2<f32>,
+    count: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> lighting: LightingUniform;
+
+@fragment
+fn fragment(
+    #import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+    // The quad is centred on the camera and faces it, so this is the position in camera space
+    let position = vec2<f32>(uv.x - 0.5, 0.5 - uv.y) * lighting.size;
+
+    var light = lighting.ambient.rgb;
+    for (var i: u32 = 0u; i < min(lighting.count, MAX_LIGHTS); i = i + 1u) {
+        let source = lighting.lights[i];
+        let falloff = clamp(1.0 - distance(position, source.xy) / source.z, 0.0, 1.0);
+        light = light + lighting.colors[i].rgb * falloff * falloff * source.w;
+    }
+
+    // Multiplied onto what's already drawn, see LightingMaterial::specialize
+    return vec4<f32>(min(light, vec3<f32>(1.0)), 1.0);
+}
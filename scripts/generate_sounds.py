@@ -0,0 +1,138 @@
+"""Generates the placeholder sound effects and music in assets/sounds and assets/music.
+
+Everything is synthesized, so this only needs the standard library:
+    python scripts/generate_sounds.py
+"""
+import math
+import pathlib
+import random
+import wave
+
+SAMPLE_RATE = 11025
+ROOT = pathlib.Path(__file__).resolve().parent.parent / 'assets'
+
+
+# Waveforms; t is in cycles
+def square(t, duty=0.5):
+    return 1.0 if t % 1.0 < duty else -1.0
+
+def triangle(t):
+    x = t % 1.0
+    return 4.0 * x - 1.0 if x < 0.5 else 3.0 - 4.0 * x
+
+def sine(t):
+    return math.sin(2.0 * math.pi * t)
+
+WAVES = {'square': square, 'triangle': triangle, 'sine': sine}
+
+
+def write_wav(path, samples):
+    path.parent.mkdir(parents=True, exist_ok=True)
+    with wave.open(str(path), 'wb') as f:
+        f.setnchannels(1)
+        f.setsampwidth(1)
+        f.setframerate(SAMPLE_RATE)
+        # 8 bit wav is unsigned
+        f.writeframes(bytes(int(max(-1.0, min(1.0, s)) * 120 + 128) for s in samples))
+    print('{:.<40}done'.format(str(path.relative_to(ROOT))))
+
+
+def effect(length, start_freq, end_freq, wave='square', noise=0.0, attack=0.005, volume=0.6, seed=0):
+    """A single note sliding between two pitches, optionally mixed with noise"""
+    rng = random.Random(seed)
+    n = int(length * SAMPLE_RATE)
+    phase = 0.0
+    held_noise = 0.0
+    samples = []
+    for i in range(n):
+        t = i / SAMPLE_RATE
+        progress = i / n
+        freq = start_freq * (end_freq / start_freq) ** progress
+        phase += freq / SAMPLE_RATE
+        # Noise gets held for a bit to give it some pitch
+        if i % max(1, int(SAMPLE_RATE / (freq * 4))) == 0:
+            held_noise = rng.uniform(-1.0, 1.0)
+        value = WAVES[wave](phase) * (1.0 - noise) + held_noise * noise
+        envelope = min(1.0, t / attack) * (1.0 - progress) ** 2
+        samples.append(value * envelope * volume)
+    return samples
+
+
+def sequence(notes, wave='square'):
+    """Plays notes one after another; notes are (frequency, length) pairs"""
+    samples = []
+    for i, (freq, length) in enumerate(notes):
+        samples += effect(length, freq, freq, wave=wave, seed=i)
+    return samples
+
+
+# Per element: (cast start, cast end, impact start, impact end, wave, noise)
+ELEMENTS = {
+    'neutral': (440, 660, 330, 220, 'triangle', 0.0),
+    'fire': (300, 900, 200, 60, 'square', 0.6),
+    'water': (600, 300, 400, 150, 'sine', 0.1),
+    'earth': (150, 90, 120, 40, 'square', 0.5),
+    'air': (800, 1600, 600, 300, 'triangle', 0.7),
+    'metal': (500, 520, 900, 700, 'square', 0.2),
+    'plant': (350, 500, 250, 200, 'triangle', 0.3),
+    'electric': (1200, 2400, 1800, 600, 'square', 0.8),
+    'ice': (1400, 1100, 1600, 1200, 'sine', 0.2),
+    'light': (660, 1320, 990, 1980, 'sine', 0.0),
+}
+
+
+def generate_effects():
+    for i, (name, (cast_start, cast_end, hit_start, hit_end, wave, noise)) in enumerate(ELEMENTS.items()):
+        write_wav(ROOT / 'sounds' / 'cast-{}.wav'.format(name),
+            effect(0.18, cast_start, cast_end, wave=wave, noise=noise * 0.5, volume=0.35, seed=i))
+        write_wav(ROOT / 'sounds' / 'impact-{}.wav'.format(name),
+            effect(0.25, hit_start, hit_end, wave=wave, noise=noise, volume=0.45, seed=i + 100))
+
+    write_wav(ROOT / 'sounds' / 'enemy-hit.wav', effect(0.12, 220, 110, noise=0.5))
+    write_wav(ROOT / 'sounds' / 'enemy-death.wav', effect(0.5, 300, 40, noise=0.7))
+    write_wav(ROOT / 'sounds' / 'player-hurt.wav', effect(0.25, 180, 90, wave='triangle', noise=0.3, volume=0.8))
+    write_wav(ROOT / 'sounds' / 'player-defeated.wav', sequence([(392, 0.25), (330, 0.25), (262, 0.25), (196, 0.6)], wave='triangle'))
+    write_wav(ROOT / 'sounds' / 'pickup-rune.wav', sequence([(523, 0.08), (659, 0.08), (784, 0.08), (1047, 0.25)]))
+    write_wav(ROOT / 'sounds' / 'footstep.wav', effect(0.06, 140, 70, wave='triangle', noise=0.7, volume=0.3))
+    write_wav(ROOT / 'sounds' / 'pickup-staff.wav', sequence([(392, 0.12), (523, 0.12), (659, 0.12), (784, 0.12), (1047, 0.5)], wave='triangle'))
+
+
+def note_freq(semitones_from_a4):
+    return 440.0 * 2.0 ** (semitones_from_a4 / 12.0)
+
+
+def song(chords, beat_length, arpeggio_wave, bass_wave='triangle', beats_per_chord=8):
+    """Loops an arpeggio over a chord progression with a bass note under it.
+    Chords are lists of semitones from A4; the first note is also the bass."""
+    samples = []
+    phase_arp = 0.0
+    phase_bass = 0.0
+    beat_samples = int(beat_length * SAMPLE_RATE)
+    for chord in chords:
+        bass_freq = note_freq(chord[0] - 24)
+        for beat in range(beats_per_chord):
+            arp_freq = note_freq(chord[beat % len(chord)])
+            for i in range(beat_samples):
+                progress = i / beat_samples
+                phase_arp += arp_freq / SAMPLE_RATE
+                phase_bass += bass_freq / SAMPLE_RATE
+                arp = WAVES[arpeggio_wave](phase_arp) * (1.0 - progress) ** 1.5 * 0.25
+                bass = WAVES[bass_wave](phase_bass) * 0.3
+                samples.append(arp + bass)
+    return samples
+
+
+def generate_music():
+    # A minor, slow
+    write_wav(ROOT / 'music' / 'title.wav', song([[0, 3, 7, 12], [-4, 0, 3, 8], [-7, -3, 0, 5], [-5, -1, 2, 7]], 0.3, 'sine'))
+    # C major-ish wandering
+    write_wav(ROOT / 'music' / 'forest.wav', song([[3, 7, 10, 15], [-2, 3, 7, 10], [5, 8, 12, 17], [-4, 0, 3, 8]], 0.2, 'triangle'))
+    # D minor, faster and harsher
+    write_wav(ROOT / 'music' / 'tower.wav', song([[5, 8, 12, 17], [1, 5, 8, 13], [3, 7, 10, 15], [0, 4, 7, 12]], 0.14, 'square'))
+    # A major, calm
+    write_wav(ROOT / 'music' / 'ending.wav', song([[0, 4, 7, 12], [5, 9, 12, 17], [-3, 0, 4, 9], [-5, -1, 2, 7]], 0.35, 'sine'))
+
+
+if __name__ == '__main__':
+    generate_effects()
+    generate_music()
@@ -0,0 +1,296 @@
+use super::{player::{self, Action}, ui, game_state};
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Keeps track of the player's (rebindable) controls.
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.insert_resource(KeyBindings::load())
+			.insert_resource(RebindingAction(None))
+			.add_system(apply_bindings)
+			.add_system(capture_rebind);
+	}
+}
+
+/// Where bindings get saved; relative to the working directory
+#[cfg(not(target_arch = "wasm32"))]
+const BINDINGS_PATH: &str = "bindings.ron";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+	Key(KeyCode),
+	Mouse(MouseButton),
+	Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+	/// Name to show to the player
+	pub fn get_display_name(&self) -> String {
+		match self {
+			Binding::Key(key) => {
+				let name = format!("{:?}", key);
+				// Number keys are called Key1 etc.
+				match name.strip_prefix("Key") {
+					Some(number) if !number.is_empty() => number.to_string(),
+					_ => name.to_uppercase(),
+				}
+			}
+			Binding::Mouse(MouseButton::Left) => "LEFT CLICK".to_string(),
+			Binding::Mouse(MouseButton::Right) => "RIGHT CLICK".to_string(),
+			Binding::Mouse(MouseButton::Middle) => "MIDDLE CLICK".to_string(),
+			Binding::Mouse(MouseButton::Other(n)) => format!("MOUSE {}", n),
+			Binding::Gamepad(button) => format!("{:?}", button).to_uppercase(),
+		}
+	}
+
+	/// Keyboard and mouse bindings can be rebound; gamepad ones are fixed
+	fn is_rebindable(&self) -> bool {
+		!matches!(self, Binding::Gamepad(_))
+	}
+}
+
+/// Resource
+/// All of the bindings, in order of priority for each action.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyBindings(pub Vec<(Action, Binding)>);
+
+impl Default for KeyBindings {
+	fn default() -> Self {
+		KeyBindings(vec![
+			(Action::Up, Binding::Key(KeyCode::W)),
+			(Action::Left, Binding::Key(KeyCode::A)),
+			(Action::Down, Binding::Key(KeyCode::S)),
+			(Action::Right, Binding::Key(KeyCode::D)),
+			(Action::Up, Binding::Key(KeyCode::Up)),
+			(Action::Left, Binding::Key(KeyCode::Left)),
+			(Action::Down, Binding::Key(KeyCode::Down)),
+			(Action::Right, Binding::Key(KeyCode::Right)),
+			(Action::Run, Binding::Key(KeyCode::LShift)),
+			(Action::Dodge, Binding::Key(KeyCode::Space)),
+			(Action::Pause, Binding::Key(KeyCode::Escape)),
+			(Action::ToggleFullscreen, Binding::Key(KeyCode::F11)),
+			// Spells
+			(Action::OpenInventory, Binding::Key(KeyCode::Tab)),
+			(Action::SpellComp0, Binding::Key(KeyCode::Key1)),
+			(Action::SpellComp1, Binding::Key(KeyCode::Key2)),
+			(Action::SpellComp2, Binding::Key(KeyCode::Key3)),
+			(Action::SpellComp3, Binding::Key(KeyCode::Key4)),
+			(Action::SpellComp4, Binding::Key(KeyCode::E)),
+			(Action::CastSpell, Binding::Mouse(MouseButton::Left)),
+			(Action::CancelSpell, Binding::Mouse(MouseButton::Right)),
+			// Gamepad; the sticks are handled in gamepad.rs
+			(Action::Up, Binding::Gamepad(GamepadButtonType::DPadUp)),
+			(Action::Left, Binding::Gamepad(GamepadButtonType::DPadLeft)),
+			(Action::Down, Binding::Gamepad(GamepadButtonType::DPadDown)),
+			(Action::Right, Binding::Gamepad(GamepadButtonType::DPadRight)),
+			(Action::Run, Binding::Gamepad(GamepadButtonType::LeftThumb)),
+			(Action::Dodge, Binding::Gamepad(GamepadButtonType::LeftTrigger)),
+			(Action::SpellComp0, Binding::Gamepad(GamepadButtonType::South)),
+			(Action::SpellComp1, Binding::Gamepad(GamepadButtonType::East)),
+			(Action::SpellComp2, Binding::Gamepad(GamepadButtonType::West)),
+			(Action::SpellComp3, Binding::Gamepad(GamepadButtonType::North)),
+			(Action::SpellComp4, Binding::Gamepad(GamepadButtonType::RightTrigger)),
+			(Action::CastSpell, Binding::Gamepad(GamepadButtonType::RightTrigger2)),
+			(Action::CancelSpell, Binding::Gamepad(GamepadButtonType::LeftTrigger2)),
+			(Action::OpenInventory, Binding::Gamepad(GamepadButtonType::Select)),
+			(Action::Pause, Binding::Gamepad(GamepadButtonType::Start)),
+		])
+	}
+}
+
+impl KeyBindings {
+	/// Loads saved bindings, falling back to the defaults
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn load() -> Self {
+		match std::fs::read_to_string(BINDINGS_PATH) {
+			Ok(contents) => ron::from_str::<Self>(&contents)
+				.map(|bindings| bindings.with_missing_defaults())
+				.unwrap_or_else(|e| {
+					warn!("could not read {}, using default bindings: {}", BINDINGS_PATH, e);
+					Self::default()
+				}),
+			Err(_) => Self::default(),
+		}
+	}
+	#[cfg(target_arch = "wasm32")]
+	pub fn load() -> Self {
+		Self::default()
+	}
+
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn save(&self) {
+		let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+			.map_err(|e| e.to_string())
+			.and_then(|contents| std::fs::write(BINDINGS_PATH, contents).map_err(|e| e.to_string()));
+
+		if let Err(e) = result {
+			warn!("could not save bindings to {}: {}", BINDINGS_PATH, e);
+		}
+	}
+	#[cfg(target_arch = "wasm32")]
+	pub fn save(&self) {}
+
+	/// Adds the default bindings for actions with none at all,
+	/// so actions added since the bindings were saved still work
+	#[cfg(not(target_arch = "wasm32"))]
+	fn with_missing_defaults(mut self) -> Self {
+		for (action, binding) in Self::default().0 {
+			if !self.0.iter().any(|(bound_action, _)| *bound_action == action) {
+				self.0.push((action, binding));
+			}
+		}
+		self
+	}
+
+	pub fn to_input_map(&self) -> InputMap<Action> {
+		let mut input_map = InputMap::default();
+		for (action, binding) in self.0.iter() {
+			match *binding {
+				Binding::Key(key) => input_map.insert(key, *action),
+				Binding::Mouse(button) => input_map.insert(button, *action),
+				Binding::Gamepad(button) => input_map.insert(button, *action),
+			};
+		}
+		input_map
+	}
+
+	/// The binding shown in menus and messages
+	pub fn get_primary_binding(&self, action: Action) -> Option<Binding> {
+		self.0.iter()
+			.find(|(bound_action, binding)| *bound_action == action && binding.is_rebindable())
+			.map(|(_, binding)| *binding)
+	}
+
+	/// Binds the input to the action, replacing its primary binding.
+	/// Fails with the conflicting action if the input is already used for something else.
+	pub fn rebind(&mut self, action: Action, binding: Binding) -> Result<(), Action> {
+		if let Some((other_action, _)) = self.0.iter().find(|(other_action, other_binding)| {
+			*other_binding == binding && *other_action != action
+		}) {
+			return Err(*other_action);
+		}
+
+		match self.0.iter_mut().find(|(bound_action, old_binding)| *bound_action == action && old_binding.is_rebindable()) {
+			Some((_, old_binding)) => *old_binding = binding,
+			None => self.0.push((action, binding)),
+		}
+		// Rebinding something to itself could leave a duplicate around
+		let mut seen = Vec::new();
+		self.0.retain(|entry| {
+			let is_new = !seen.contains(entry);
+			seen.push(*entry);
+			is_new
+		});
+		Ok(())
+	}
+
+	/// Replaces placeholders like [Up] with the names of the keys bound to that action.
+	/// Used so tutorial messages stay correct after rebinding.
+	pub fn format_message(&self, message: &str) -> String {
+		let mut result = message.to_string();
+		for action in Action::variants() {
+			let placeholder = format!("[{:?}]", action);
+			if result.contains(&placeholder) {
+				let name = self.get_primary_binding(action)
+					.map_or("(UNBOUND)".to_string(), |binding| binding.get_display_name());
+				result = result.replace(&placeholder, &name);
+			}
+		}
+		result
+	}
+}
+
+fn apply_bindings(
+	bindings: Res<KeyBindings>,
+	mut query: Query<&mut InputMap<Action>, With<player::Player>>,
+) {
+	if !bindings.is_changed() {
+		return;
+	}
+
+	for mut input_map in query.iter_mut() {
+		// Keep the gamepad that gamepad.rs picked
+		let gamepad = input_map.gamepad();
+		*input_map = bindings.to_input_map();
+		if let Some(gamepad) = gamepad {
+			input_map.set_gamepad(gamepad);
+		}
+	}
+}
+
+/// Resource
+/// The action waiting for a new binding, if any
+pub struct RebindingAction(pub Option<Action>);
+
+/// Binds the next key or mouse button pressed to the action being rebound
+pub fn capture_rebind(
+	mut rebinding: ResMut<RebindingAction>,
+	mut bindings: ResMut<KeyBindings>,
+	keys: Res<Input<KeyCode>>,
+	mouse_buttons: Res<Input<MouseButton>>,
+	state: Res<State<game_state::GameState>>,
+	mut message_events: EventWriter<ui::MessageEvent>,
+) {
+	let action = match rebinding.0 {
+		Some(action) => action,
+		None => return,
+	};
+
+	// Closing the menu or pressing escape cancels
+	if !state.current().is_in_settings_menu() || keys.just_pressed(KeyCode::Escape) {
+		rebinding.0 = None;
+		return;
+	}
+
+	let maybe_binding = keys.get_just_pressed().next().map(|key| Binding::Key(*key))
+		.or_else(|| mouse_buttons.get_just_pressed().next().map(|button| Binding::Mouse(*button)));
+
+	if let Some(binding) = maybe_binding {
+		rebinding.0 = None;
+		match bindings.rebind(action, binding) {
+			Ok(()) => {
+				bindings.save();
+				message_events.send(ui::MessageEvent {
+					message: None,
+					source: ui::MessageSource::Controls,
+				});
+			}
+			Err(other_action) => {
+				message_events.send(ui::MessageEvent {
+					message: Some(format!(
+						"{} is already used for {}.",
+						binding.get_display_name(),
+						get_action_name(other_action),
+					)),
+					source: ui::MessageSource::Controls,
+				});
+			}
+		}
+	}
+}
+
+/// Name of an action for menus
+pub fn get_action_name(action: Action) -> &'static str {
+	match action {
+		Action::Up => "Move up",
+		Action::Down => "Move down",
+		Action::Left => "Move left",
+		Action::Right => "Move right",
+		Action::Run => "Run",
+		Action::Dodge => "Dodge",
+		Action::CastSpell => "Cast spell",
+		Action::CancelSpell => "Cancel spell",
+		Action::OpenInventory => "Inventory",
+		Action::Pause => "Pause",
+		Action::ToggleFullscreen => "Fullscreen",
+		Action::SpellComp0 => "Rune 1",
+		Action::SpellComp1 => "Rune 2",
+		Action::SpellComp2 => "Rune 3",
+		Action::SpellComp3 => "Rune 4",
+		Action::SpellComp4 => "Rune 5",
+	}
+}
@@ -0,0 +1,349 @@
+use super::{physics, enemy, sprite, expand_vec2, collapse_vec3};
+use bevy::prelude::*;
+use std::marker::PhantomData;
+
+/// Shows what physics and the AI are thinking: colliders, wall normals, enemy view ranges and AI state,
+/// and how many collisions of each kind are going on. Toggled with F3.
+/// Only compiled in with the debug-overlay feature.
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.insert_resource(DebugOverlay::default())
+			.add_startup_system(setup_debug_overlay)
+			.add_system(toggle_debug_overlay)
+			.add_system(clear_debug_shapes.after(toggle_debug_overlay))
+			.add_plugin(DebugCategoryPlugin::<physics::WallCollidable>::default())
+			.add_plugin(DebugCategoryPlugin::<physics::InteractsWithPlayer>::default())
+			.add_plugin(DebugCategoryPlugin::<physics::InteractsWithEnemies>::default())
+			.add_plugin(DebugCategoryPlugin::<physics::TakesSpace>::default())
+			.add_system(draw_wall_normals.after(clear_debug_shapes))
+			.add_system(draw_enemy_ai.after(clear_debug_shapes))
+			.add_system(update_collision_counts.after(clear_debug_shapes));
+	}
+}
+
+/// Resource
+#[derive(Debug, Default)]
+struct DebugOverlay {
+	enabled: bool,
+	font: Handle<Font>,
+	/// Lines for the collision counts, gathered from each category every frame
+	collision_counts: Vec<String>,
+}
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+
+fn toggle_debug_overlay(
+	keys: Res<Input<KeyCode>>,
+	mut overlay: ResMut<DebugOverlay>,
+) {
+	if keys.just_pressed(TOGGLE_KEY) {
+		overlay.enabled = !overlay.enabled;
+	}
+}
+
+// Collision categories ///////////////
+/// How each collision category shows up
+trait DebugCategory: Send + Sync + 'static {
+	const NAME: &'static str;
+	const COLOR: &'static str;
+}
+
+impl DebugCategory for physics::WallCollidable {
+	const NAME: &'static str = "Walls";
+	const COLOR: &'static str = "9BABB2";
+}
+impl DebugCategory for physics::InteractsWithPlayer {
+	const NAME: &'static str = "Player";
+	const COLOR: &'static str = "E83B3B";
+}
+impl DebugCategory for physics::InteractsWithEnemies {
+	const NAME: &'static str = "Enemies";
+	const COLOR: &'static str = "4D9BE6";
+}
+impl DebugCategory for physics::TakesSpace {
+	const NAME: &'static str = "Takes space";
+	const COLOR: &'static str = "FBB954";
+}
+
+#[derive(Default)]
+struct DebugCategoryPlugin<T>(PhantomData<T>);
+impl<T: DebugCategory> Plugin for DebugCategoryPlugin<T> {
+	fn build(&self, app: &mut App) {
+		app
+			.add_system(draw_colliders::<T>.after(clear_debug_shapes))
+			.add_system(count_collisions::<T>.after(clear_debug_shapes).before(update_collision_counts));
+	}
+}
+
+/// Sources are drawn solid, recipients fainter, and anything inactive barely at all
+fn draw_colliders<T: DebugCategory>(
+	mut commands: Commands,
+	overlay: Res<DebugOverlay>,
+	source_query: Query<(&physics::CollisionSource<T>, Option<&GlobalTransform>, Option<&physics::ColliderActive<T>>)>,
+	recip_query: Query<(&physics::CollisionRecipient<T>, Option<&GlobalTransform>, Option<&physics::ColliderActive<T>>)>,
+	symmetric_query: Query<(&physics::SymmetricCollisionSource<T>, Option<&GlobalTransform>, Option<&physics::ColliderActive<T>>)>,
+) {
+	if !overlay.enabled {
+		return;
+	}
+
+	let color = Color::hex(T::COLOR).unwrap();
+	let sources = source_query.iter().map(|(c, t, a)| (&c.0, t, a, 1.0));
+	let recipients = recip_query.iter().map(|(c, t, a)| (&c.0, t, a, 0.6));
+	let symmetric = symmetric_query.iter().map(|(c, t, a)| (&c.0, t, a, 1.0));
+
+	for (collider, maybe_transform, maybe_active, alpha) in sources.chain(recipients).chain(symmetric) {
+		let collider = match maybe_transform {
+			Some(transform) => collider.with_transform(&transform.compute_transform()),
+			None => collider.clone(),
+		};
+		let is_active = maybe_active.map_or(true, |active| active.0);
+		let mut color = color;
+		color.set_a(if is_active { alpha } else { 0.25 });
+
+		draw_collider(&mut commands, &collider, color);
+	}
+}
+
+fn draw_collider(commands: &mut Commands, collider: &physics::Collider, color: Color) {
+	match *collider {
+		physics::Collider::Circle { center, radius } => {
+			draw_circle(commands, center, radius, color);
+		},
+		physics::Collider::LineSegment(point1, point2) => {
+			draw_line(commands, point1, point2, LINE_THICKNESS, color);
+		},
+		physics::Collider::LineRay { anchor, direction } => {
+			draw_line(commands, anchor, anchor + direction.normalize_or_zero() * RAY_LENGTH, LINE_THICKNESS, color);
+		},
+		physics::Collider::ThickLineSegment { point1, point2, thickness } => {
+			let mut faint = color;
+			faint.set_a(color.a() * 0.3);
+			draw_line(commands, point1, point2, thickness * 2.0, faint);
+			draw_line(commands, point1, point2, LINE_THICKNESS, color);
+		},
+	}
+}
+
+fn count_collisions<T: DebugCategory>(
+	mut overlay: ResMut<DebugOverlay>,
+	collisions: Res<physics::ActiveCollisions<T>>,
+) {
+	let line = format!("{}: {}", T::NAME, collisions.len());
+	overlay.collision_counts.push(line);
+}
+
+// Shapes ///////////////
+/// Everything drawn by the overlay; it's all thrown away and redrawn each frame
+#[derive(Component, Debug)]
+struct DebugShape;
+
+const LINE_THICKNESS: f32 = 1.5;
+const RAY_LENGTH: f32 = 1000.0;
+const CIRCLE_SEGMENTS: usize = 24;
+/// Just above the ground so it doesn't sit exactly on top of shadows
+const SHAPE_HEIGHT: f32 = 0.5;
+
+fn clear_debug_shapes(
+	mut commands: Commands,
+	query: Query<Entity, With<DebugShape>>,
+	mut overlay: ResMut<DebugOverlay>,
+) {
+	for e in query.iter() {
+		commands.entity(e).despawn_recursive();
+	}
+	overlay.collision_counts.clear();
+}
+
+/// A flat line lying on the ground, between two points in physics space
+fn draw_line(commands: &mut Commands, point1: Vec2, point2: Vec2, thickness: f32, color: Color) {
+	let difference = point2 - point1;
+	let length = difference.length();
+	if length <= f32::EPSILON {
+		return;
+	}
+
+	let rotation = Quat::from_rotation_y((-difference.y).atan2(difference.x))
+		* Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2);
+	commands
+		.spawn_bundle(SpriteBundle {
+			sprite: Sprite {
+				color,
+				custom_size: Some(Vec2::new(length, thickness)),
+				..default()
+			},
+			transform: Transform {
+				translation: expand_vec2((point1 + point2) / 2.0) + Vec3::Y * SHAPE_HEIGHT,
+				rotation,
+				..default()
+			},
+			..default()
+		})
+		.insert(DebugShape);
+}
+
+fn draw_circle(commands: &mut Commands, center: Vec2, radius: f32, color: Color) {
+	let point_at = |i: usize| {
+		let angle = std::f32::consts::TAU * i as f32 / CIRCLE_SEGMENTS as f32;
+		center + Vec2::new(angle.cos(), angle.sin()) * radius
+	};
+	for i in 0..CIRCLE_SEGMENTS {
+		draw_line(commands, point_at(i), point_at(i + 1), LINE_THICKNESS, color);
+	}
+}
+
+// Walls and AI ///////////////
+const WALL_NORMAL_LENGTH: f32 = 12.0;
+
+fn draw_wall_normals(
+	mut commands: Commands,
+	overlay: Res<DebugOverlay>,
+	wall_query: Query<(&physics::CollisionSource<physics::WallCollidable>, &physics::WallInsideDirection, Option<&GlobalTransform>)>,
+) {
+	if !overlay.enabled {
+		return;
+	}
+
+	let color = Color::hex(physics::WallCollidable::COLOR).unwrap();
+	for (source, inside_direction, maybe_transform) in wall_query.iter() {
+		let (collider, normal) = match maybe_transform.map(GlobalTransform::compute_transform) {
+			Some(transform) => (
+				source.0.with_transform(&transform),
+				collapse_vec3(transform.rotation * expand_vec2(inside_direction.0)),
+			),
+			None => (source.0.clone(), inside_direction.0),
+		};
+		if let physics::Collider::LineSegment(point1, point2) = collider {
+			let middle = (point1 + point2) / 2.0;
+			draw_line(&mut commands, middle, middle + normal * WALL_NORMAL_LENGTH, LINE_THICKNESS, color);
+		}
+	}
+}
+
+/// Height above the enemy the state is written at
+const AI_LABEL_HEIGHT: f32 = 44.0;
+
+fn draw_enemy_ai(
+	mut commands: Commands,
+	overlay: Res<DebugOverlay>,
+	enemy_query: Query<(
+		&Transform,
+		&enemy::AIGeneralState,
+		&enemy::EnemyHealth,
+		Option<&enemy::AIPeriodicCharge>,
+		Option<&enemy::AIRotateAround>,
+		Option<&enemy::AIShielded>,
+		Option<&enemy::AITeleporter>,
+		Option<&enemy::AISummoner>,
+		Option<&enemy::AITurret>,
+	), With<enemy::EnemyMarker>>,
+) {
+	if !overlay.enabled {
+		return;
+	}
+
+	for (
+		transform, general, health,
+		maybe_charge, maybe_rotate, maybe_shielded, maybe_teleporter, maybe_summoner, maybe_turret,
+	) in enemy_query.iter() {
+		let position = collapse_vec3(transform.translation);
+		let mut view_color = Color::WHITE;
+		view_color.set_a(if general.has_noticed_player() { 0.5 } else { 0.2 });
+		draw_circle(&mut commands, position, general.view_radius(), view_color);
+
+		let mut lines = vec![format!(
+			"{} {}/{}",
+			if general.has_noticed_player() { "noticed" } else { "idle" },
+			health.0,
+			health.1,
+		)];
+		if let Some(charge) = maybe_charge {
+			lines.push(format!("charge: {}", if charge.is_charging { "charging" } else { "waiting" }));
+		}
+		if let Some(rotate) = maybe_rotate {
+			lines.push(format!("rotate: {}", if rotate.is_charging { "charging" } else { "circling" }));
+		}
+		if let Some(shielded) = maybe_shielded {
+			lines.push(format!("shield: facing {:.1}, {:.1}", shielded.facing.x, shielded.facing.y));
+		}
+		if let Some(teleporter) = maybe_teleporter {
+			lines.push(format!("teleport: {}", if teleporter.is_winding_up { "winding up" } else { "waiting" }));
+		}
+		if let Some(summoner) = maybe_summoner {
+			lines.push(format!("summon: {} pending", summoner.pending_summons));
+		}
+		if let Some(turret) = maybe_turret {
+			lines.push(format!("turret: {} shots left", turret.shots_remaining));
+		}
+
+		commands
+			.spawn_bundle(SpatialBundle {
+				transform: Transform::from_translation(transform.translation),
+				..default()
+			})
+			.insert(DebugShape)
+			.with_children(|parent| {
+				parent
+					.spawn_bundle(Text2dBundle {
+						text: Text::from_section(
+							lines.join("\n"),
+							TextStyle {
+								font: overlay.font.clone(),
+								font_size: 8.0,
+								color: Color::WHITE,
+							},
+						).with_alignment(TextAlignment::CENTER),
+						..default()
+					})
+					.insert(sprite::SpriteOffset(Vec3::Y * AI_LABEL_HEIGHT))
+					.insert(sprite::SpriteLayer::Overlay)
+					.insert(sprite::FacingSpriteMarker);
+			});
+	}
+}
+
+// Collision counts ///////////////
+#[derive(Component, Debug)]
+struct CollisionCountText;
+
+fn setup_debug_overlay(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut overlay: ResMut<DebugOverlay>,
+) {
+	overlay.font = asset_server.load("font/Mechanical-g5Y5.otf");
+	commands
+		.spawn_bundle(TextBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					top: Val::Px(4.0),
+					left: Val::Px(4.0),
+					..default()
+				},
+				..default()
+			},
+			text: Text::from_section("", TextStyle {
+				font: overlay.font.clone(),
+				font_size: 16.0,
+				color: Color::WHITE,
+			}),
+			visibility: Visibility { is_visible: false },
+			..default()
+		})
+		.insert(CollisionCountText);
+}
+
+fn update_collision_counts(
+	overlay: Res<DebugOverlay>,
+	mut text_query: Query<(&mut Text, &mut Visibility), With<CollisionCountText>>,
+) {
+	let (mut text, mut visibility) = text_query.single_mut();
+	visibility.is_visible = overlay.enabled;
+	if overlay.enabled {
+		text.sections[0].value = format!("Active collisions\n{}", overlay.collision_counts.join("\n"));
+	}
+}
@@ -0,0 +1,351 @@
+use super::{enemy, levels, player, spells, tuning, ui, viewport, collapse_vec3};
+use bevy::{
+	input::InputSystem,
+	prelude::*,
+	window::ReceivedCharacter,
+};
+use leafwing_input_manager::plugin::InputManagerSystem;
+
+/// A console for testing, so late rooms can be reached without playing through the early ones.
+/// Opened with the backquote key. Commands go through the same events the game itself uses where there are some.
+/// Only compiled in with the dev-console feature.
+pub struct DevConsolePlugin;
+
+impl Plugin for DevConsolePlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.insert_resource(DevConsole::default())
+			.add_startup_system(setup_dev_console)
+			.add_system_to_stage(
+				CoreStage::PreUpdate,
+				update_console_input
+					.after(InputSystem)
+					.before(InputManagerSystem::Update)
+			)
+			.add_system(run_console_commands)
+			.add_system(update_infinite_mana.after(run_console_commands).after(player::update_spell_casting))
+			.add_system(update_console_text.after(run_console_commands));
+	}
+}
+
+/// Resource
+#[derive(Debug, Default)]
+struct DevConsole {
+	open: bool,
+	input: String,
+	/// Entered lines waiting to be run
+	pending: Vec<String>,
+	/// What's been entered and the replies, oldest first
+	log: Vec<String>,
+	infinite_mana: bool,
+}
+
+impl DevConsole {
+	fn print(&mut self, line: impl Into<String>) {
+		self.log.push(line.into());
+		if self.log.len() > MAX_LOG_LINES {
+			self.log.remove(0);
+		}
+	}
+}
+
+const TOGGLE_KEY: KeyCode = KeyCode::Grave;
+const MAX_LOG_LINES: usize = 10;
+const HELP: &str = "room <n>, spawn <enemy> <x> <y>, give rune <name|all>, give staff, heal, mana [inf], god, cast <runes...>";
+
+// Input ///////////////
+/// Runs before the game reads any input, so that typing doesn't also move the player around or open menus
+fn update_console_input(
+	mut console: ResMut<DevConsole>,
+	mut keys: ResMut<Input<KeyCode>>,
+	mut mouse_buttons: ResMut<Input<MouseButton>>,
+	mut characters: EventReader<ReceivedCharacter>,
+) {
+	let was_open = console.open;
+	if keys.just_pressed(TOGGLE_KEY) || (was_open && keys.just_pressed(KeyCode::Escape)) {
+		console.open = !console.open;
+		console.input.clear();
+	}
+
+	for character in characters.iter() {
+		// The toggle key types a character too
+		if console.open && was_open && !character.char.is_control() && character.char != '`' {
+			console.input.push(character.char);
+		}
+	}
+
+	if !(console.open || was_open) {
+		return;
+	}
+
+	if keys.just_pressed(KeyCode::Back) {
+		console.input.pop();
+	}
+	if keys.just_pressed(KeyCode::Return) {
+		let line = std::mem::take(&mut console.input);
+		if !line.trim().is_empty() {
+			console.pending.push(line);
+		}
+	}
+
+	let pressed_keys: Vec<KeyCode> = keys.get_pressed().copied().collect();
+	for key in pressed_keys {
+		keys.reset(key);
+	}
+	let pressed_buttons: Vec<MouseButton> = mouse_buttons.get_pressed().copied().collect();
+	for button in pressed_buttons {
+		mouse_buttons.reset(button);
+	}
+}
+
+// Commands ///////////////
+enum ConsoleCommand {
+	Room(usize),
+	Spawn(Box<enemy::EnemySpawnInfo>),
+	/// None for all of them
+	GiveRune(Option<spells::Rune>),
+	GiveStaff,
+	Heal,
+	RefillMana,
+	ToggleInfiniteMana,
+	ToggleGodMode,
+	Cast(Vec<spells::Rune>),
+	Help,
+}
+
+fn parse_rune(name: &str) -> Result<spells::Rune, String> {
+	use spells::{Rune, SpellElement, SpellShape};
+	match name {
+		"fire" => Ok(Rune::ElementRune(SpellElement::Fire)),
+		"water" => Ok(Rune::ElementRune(SpellElement::Water)),
+		"earth" => Ok(Rune::ElementRune(SpellElement::Earth)),
+		"air" => Ok(Rune::ElementRune(SpellElement::Air)),
+		"orb" => Ok(Rune::ShapeRune(SpellShape::Orb)),
+		"line" => Ok(Rune::ShapeRune(SpellShape::Line)),
+		"burst" => Ok(Rune::ShapeRune(SpellShape::Burst)),
+		"scatter" => Ok(Rune::ShapeRune(SpellShape::Scatter)),
+		_ => Err(format!("unknown rune {}", name)),
+	}
+}
+
+/// Enemies come out the same as the first room they show up in
+fn parse_enemy(name: &str, x: f32, y: f32, tuning: &tuning::AITuning) -> Result<enemy::EnemySpawnInfo, String> {
+	use enemy::{EnemySpawnInfo, AIPeriodicCharge, AIRotateAround, AIShielded, AITeleporter, AISummoner, AITurret};
+	match name {
+		"flame" => Ok(EnemySpawnInfo::flame(x, y)),
+		"spiky" => Ok(EnemySpawnInfo::spiky(AIPeriodicCharge::from_tuning(tuning), 40, 2, x, y)),
+		"eye" => Ok(EnemySpawnInfo::eye(AIRotateAround::from_tuning(tuning), 100, 3, x, y)),
+		"shielded" => Ok(EnemySpawnInfo::shielded(AIShielded::from_tuning(tuning), 60, 2, x, y)),
+		"teleporter" => Ok(EnemySpawnInfo::teleporter(AITeleporter::from_tuning(tuning), 40, 2, x, y)),
+		"summoner" => Ok(EnemySpawnInfo::summoner(AISummoner::from_tuning(tuning), 60, 1, x, y)),
+		"turret" => Ok(EnemySpawnInfo::turret(AITurret::from_tuning(tuning), 40, 1, x, y)),
+		_ => Err(format!("unknown enemy {}", name)),
+	}
+}
+
+fn parse_number<T: std::str::FromStr>(word: Option<&&str>) -> Result<T, String> {
+	match word {
+		Some(word) => word.parse().map_err(|_| format!("{} isn't a number", word)),
+		None => Err("missing a number".to_string()),
+	}
+}
+
+fn parse_command(line: &str, tuning: &tuning::Tuning) -> Result<ConsoleCommand, String> {
+	let line = line.to_lowercase();
+	let words: Vec<&str> = line.split_whitespace().collect();
+	match words.as_slice() {
+		["room", ..] => Ok(ConsoleCommand::Room(parse_number(words.get(1))?)),
+		["spawn", name, ..] => parse_enemy(
+			name,
+			parse_number(words.get(2))?,
+			parse_number(words.get(3))?,
+			&tuning.ai,
+		).map(|info| ConsoleCommand::Spawn(Box::new(info))),
+		["give", "rune", "all"] => Ok(ConsoleCommand::GiveRune(None)),
+		["give", "rune", name] => parse_rune(name).map(|rune| ConsoleCommand::GiveRune(Some(rune))),
+		["give", "staff"] => Ok(ConsoleCommand::GiveStaff),
+		["heal"] => Ok(ConsoleCommand::Heal),
+		["mana"] => Ok(ConsoleCommand::RefillMana),
+		["mana", "inf"] => Ok(ConsoleCommand::ToggleInfiniteMana),
+		["god"] => Ok(ConsoleCommand::ToggleGodMode),
+		["cast", runes @ ..] if !runes.is_empty() => runes.iter()
+			.map(|name| parse_rune(name))
+			.collect::<Result<Vec<_>, _>>()
+			.map(ConsoleCommand::Cast),
+		["help"] => Ok(ConsoleCommand::Help),
+		_ => Err(format!("unknown command; try {}", HELP)),
+	}
+}
+
+fn run_console_commands(
+	mut commands: Commands,
+	mut console: ResMut<DevConsole>,
+	mut player_query: Query<(Entity, &Transform, &mut player::PlayerHealth, &mut player::PlayerMana, Option<&player::GodMode>), With<player::Player>>,
+	camera_query: Query<(&Camera, &GlobalTransform)>,
+	windows: Res<Windows>,
+	current_room: Res<levels::CurrentRoom>,
+	mut rune_inventory: ResMut<spells::RuneInventory>,
+	mut transition_events: EventWriter<levels::RoomTransitionEvent>,
+	mut create_spell_events: EventWriter<spells::CreateSpellEvent>,
+	mut staff_events: EventWriter<player::GiveStaffEvent>,
+	tuning: Res<tuning::Tuning>,
+) {
+	if console.pending.is_empty() {
+		return;
+	}
+
+	let lines = std::mem::take(&mut console.pending);
+	for line in lines {
+		console.print(format!("> {}", line));
+		let command = match parse_command(&line, &tuning) {
+			Ok(command) => command,
+			Err(error) => {
+				console.print(error);
+				continue;
+			}
+		};
+
+		let (player_entity, player_transform, mut player_health, mut player_mana, maybe_god_mode) = player_query.single_mut();
+		let reply = match command {
+			ConsoleCommand::Room(_) if current_room.0.is_none() => "start a game first".to_string(),
+			ConsoleCommand::Room(target) if target > levels::ENDING_ROOM => {
+				format!("rooms go from 0 to {}", levels::ENDING_ROOM)
+			},
+			ConsoleCommand::Room(target) => {
+				transition_events.send(levels::RoomTransitionEvent(levels::DestinationRoom::TargetRoom {
+					target,
+					respawn: false,
+				}));
+				format!("going to room {}", target)
+			},
+			ConsoleCommand::Spawn(info) => {
+				commands.spawn()
+					.insert(enemy::EnemySpawner::new(vec![
+						enemy::EnemyWave {
+							trigger: enemy::WaveTrigger::Immediate,
+							enemies: vec![*info],
+						},
+					]))
+					.insert(levels::CleanUpOnRoomLoad);
+				"spawned".to_string()
+			},
+			ConsoleCommand::GiveRune(maybe_rune) => {
+				for slot in rune_inventory.0.iter_mut() {
+					if maybe_rune.map_or(true, |rune| rune == slot.rune) {
+						slot.unlocked = true;
+					}
+				}
+				"unlocked".to_string()
+			},
+			ConsoleCommand::GiveStaff => {
+				staff_events.send(player::GiveStaffEvent);
+				"here you go".to_string()
+			},
+			ConsoleCommand::Heal => {
+				player_health.health = player_health.max_health;
+				"healed".to_string()
+			},
+			ConsoleCommand::RefillMana => {
+				player_mana.mana = player_mana.max_mana;
+				"mana refilled".to_string()
+			},
+			ConsoleCommand::ToggleInfiniteMana => {
+				console.infinite_mana = !console.infinite_mana;
+				format!("infinite mana {}", if console.infinite_mana { "on" } else { "off" })
+			},
+			ConsoleCommand::ToggleGodMode => if maybe_god_mode.is_some() {
+				commands.entity(player_entity).remove::<player::GodMode>();
+				"god mode off".to_string()
+			} else {
+				commands.entity(player_entity).insert(player::GodMode);
+				"god mode on".to_string()
+			},
+			ConsoleCommand::Cast(runes) => {
+				let mut queue = spells::RuneCastQueue::new();
+				queue.extend(runes);
+				match queue.generate_spell(&tuning.spells) {
+					Some(spell_data) => {
+						// Towards the mouse, like the player would
+						let (camera, camera_transform) = camera_query.single();
+						let maybe_mouse_position = ui::get_cursor_world_position(
+							&windows,
+							camera,
+							camera_transform,
+							Vec3::new(0.0, 12.0, 0.0),
+							Vec3::Y,
+						);
+						let aim_dir = maybe_mouse_position
+							.and_then(|mouse_pos| collapse_vec3(mouse_pos - player_transform.translation).try_normalize())
+							.unwrap_or(Vec2::X);
+
+						create_spell_events.send(spells::CreateSpellEvent {
+							spell_data,
+							position: collapse_vec3(player_transform.translation) + 24.0 * aim_dir,
+							move_direction: aim_dir,
+						});
+						"cast".to_string()
+					},
+					None => "those runes don't make a spell".to_string(),
+				}
+			},
+			ConsoleCommand::Help => HELP.to_string(),
+		};
+		console.print(reply);
+	}
+}
+
+fn update_infinite_mana(
+	console: Res<DevConsole>,
+	mut player_query: Query<&mut player::PlayerMana, With<player::Player>>,
+) {
+	if console.infinite_mana {
+		let mut player_mana = player_query.single_mut();
+		player_mana.mana = player_mana.max_mana;
+	}
+}
+
+// Display ///////////////
+#[derive(Component, Debug)]
+struct ConsoleText;
+
+fn setup_dev_console(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	commands
+		.spawn_bundle(TextBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					bottom: Val::Px(4.0),
+					left: Val::Px(4.0),
+					..default()
+				},
+				..default()
+			},
+			text: Text::from_section("", TextStyle {
+				font: asset_server.load("font/Mechanical-g5Y5.otf"),
+				font_size: 16.0,
+				color: Color::hex("F2F5E6").unwrap(),
+			}),
+			visibility: Visibility { is_visible: false },
+			..default()
+		})
+		.insert(ConsoleText)
+		.insert(viewport::InMenuLayer);
+}
+
+fn update_console_text(
+	console: Res<DevConsole>,
+	mut text_query: Query<(&mut Text, &mut Visibility), With<ConsoleText>>,
+) {
+	if !console.is_changed() {
+		return;
+	}
+
+	let (mut text, mut visibility) = text_query.single_mut();
+	visibility.is_visible = console.open;
+	let mut lines = console.log.clone();
+	lines.push(format!("> {}_", console.input));
+	text.sections[0].value = lines.join("\n");
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.insert_resource(Difficulty::from_preset(DifficultyPreset::Normal));
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifficultyPreset {
+	Story,
+	Normal,
+	Hard,
+	Custom,
+}
+
+impl DifficultyPreset {
+	pub const SELECTABLE: [DifficultyPreset; 3] = [
+		DifficultyPreset::Story,
+		DifficultyPreset::Normal,
+		DifficultyPreset::Hard,
+	];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			DifficultyPreset::Story => "Story",
+			DifficultyPreset::Normal => "Normal",
+			DifficultyPreset::Hard => "Hard",
+			DifficultyPreset::Custom => "Custom",
+		}
+	}
+}
+
+/// The individual values that can be tuned with the custom sliders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifficultySetting {
+	EnemyHealth,
+	EnemyDamage,
+	InvulnerabilityTime,
+	ManaRegen,
+	SpellCost,
+}
+
+impl DifficultySetting {
+	pub const ALL: [DifficultySetting; 5] = [
+		DifficultySetting::EnemyHealth,
+		DifficultySetting::EnemyDamage,
+		DifficultySetting::InvulnerabilityTime,
+		DifficultySetting::ManaRegen,
+		DifficultySetting::SpellCost,
+	];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			DifficultySetting::EnemyHealth => "Enemy health",
+			DifficultySetting::EnemyDamage => "Enemy damage",
+			DifficultySetting::InvulnerabilityTime => "Invulnerability",
+			DifficultySetting::ManaRegen => "Mana regen",
+			DifficultySetting::SpellCost => "Spell cost",
+		}
+	}
+
+	/// Amount a single slider click changes the multiplier by
+	const STEP: f32 = 0.25;
+	const MIN: f32 = 0.25;
+	const MAX: f32 = 3.0;
+}
+
+/// Resource
+/// Multipliers applied on top of the base balance constants.
+/// 1.0 everywhere is the original balance.
+#[derive(Debug, Clone)]
+pub struct Difficulty {
+	pub preset: DifficultyPreset,
+	pub enemy_health: f32,
+	pub enemy_damage: f32,
+	pub invulnerability_time: f32,
+	pub mana_regen: f32,
+	pub spell_cost: f32,
+}
+
+/// How long the player is invulnerable for after being hit, before scaling
+const BASE_INVULNERABILITY_TIME: f32 = 1.0;
+
+impl Difficulty {
+	pub fn from_preset(preset: DifficultyPreset) -> Self {
+		let (enemy_health, enemy_damage, invulnerability_time, mana_regen, spell_cost) = match preset {
+			DifficultyPreset::Story => (0.5, 0.5, 1.5, 1.5, 0.75),
+			DifficultyPreset::Normal | DifficultyPreset::Custom => (1.0, 1.0, 1.0, 1.0, 1.0),
+			DifficultyPreset::Hard => (1.5, 1.5, 0.75, 0.75, 1.25),
+		};
+
+		Self {
+			preset,
+			enemy_health,
+			enemy_damage,
+			invulnerability_time,
+			mana_regen,
+			spell_cost,
+		}
+	}
+
+	pub fn get(&self, setting: DifficultySetting) -> f32 {
+		match setting {
+			DifficultySetting::EnemyHealth => self.enemy_health,
+			DifficultySetting::EnemyDamage => self.enemy_damage,
+			DifficultySetting::InvulnerabilityTime => self.invulnerability_time,
+			DifficultySetting::ManaRegen => self.mana_regen,
+			DifficultySetting::SpellCost => self.spell_cost,
+		}
+	}
+
+	/// Moves a setting by one slider step; this switches the preset to Custom
+	pub fn step(&mut self, setting: DifficultySetting, increase: bool) {
+		let delta = if increase { DifficultySetting::STEP } else { -DifficultySetting::STEP };
+		let value = match setting {
+			DifficultySetting::EnemyHealth => &mut self.enemy_health,
+			DifficultySetting::EnemyDamage => &mut self.enemy_damage,
+			DifficultySetting::InvulnerabilityTime => &mut self.invulnerability_time,
+			DifficultySetting::ManaRegen => &mut self.mana_regen,
+			DifficultySetting::SpellCost => &mut self.spell_cost,
+		};
+		*value = (*value + delta).clamp(DifficultySetting::MIN, DifficultySetting::MAX);
+		self.preset = DifficultyPreset::Custom;
+	}
+
+	pub fn scale_enemy_health(&self, health: i32) -> i32 {
+		((health as f32 * self.enemy_health).round() as i32).max(1)
+	}
+
+	/// Damage that was nonzero stays nonzero, so nothing becomes harmless
+	pub fn scale_enemy_damage(&self, damage: i32) -> i32 {
+		if damage <= 0 {
+			return damage;
+		}
+		((damage as f32 * self.enemy_damage).round() as i32).max(1)
+	}
+
+	pub fn get_invulnerability_duration(&self) -> Duration {
+		Duration::from_secs_f32(BASE_INVULNERABILITY_TIME * self.invulnerability_time)
+	}
+
+	pub fn scale_spell_cost(&self, cost: i32) -> i32 {
+		if cost <= 0 {
+			return cost;
+		}
+		((cost as f32 * self.spell_cost).round() as i32).max(1)
+	}
+
+	/// One line summary, for the ending screen
+	pub fn describe(&self) -> String {
+		match self.preset {
+			DifficultyPreset::Custom => format!(
+				"Custom (health x{:.2}, damage x{:.2}, invuln. x{:.2}, regen x{:.2}, cost x{:.2})",
+				self.enemy_health,
+				self.enemy_damage,
+				self.invulnerability_time,
+				self.mana_regen,
+				self.spell_cost,
+			),
+			preset => preset.name().to_string(),
+		}
+	}
+}
@@ -4,8 +4,8 @@ use bevy::{
 	utils::{Duration, HashMap},
 };
 use bevy_turborand::*;
-use std::f32::consts::PI;
-use super::{player, physics, ui, spells, collapse_vec3, expand_vec2, levels};
+use std::collections::VecDeque;
+use super::{player, physics, spells, sprite, game_state, sound, tuning, collapse_vec3, expand_vec2, levels, difficulty, room_layout};
 
 pub struct EnemyPlugin;
 
@@ -13,18 +13,35 @@ impl Plugin for EnemyPlugin {
 	fn build(&self, app: &mut App) {
 		app
 			.add_startup_system_to_stage(StartupStage::PreStartup, load_enemy_sprites)
-			.add_system(enemy_ai_general_update)
-			.add_system_to_stage(CoreStage::PreUpdate, knockback_pre_update)
+			.add_system(enemy_ai_general_update.with_run_criteria(game_state::is_world_running))
+			.add_system_to_stage(CoreStage::PreUpdate, knockback_pre_update.with_run_criteria(game_state::is_world_running))
 			.add_system(
-				knockback_post_update
+				knockback_post_update.with_run_criteria(game_state::is_world_running)
 					.before(physics::update_movement)
 					.after(spells::process_spell_enemy_collisions)
 			)
-			.add_system(update_vulnerability)
-			.add_system(do_enemy_ai::<NoAI>.before(knockback_post_update))
-			.add_system(do_enemy_ai::<AIPeriodicCharge>.before(knockback_post_update))
-			.add_system(do_enemy_ai::<AIRotateAround>.before(knockback_post_update))
-			.add_system(clean_dead_enemies);
+			.add_system(update_vulnerability.with_run_criteria(game_state::is_world_running))
+			.add_system(do_enemy_ai::<NoAI>.with_run_criteria(game_state::is_world_running).before(knockback_post_update))
+			.add_system(do_enemy_ai::<AIPeriodicCharge>.with_run_criteria(game_state::is_world_running).before(knockback_post_update))
+			.add_system(do_enemy_ai::<AIRotateAround>.with_run_criteria(game_state::is_world_running).before(knockback_post_update))
+			.add_system(do_enemy_ai::<AIShielded>.with_run_criteria(game_state::is_world_running).before(knockback_post_update))
+			.add_system(fit_teleporters_to_room.before(do_enemy_ai::<AITeleporter>))
+			.add_system(do_enemy_ai::<AITeleporter>.with_run_criteria(game_state::is_world_running).before(knockback_post_update))
+			.add_system(do_enemy_ai::<AISummoner>.with_run_criteria(game_state::is_world_running).before(knockback_post_update))
+			.add_system(do_enemy_ai::<AITurret>.with_run_criteria(game_state::is_world_running).before(knockback_post_update))
+			.add_system(update_shield_sprites.after(do_enemy_ai::<AIShielded>))
+			.add_system(do_summons.after(do_enemy_ai::<AISummoner>))
+			.add_system(fire_turret_shots.after(do_enemy_ai::<AITurret>))
+			.add_system(update_enemy_projectiles.with_run_criteria(game_state::is_world_running))
+			.add_system(apply_difficulty_to_enemy_health)
+			.add_system(update_enemy_spawners.with_run_criteria(game_state::is_world_running))
+			.add_system(start_enemy_deaths)
+			.add_system(update_dying_enemies
+				.with_run_criteria(game_state::is_world_running)
+				// Squashing takes over from the shadow's usual size
+				.after(sprite::update_shadows)
+			)
+			.add_system(update_enemy_animations.before(sprite::update_animators));
 	}	
 }
 
@@ -48,6 +65,7 @@ pub struct EnemyBundle<T: EnemyAIState> {
 	ai_state: T,
 	ai_data: AIGeneralState,
 	health: EnemyHealth,
+	base_health: EnemyBaseHealth,
 	collide_damage: DamagePlayerComponent,
 	speed: physics::Speed,
 	vulnerability: EnemyVulnerability,
@@ -63,23 +81,6 @@ pub struct EnemyBundle<T: EnemyAIState> {
 }
 
 impl<T: EnemyAIState> EnemyBundle<T> {
-	pub fn new(
-		max_health: i32, 
-		contact_damage: i32,
-		collider: physics::Collider, 
-		spatial: SpatialBundle, 
-		global_rng: &mut GlobalRng,
-	) -> Self {
-		EnemyBundle::<T>::with_state(
-			T::default(),
-			max_health, 
-			contact_damage,
-			1.0,
-			collider, 
-			spatial, 
-			global_rng,
-		)
-	}
 	pub fn with_state(
 		ai_state: T,
 		max_health: i32, 
@@ -97,6 +98,7 @@ impl<T: EnemyAIState> EnemyBundle<T> {
 				view_radius: 130.0,
 			},
 			health: EnemyHealth(max_health, max_health),
+			base_health: EnemyBaseHealth(max_health),
 			collide_damage: DamagePlayerComponent(contact_damage),
 			speed: physics::Speed(Vec2::ZERO),
 			vulnerability: EnemyVulnerability {
@@ -127,12 +129,7 @@ pub struct EnemyKnockbackComponent(pub Vec2, f32);
 fn knockback_pre_update(
 	mut query: Query<(&mut EnemyKnockbackComponent, &mut physics::Speed)>,
 	time: Res<Time>,
-    spell_ui_active: Res<ui::SpellUiActive>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
-	
 	let base = 0.1f32;
 	let decay_factor = base.powf(time.delta_seconds());
 	
@@ -145,11 +142,7 @@ fn knockback_pre_update(
 
 fn knockback_post_update(
 	mut query: Query<(&EnemyKnockbackComponent, &mut physics::Speed)>,
-    spell_ui_active: Res<ui::SpellUiActive>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
 	for (knockback, mut speed) in query.iter_mut() {
 		speed.0 += knockback.0 * knockback.1;
 	}
@@ -162,47 +155,63 @@ pub struct AIGeneralState {
 	view_radius: f32,
 }
 
+impl AIGeneralState {
+	pub fn has_noticed_player(&self) -> bool {
+		self.has_noticed_player
+	}
+
+	#[cfg(feature = "debug-overlay")]
+	pub fn view_radius(&self) -> f32 {
+		self.view_radius
+	}
+}
+
 #[derive(Component, Debug)]
 pub struct EnemyHealth(pub i32, pub i32);
+/// Max health before difficulty scaling
+#[derive(Component, Debug)]
+pub struct EnemyBaseHealth(pub i32);
 #[derive(Component, Debug)]
 pub struct EnemyVulnerability {
 	pub tangible: bool,
 	pub hit_timer: Timer,
 }
 
-const FLICKER_TIME: f32 = 0.14;
+/// Keeps max health in line with the difficulty, including when it's changed mid-fight.
+/// Current health is scaled proportionally.
+fn apply_difficulty_to_enemy_health(
+	mut query: Query<(&EnemyBaseHealth, &mut EnemyHealth), Without<EnemyDying>>,
+	difficulty: Res<difficulty::Difficulty>,
+) {
+	for (base_health, mut health) in query.iter_mut() {
+		let new_max = difficulty.scale_enemy_health(base_health.0);
+		if new_max == health.1 {
+			continue;
+		}
+		
+		health.0 = ((health.0 as f32 * new_max as f32 / health.1 as f32).ceil() as i32).max(1);
+		health.1 = new_max;
+	}
+}
+
 // does hit timer and tangibility updates
 fn update_vulnerability (
-	mut query: Query<(&mut Visibility, &mut EnemyVulnerability), With<EnemyMarker>>,
+	mut query: Query<&mut EnemyVulnerability, (With<EnemyMarker>, Without<EnemyDying>)>,
 	time: Res<Time>,
-    spell_ui_active: Res<ui::SpellUiActive>, 
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
-	
-	let current_time = time.time_since_startup().as_secs_f32();
-	let should_flicker = (current_time % (2.0 * FLICKER_TIME)) < FLICKER_TIME;
-	
-	for (mut visibility, mut vulnerability) in query.iter_mut() {
+	for mut vulnerability in query.iter_mut() {
 		if !vulnerability.tangible {
 			vulnerability.hit_timer.tick(time.delta());
 			vulnerability.tangible = vulnerability.hit_timer.finished();
 		}
-		
-		visibility.is_visible = vulnerability.tangible || should_flicker;
 	}
 }
 
 // General systems /////////////////////////////
 fn enemy_ai_general_update(
-	mut query: Query<(&mut AIGeneralState, &Transform, &EnemyHealth), Without<player::Player>>,
+	mut query: Query<(&mut AIGeneralState, &Transform, &EnemyHealth), (Without<player::Player>, Without<EnemyDying>)>,
 	player_query: Query<&Transform, With<player::Player>>,
-    spell_ui_active: Res<ui::SpellUiActive>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
 	let player_pos = player_query.single().translation;
 	
 	for (mut state, transform, health) in query.iter_mut() {
@@ -217,15 +226,10 @@ fn enemy_ai_general_update(
 }
 
 fn do_enemy_ai<T: EnemyAIState>(
-	mut query: Query<(&mut T, &AIGeneralState, &mut physics::Speed, &mut Transform, &mut RngComponent), Without<player::Player>>,
+	mut query: Query<(&mut T, &AIGeneralState, &mut physics::Speed, &mut Transform, &mut RngComponent), (Without<player::Player>, Without<EnemyDying>)>,
 	player_query: Query<&Transform, With<player::Player>>,
 	time: Res<Time>,
-    spell_ui_active: Res<ui::SpellUiActive>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
-	
 	let player_transform = player_query.single();
 	
 	for (mut state, general_data, mut speed, mut transform, mut rng) in query.iter_mut() {
@@ -240,20 +244,232 @@ fn do_enemy_ai<T: EnemyAIState>(
 	}
 }
 
-fn clean_dead_enemies(
+// Death handling //////////////////////////////////////////////////
+
+/// Enemies stick around in this state for a bit after being defeated so they can fade out.
+/// They still count as enemies (so gates stay closed) until they are fully removed.
+#[derive(Component)]
+pub struct EnemyDying(Timer);
+const DEATH_ANIMATION_TIME: f32 = 0.5;
+
+/// Things that happen when an enemy is defeated
+#[derive(Component, Clone, Default)]
+pub struct OnDeathEffects(pub Vec<OnDeathEffect>);
+#[derive(Clone)]
+pub enum OnDeathEffect {
+	/// Casts the given spell where the enemy died
+	Explode(spells::SpellData),
+	/// Spawns the given enemies; their positions are relative to where this one died
+	Split(Vec<EnemySpawnInfo>),
+}
+
+fn start_enemy_deaths(
+	mut commands: Commands,
+	mut query: Query<(
+		Entity,
+		&EnemyHealth,
+		&Transform,
+		&mut physics::Speed,
+		&mut EnemyVulnerability,
+		&mut sprite::SpriteTint,
+		Option<&DropTable>,
+		Option<&OnDeathEffects>,
+		Option<&SpawnedBy>,
+		&mut RngComponent,
+	), Without<EnemyDying>>,
+	mut create_spell_events: EventWriter<spells::CreateSpellEvent>,
+	rune_inventory: Res<spells::RuneInventory>,
+	level_textures: Res<levels::LevelSprites>,
+	enemy_textures: Res<EnemySprites>,
+	spell_textures: Res<spells::AllSpellSprites>,
+	mut global_rng: ResMut<GlobalRng>,
+	mut sound_events: EventWriter<sound::PlaySoundEvent>,
+	tuning: Res<tuning::Tuning>,
+) {
+	for (
+		e, health, transform, mut speed, mut vulnerability, mut tint,
+		maybe_drops, maybe_effects, maybe_spawned_by, mut rng
+	) in query.iter_mut() {
+		if health.0 > 0 {
+			continue;
+		}
+		
+		// Stop being tangible and colliding with things (except walls)
+		commands.entity(e)
+			.insert(EnemyDying(Timer::from_seconds(DEATH_ANIMATION_TIME, false)))
+			.insert(physics::ColliderActive::<physics::InteractsWithEnemies>::new(false))
+			.insert(physics::ColliderActive::<physics::InteractsWithPlayer>::new(false))
+			.insert(physics::ColliderActive::<physics::TakesSpace>::new(false));
+		vulnerability.tangible = false;
+		tint.push(sprite::Tint::new(
+			sprite::TintSource::Death,
+			sprite::TintEffect::Opacity(0.0),
+			sprite::TintEnvelope::FadeIn,
+		).with_duration(DEATH_ANIMATION_TIME));
+		speed.0 = Vec2::ZERO;
+		sound_events.send(sound::PlaySoundEvent(sound::SoundEffect::EnemyDeath));
+		
+		let position = collapse_vec3(transform.translation);
+		
+		if let Some(drops) = maybe_drops {
+			drops.spawn_drops(
+				&mut commands,
+				position,
+				&mut rng,
+				&rune_inventory,
+				&level_textures,
+				&tuning.player,
+			);
+		}
+		
+		if let Some(effects) = maybe_effects {
+			for effect in effects.0.iter() {
+				match effect {
+					OnDeathEffect::Explode(spell_data) => {
+						create_spell_events.send(spells::CreateSpellEvent {
+							spell_data: spell_data.clone(),
+							position,
+							move_direction: Vec2::ZERO,
+						});
+					},
+					OnDeathEffect::Split(enemies) => {
+						for info in enemies.iter() {
+							let split_info = EnemySpawnInfo {
+								position: position + info.position,
+								..info.clone()
+							};
+							let split_enemy = spawn_enemy(
+								&mut commands,
+								&split_info,
+								&enemy_textures,
+								&spell_textures,
+								&mut global_rng,
+							);
+							// Make sure waves wait for these too
+							if let Some(spawned_by) = maybe_spawned_by {
+								commands.entity(split_enemy).insert(SpawnedBy(spawned_by.0));
+							}
+						}
+					},
+				}
+			}
+		}
+	}
+}
+
+/// Fades out and squashes dying enemies, then despawns them
+fn update_dying_enemies(
 	mut commands: Commands,
-	query: Query<(Entity, &EnemyHealth)>
+	mut query: Query<(Entity, &mut EnemyDying, &Children)>,
+	mut sprite_query: Query<&mut Transform, With<TextureAtlasSprite>>,
+	time: Res<Time>,
 ) {
-	for (e, health) in query.iter() {
-		if health.0 <= 0 {
+	for (e, mut dying, children) in query.iter_mut() {
+		dying.0.tick(time.delta());
+		if dying.0.finished() {
 			commands.get_or_spawn(e).despawn_recursive();
+			continue;
+		}
+		
+		let remaining = 1.0 - dying.0.percent();
+		for child in children.iter() {
+			if let Ok(mut transform) = sprite_query.get_mut(*child) {
+				transform.scale = Vec3::new(1.0 + 0.5 * (1.0 - remaining), remaining, 1.0);
+			}
+		}
+	}
+}
+
+/// Enemies only have a few clips to pick from
+fn update_enemy_animations(
+	query: Query<(&EnemyVulnerability, Option<&EnemyDying>, &Children), With<EnemyMarker>>,
+	mut animator_query: Query<&mut sprite::Animator>,
+) {
+	for (vulnerability, maybe_dying, children) in query.iter() {
+		let clip = if maybe_dying.is_some() {
+			"death"
+		} else if !vulnerability.tangible {
+			"hurt"
+		} else {
+			"idle"
+		};
+		for child in children.iter() {
+			if let Ok(mut animator) = animator_query.get_mut(*child) {
+				animator.request(clip);
+			}
+		}
+	}
+}
+
+// Loot drops //////////////////////////////////////////////////
+
+/// Drops that can be left behind on death. Each one is rolled independently.
+#[derive(Component, Clone, Default)]
+pub struct DropTable(pub Vec<LootDrop>);
+#[derive(Clone, Copy)]
+pub struct LootDrop {
+	pub item: levels::PickupItem,
+	pub chance: f64,
+}
+
+impl DropTable {
+	pub fn new(drops: &[(levels::PickupItem, f64)]) -> Self {
+		Self(drops.iter()
+			.map(|(item, chance)| LootDrop {
+				item: *item,
+				chance: *chance,
+			})
+			.collect())
+	}
+	
+	fn spawn_drops(
+		&self,
+		commands: &mut Commands,
+		position: Vec2,
+		rng: &mut RngComponent,
+		rune_inventory: &spells::RuneInventory,
+		level_textures: &levels::LevelSprites,
+		tuning: &tuning::PlayerTuning,
+	) {
+		for drop in self.0.iter() {
+			if !rng.chance(drop.chance) {
+				continue;
+			}
+			
+			let interaction = match drop.item {
+				levels::PickupItem::Heart => levels::PlayerInteraction::RestoreHealth(tuning.heart_pickup_health),
+				levels::PickupItem::ManaOrb => levels::PlayerInteraction::RestoreMana(tuning.mana_orb_pickup_mana),
+				levels::PickupItem::RuneScroll => {
+					// Only drop runes that haven't been found yet
+					let locked_runes: Vec<usize> = rune_inventory.0.iter()
+						.enumerate()
+						.filter(|(_, slot)| !slot.unlocked)
+						.map(|(i, _)| i)
+						.collect();
+					if locked_runes.is_empty() {
+						continue;
+					}
+					levels::PlayerInteraction::GiveRune(locked_runes[rng.usize(0..locked_runes.len())])
+				},
+			};
+			
+			// Scatter them a bit so they don't all overlap
+			let offset = Vec2::new(rng.f32_normalized(), rng.f32_normalized()) * 12.0;
+			
+			levels::spawn_pickup(
+				commands,
+				drop.item,
+				interaction,
+				position + offset,
+				level_textures,
+			);
 		}
 	}
 }
 
 // AI types //////////////////////////////////////////////////
 
-#[derive(Component, Default)]
+#[derive(Component, Default, Clone)]
 pub struct NoAI;
 
 impl EnemyAIState for NoAI {
@@ -271,7 +487,7 @@ impl EnemyAIState for NoAI {
 }
 
 // //////////////////////////////////////////////////////////////////////
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct AIPeriodicCharge {
 	pub timer: Timer,
 	pub is_charging: bool,
@@ -318,21 +534,28 @@ impl EnemyAIState for AIPeriodicCharge {
 	}
 }
 
-impl Default for AIPeriodicCharge {
-	fn default() -> Self {
+impl AIPeriodicCharge {
+	pub fn from_tuning(tuning: &tuning::AITuning) -> Self {
+		let tuning = &tuning.periodic_charge;
 		AIPeriodicCharge {
-			timer: Timer::from_seconds(1.0, true),
+			timer: Timer::from_seconds(tuning.charge_period, true),
 			is_charging: true,
-			speed: 160.0,
-			max_dev_angle: PI / 8.0,
+			speed: tuning.speed,
+			max_dev_angle: tuning.max_dev_angle.to_radians(),
 			target_speed: Vec2::ZERO,
 		}
 	}
 }
 
+impl Default for AIPeriodicCharge {
+	fn default() -> Self {
+		Self::from_tuning(&default())
+	}
+}
+
 
 // //////////////////////////////////////////////////////////////////////
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone)]
 pub struct AIRotateAround {
 	pub is_charging: bool,
 	pub rotate_max_speed: f32,
@@ -346,20 +569,25 @@ pub struct AIRotateAround {
 
 impl Default for AIRotateAround {
 	fn default() -> Self {
+		Self::from_tuning(&default())
+	}
+}
+
+impl AIRotateAround {
+	pub fn from_tuning(tuning: &tuning::AITuning) -> Self {
+		let tuning = &tuning.rotate_around;
 		Self {
 			is_charging: false,
-			rotate_max_speed: 100.0,
-			charge_speed: 160.0,
-			rotate_dist: 80.0,
+			rotate_max_speed: tuning.rotate_max_speed,
+			charge_speed: tuning.charge_speed,
+			rotate_dist: tuning.rotate_dist,
 			time_counter: Duration::from_secs(0),
-			rotate_period_coef: std::f32::consts::TAU / 6.0,
-			charge_decide_rate: 0.25,
-			charge_timer: Timer::from_seconds(2.0 * 80.0 / 140.0, false),
+			rotate_period_coef: std::f32::consts::TAU / tuning.rotate_period,
+			charge_decide_rate: tuning.charge_decide_rate,
+			charge_timer: Timer::from_seconds(tuning.charge_time, false),
 		}
 	}
-}
 
-impl AIRotateAround {
 	fn new(rotate_max_speed: f32, rotate_period: f32, rotate_dist: f32, charge_speed: f32, charge_frequency: f32) -> Self {
 		Self {
 			rotate_max_speed,
@@ -430,12 +658,790 @@ impl EnemyAIState for AIRotateAround {
 	}
 }
 
+// //////////////////////////////////////////////////////////////////////
+/// Slowly turns to face the player and walks towards them.
+/// Hits from the front are blocked by its shield (see process_spell_enemy_collisions).
+#[derive(Component, Debug, Clone)]
+pub struct AIShielded {
+	pub walk_speed: f32,
+	/// Maximum turning speed, in radians per second
+	pub turn_rate: f32,
+	/// Hits within this angle of the facing direction are blocked
+	pub shield_half_angle: f32,
+	pub facing: Vec2,
+}
+
+impl Default for AIShielded {
+	fn default() -> Self {
+		Self::from_tuning(&default())
+	}
+}
+
+impl AIShielded {
+	pub fn from_tuning(tuning: &tuning::AITuning) -> Self {
+		let tuning = &tuning.shielded;
+		Self {
+			walk_speed: tuning.walk_speed,
+			turn_rate: tuning.turn_rate.to_radians(),
+			shield_half_angle: tuning.shield_half_angle.to_radians(),
+			facing: Vec2::X,
+		}
+	}
+
+
+	/// Whether something coming from the given direction (relative to the enemy) gets blocked
+	pub fn blocks_hit_from(&self, direction: Vec2) -> bool {
+		match direction.try_normalize() {
+			Some(dir) => dir.dot(self.facing) > self.shield_half_angle.cos(),
+			None => false,
+		}
+	}
+}
+
+impl EnemyAIState for AIShielded {
+	fn update(
+		&mut self,
+		general_state: &AIGeneralState, 
+		speed: &mut physics::Speed, 
+		own_pos: &mut Transform,
+		player_pos: Vec2,
+		time_delta: Duration,
+		_rng: &mut RngComponent,
+	) {
+		if !general_state.has_noticed_player {
+			return;
+		}
+		
+		let target_dir = (player_pos - collapse_vec3(own_pos.translation)).normalize_or_zero();
+		if target_dir != Vec2::ZERO {
+			// Turn towards the player, but only so fast
+			let max_turn = self.turn_rate * time_delta.as_secs_f32();
+			let turn = self.facing.angle_between(target_dir).clamp(-max_turn, max_turn);
+			let (sin, cos) = turn.sin_cos();
+			self.facing = Vec2::new(
+				cos * self.facing.x - sin * self.facing.y,
+				sin * self.facing.x + cos * self.facing.y,
+			).normalize_or_zero();
+		}
+		
+		speed.0 = self.facing * self.walk_speed;
+	}
+}
+
+// //////////////////////////////////////////////////////////////////////
+/// Periodically blinks to a random spot near the player, then lunges at them.
+#[derive(Component, Debug, Clone)]
+pub struct AITeleporter {
+	pub blink_timer: Timer,
+	pub windup_timer: Timer,
+	pub blink_distance: f32,
+	pub lunge_speed: f32,
+	/// Blink destinations are kept inside this rectangle so it doesn't end up outside the room.
+	/// Set from the room's bounds when the teleporter is spawned.
+	pub bounds_min: Vec2,
+	pub bounds_max: Vec2,
+	pub is_winding_up: bool,
+}
+
+impl AITeleporter {
+	pub fn from_tuning(tuning: &tuning::AITuning) -> Self {
+		let tuning = &tuning.teleporter;
+		Self {
+			blink_timer: Timer::from_seconds(tuning.blink_period, true),
+			windup_timer: Timer::from_seconds(tuning.windup_time, false),
+			blink_distance: tuning.blink_distance,
+			lunge_speed: tuning.lunge_speed,
+			bounds_min: Vec2::splat(f32::MIN),
+			bounds_max: Vec2::splat(f32::MAX),
+			is_winding_up: false,
+		}
+	}
+}
+
+impl Default for AITeleporter {
+	fn default() -> Self {
+		Self::from_tuning(&default())
+	}
+}
+
+/// How far inside the room's edges blinks have to land
+const TELEPORTER_WALL_MARGIN: f32 = 16.0;
+
+fn fit_teleporters_to_room(
+	mut query: Query<&mut AITeleporter, Added<AITeleporter>>,
+	room_bounds: Res<room_layout::RoomBounds>,
+) {
+	for mut teleporter in query.iter_mut() {
+		teleporter.bounds_min = room_bounds.min + Vec2::splat(TELEPORTER_WALL_MARGIN);
+		teleporter.bounds_max = room_bounds.max - Vec2::splat(TELEPORTER_WALL_MARGIN);
+	}
+}
+
+impl EnemyAIState for AITeleporter {
+	fn update(
+		&mut self,
+		general_state: &AIGeneralState, 
+		speed: &mut physics::Speed, 
+		own_pos: &mut Transform,
+		player_pos: Vec2,
+		time_delta: Duration,
+		rng: &mut RngComponent,
+	) {
+		if !general_state.has_noticed_player {
+			return;
+		}
+		
+		self.blink_timer.tick(time_delta);
+		if self.blink_timer.just_finished() {
+			let angle = rng.f32() * std::f32::consts::TAU;
+			let target = (player_pos + Vec2::new(angle.cos(), angle.sin()) * self.blink_distance)
+				.clamp(self.bounds_min, self.bounds_max);
+			
+			own_pos.translation = expand_vec2(target);
+			speed.0 = Vec2::ZERO;
+			self.windup_timer.reset();
+			self.is_winding_up = true;
+		} else if self.is_winding_up {
+			self.windup_timer.tick(time_delta);
+			if self.windup_timer.finished() {
+				self.is_winding_up = false;
+				speed.0 = (player_pos - collapse_vec3(own_pos.translation)).normalize_or_zero() * self.lunge_speed;
+			}
+		} else {
+			// Slow down after lunging
+			let base = 0.3f32;
+			speed.0 *= base.powf(time_delta.as_secs_f32());
+		}
+	}
+}
+
+// //////////////////////////////////////////////////////////////////////
+/// Keeps its distance from the player and periodically summons minions.
+/// The actual spawning happens in do_summons.
+#[derive(Component, Clone)]
+pub struct AISummoner {
+	pub summon_timer: Timer,
+	pub summon_count: u32,
+	pub max_minions: usize,
+	pub keep_away_dist: f32,
+	pub move_speed: f32,
+	pub minion: Box<EnemySpawnInfo>,
+	pub pending_summons: u32,
+}
+
+impl AISummoner {
+	pub fn from_tuning(tuning: &tuning::AITuning) -> Self {
+		let summoner = &tuning.summoner;
+		Self {
+			summon_timer: Timer::from_seconds(summoner.summon_period, true),
+			summon_count: summoner.summon_count,
+			max_minions: summoner.max_minions,
+			keep_away_dist: summoner.keep_away_dist,
+			move_speed: summoner.move_speed,
+			minion: Box::new(
+				EnemySpawnInfo::spiky(AIPeriodicCharge::from_tuning(tuning), summoner.minion_health, summoner.minion_damage, 0.0, 0.0)
+					.with_drops(DropTable::default())
+			),
+			pending_summons: 0,
+		}
+	}
+}
+
+impl Default for AISummoner {
+	fn default() -> Self {
+		Self::from_tuning(&default())
+	}
+}
+
+impl EnemyAIState for AISummoner {
+	fn update(
+		&mut self,
+		general_state: &AIGeneralState, 
+		speed: &mut physics::Speed, 
+		own_pos: &mut Transform,
+		player_pos: Vec2,
+		time_delta: Duration,
+		_rng: &mut RngComponent,
+	) {
+		if !general_state.has_noticed_player {
+			return;
+		}
+		
+		let player_dist_vec = player_pos - collapse_vec3(own_pos.translation);
+		let to_player = player_dist_vec.normalize_or_zero();
+		speed.0 = if player_dist_vec.length() < self.keep_away_dist {
+			-to_player * self.move_speed
+		} else {
+			to_player.perp() * self.move_speed * 0.5
+		};
+		
+		self.summon_timer.tick(time_delta);
+		if self.summon_timer.just_finished() {
+			self.pending_summons += self.summon_count;
+		}
+	}
+}
+
+/// Marks which summoner a minion came from
+#[derive(Component)]
+pub struct SummonedBy(pub Entity);
+
+fn do_summons(
+	mut commands: Commands,
+	mut summoner_query: Query<(Entity, &mut AISummoner, &Transform, Option<&SpawnedBy>), Without<EnemyDying>>,
+	minion_query: Query<&SummonedBy, Without<EnemyDying>>,
+	enemy_textures: Res<EnemySprites>,
+	spell_textures: Res<spells::AllSpellSprites>,
+	mut global_rng: ResMut<GlobalRng>,
+) {
+	for (e, mut summoner, transform, maybe_spawned_by) in summoner_query.iter_mut() {
+		if summoner.pending_summons == 0 {
+			continue;
+		}
+		
+		let n_alive = minion_query.iter().filter(|summoned_by| summoned_by.0 == e).count();
+		let n_to_summon = (summoner.pending_summons as usize).min(summoner.max_minions.saturating_sub(n_alive));
+		summoner.pending_summons = 0;
+		
+		for i in 0..n_to_summon {
+			let angle = std::f32::consts::TAU * i as f32 / n_to_summon as f32;
+			let minion_info = EnemySpawnInfo {
+				position: collapse_vec3(transform.translation) + Vec2::new(angle.cos(), angle.sin()) * 20.0,
+				..(*summoner.minion).clone()
+			};
+			let minion = spawn_enemy(
+				&mut commands,
+				&minion_info,
+				&enemy_textures,
+				&spell_textures,
+				&mut global_rng,
+			);
+			commands.entity(minion).insert(SummonedBy(e));
+			// Make sure waves wait for these too
+			if let Some(spawned_by) = maybe_spawned_by {
+				commands.entity(minion).insert(SpawnedBy(spawned_by.0));
+			}
+		}
+	}
+}
+
+// //////////////////////////////////////////////////////////////////////
+/// Never moves, but fires bursts of projectiles at the player.
+/// The projectiles are spawned in fire_turret_shots.
+#[derive(Component, Debug, Clone)]
+pub struct AITurret {
+	pub burst_timer: Timer,
+	pub shot_timer: Timer,
+	pub shots_per_burst: u32,
+	/// Maximum angle each shot deviates from aiming directly at the player
+	pub spread: f32,
+	pub shot_speed: f32,
+	pub shot_damage: i32,
+	pub shots_remaining: u32,
+	/// Velocities of shots that have been fired but not yet spawned
+	pub pending_shots: Vec<Vec2>,
+}
+
+impl AITurret {
+	pub fn from_tuning(tuning: &tuning::AITuning) -> Self {
+		let tuning = &tuning.turret;
+		Self {
+			burst_timer: Timer::from_seconds(tuning.burst_period, true),
+			shot_timer: Timer::from_seconds(tuning.shot_period, true),
+			shots_per_burst: tuning.shots_per_burst,
+			spread: tuning.spread.to_radians(),
+			shot_speed: tuning.shot_speed,
+			shot_damage: tuning.shot_damage,
+			shots_remaining: 0,
+			pending_shots: Vec::new(),
+		}
+	}
+}
+
+impl Default for AITurret {
+	fn default() -> Self {
+		Self::from_tuning(&default())
+	}
+}
+
+impl EnemyAIState for AITurret {
+	fn update(
+		&mut self,
+		general_state: &AIGeneralState, 
+		speed: &mut physics::Speed, 
+		own_pos: &mut Transform,
+		player_pos: Vec2,
+		time_delta: Duration,
+		rng: &mut RngComponent,
+	) {
+		speed.0 = Vec2::ZERO;
+		if !general_state.has_noticed_player {
+			return;
+		}
+		
+		if self.shots_remaining == 0 {
+			self.burst_timer.tick(time_delta);
+			if self.burst_timer.just_finished() {
+				self.shots_remaining = self.shots_per_burst;
+				self.shot_timer.reset();
+			}
+		} else {
+			self.shot_timer.tick(time_delta);
+			if self.shot_timer.just_finished() {
+				let dir_to_player = player_pos - collapse_vec3(own_pos.translation);
+				let angle = rng.f32_normalized() * self.spread;
+				let shot_dir = collapse_vec3(
+					Quat::from_rotation_y(angle) * expand_vec2(dir_to_player)
+				).normalize_or_zero();
+				
+				self.pending_shots.push(shot_dir * self.shot_speed);
+				self.shots_remaining -= 1;
+			}
+		}
+	}
+}
+
+/// Projectiles fired by enemies; they hurt the player and disappear on hitting anything
+#[derive(Component)]
+pub struct EnemyProjectile(Timer);
+
+fn fire_turret_shots(
+	mut commands: Commands,
+	mut query: Query<(&mut AITurret, &Transform), Without<EnemyDying>>,
+	spell_textures: Res<spells::AllSpellSprites>,
+) {
+	for (mut turret, transform) in query.iter_mut() {
+		let shot_damage = turret.shot_damage;
+		for shot_speed in turret.pending_shots.drain(..) {
+			let collider = physics::Collider::Circle {
+				center: Vec2::ZERO,
+				radius: 4.0,
+			};
+			commands.spawn()
+				.insert(EnemyProjectile(Timer::from_seconds(4.0, false)))
+				.insert(DamagePlayerComponent(shot_damage))
+				.insert(physics::Speed(shot_speed))
+				.insert(physics::CollisionSource::<physics::InteractsWithPlayer>::new(collider.clone()))
+				.insert(physics::CollisionRecipient::<physics::WallCollidable>::new(collider))
+				.insert(levels::CleanUpOnRoomLoad)
+				.insert_bundle(SpatialBundle {
+					transform: Transform::from_translation(transform.translation + expand_vec2(shot_speed.normalize_or_zero() * 10.0)),
+					..default()
+				})
+				.insert(sprite::CastsShadow::new(0, 16.0))
+				.with_children(|parent| {
+					parent.spawn_bundle(sprite::SimpleAnimationBundle::new(
+						spell_textures.get_atlas_from_type(spells::SpellElement::Metal, spells::SpellSize::Tiny),
+						16.0,
+						true
+					)).insert(sprite::SpriteLayer::Air);
+				});
+		}
+	}
+}
+
+fn update_enemy_projectiles(
+	mut commands: Commands,
+	mut query: Query<(Entity, &mut EnemyProjectile)>,
+	player_collisions: Res<physics::ActiveCollisions<physics::InteractsWithPlayer>>,
+	wall_collisions: Res<physics::ActiveCollisions<physics::WallCollidable>>,
+	time: Res<Time>,
+) {
+	for (e, mut projectile) in query.iter_mut() {
+		projectile.0.tick(time.delta());
+		
+		let hit_something = player_collisions.iter().any(|collision| collision.source_entity == e)
+			|| wall_collisions.iter().any(|collision| collision.recip_entity == e);
+		
+		if hit_something || projectile.0.finished() {
+			commands.get_or_spawn(e).despawn_recursive();
+		}
+	}
+}
+
+#[derive(Component)]
+pub struct ShieldSpriteMarker;
+
+/// Keeps the shield sprite in front of its owner
+fn update_shield_sprites(
+	query: Query<(&AIShielded, &Children)>,
+	mut shield_query: Query<&mut sprite::SpriteOffset, With<ShieldSpriteMarker>>,
+) {
+	for (shielded, children) in query.iter() {
+		for child in children.iter() {
+			if let Ok(mut offset) = shield_query.get_mut(*child) {
+				offset.0 = expand_vec2(shielded.facing * 12.0) + Vec3::Y * 16.0;
+			}
+		}
+	}
+}
+
+// Spawning //////////////////////////////////////////////////
+
+/// Describes an enemy so that it can be spawned at some later point (e.g. by an EnemySpawner)
+#[derive(Clone)]
+pub struct EnemySpawnInfo {
+	pub ai: EnemyAI,
+	pub sprite: EnemySpriteKind,
+	pub max_health: i32,
+	pub contact_damage: i32,
+	pub knockback_factor: f32,
+	pub radius: f32,
+	pub drops: DropTable,
+	pub on_death: OnDeathEffects,
+	pub position: Vec2,
+}
+
+/// AI state to spawn the enemy with
+#[derive(Clone)]
+pub enum EnemyAI {
+	NoAI(NoAI),
+	PeriodicCharge(AIPeriodicCharge),
+	RotateAround(AIRotateAround),
+	Shielded(AIShielded),
+	Teleporter(AITeleporter),
+	Summoner(AISummoner),
+	Turret(AITurret),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum EnemySpriteKind {
+	Flame,
+	Spiky {
+		reversed: bool,
+	},
+	Eye,
+	Shielded,
+	Teleporter,
+	Summoner,
+	Turret,
+}
+
+impl EnemySpawnInfo {
+	pub fn flame(x: f32, y: f32) -> Self {
+		Self {
+			ai: EnemyAI::NoAI(NoAI),
+			sprite: EnemySpriteKind::Flame,
+			max_health: 1,
+			contact_damage: 1,
+			knockback_factor: 0.0,
+			radius: 8.0,
+			drops: DropTable::default(),
+			on_death: OnDeathEffects::default(),
+			position: Vec2::new(x, y),
+		}
+	}
+	pub fn spiky(ai: AIPeriodicCharge, max_health: i32, contact_damage: i32, x: f32, y: f32) -> Self {
+		Self {
+			ai: EnemyAI::PeriodicCharge(ai),
+			sprite: EnemySpriteKind::Spiky { reversed: false },
+			max_health,
+			contact_damage,
+			knockback_factor: 1.0,
+			radius: 10.0,
+			drops: DropTable::new(&[
+				(levels::PickupItem::Heart, 0.25),
+				(levels::PickupItem::ManaOrb, 0.3),
+				(levels::PickupItem::RuneScroll, 0.05),
+			]),
+			on_death: OnDeathEffects::default(),
+			position: Vec2::new(x, y),
+		}
+	}
+	pub fn eye(ai: AIRotateAround, max_health: i32, contact_damage: i32, x: f32, y: f32) -> Self {
+		Self {
+			ai: EnemyAI::RotateAround(ai),
+			sprite: EnemySpriteKind::Eye,
+			max_health,
+			contact_damage,
+			knockback_factor: 0.5,
+			radius: 8.0,
+			drops: DropTable::new(&[
+				(levels::PickupItem::Heart, 0.6),
+				(levels::PickupItem::ManaOrb, 0.6),
+				(levels::PickupItem::RuneScroll, 0.2),
+			]),
+			on_death: OnDeathEffects::default(),
+			position: Vec2::new(x, y),
+		}
+	}
+	
+	pub fn shielded(ai: AIShielded, max_health: i32, contact_damage: i32, x: f32, y: f32) -> Self {
+		Self {
+			ai: EnemyAI::Shielded(ai),
+			sprite: EnemySpriteKind::Shielded,
+			knockback_factor: 0.3,
+			..Self::spiky(AIPeriodicCharge::default(), max_health, contact_damage, x, y)
+		}
+	}
+	pub fn teleporter(ai: AITeleporter, max_health: i32, contact_damage: i32, x: f32, y: f32) -> Self {
+		Self {
+			ai: EnemyAI::Teleporter(ai),
+			sprite: EnemySpriteKind::Teleporter,
+			..Self::eye(AIRotateAround::default(), max_health, contact_damage, x, y)
+		}
+	}
+	pub fn summoner(ai: AISummoner, max_health: i32, contact_damage: i32, x: f32, y: f32) -> Self {
+		Self {
+			ai: EnemyAI::Summoner(ai),
+			sprite: EnemySpriteKind::Summoner,
+			..Self::eye(AIRotateAround::default(), max_health, contact_damage, x, y)
+		}
+	}
+	pub fn turret(ai: AITurret, max_health: i32, contact_damage: i32, x: f32, y: f32) -> Self {
+		Self {
+			ai: EnemyAI::Turret(ai),
+			sprite: EnemySpriteKind::Turret,
+			knockback_factor: 0.0,
+			..Self::spiky(AIPeriodicCharge::default(), max_health, contact_damage, x, y)
+		}
+	}
+	
+	pub fn with_knockback_factor(mut self, knockback_factor: f32) -> Self {
+		self.knockback_factor = knockback_factor;
+		self
+	}
+	pub fn with_sprite(mut self, sprite: EnemySpriteKind) -> Self {
+		self.sprite = sprite;
+		self
+	}
+	pub fn with_drops(mut self, drops: DropTable) -> Self {
+		self.drops = drops;
+		self
+	}
+	pub fn with_on_death(mut self, effect: OnDeathEffect) -> Self {
+		self.on_death.0.push(effect);
+		self
+	}
+}
+
+/// Spawns an enemy (along with its sprite and shadow) from its description.
+pub fn spawn_enemy(
+	commands: &mut Commands,
+	info: &EnemySpawnInfo,
+	enemy_textures: &EnemySprites,
+	spell_textures: &spells::AllSpellSprites,
+	global_rng: &mut GlobalRng,
+) -> Entity {
+	let collider = physics::Collider::Circle {
+		center: Vec2::ZERO,
+		radius: info.radius,
+	};
+	let spatial = SpatialBundle {
+		transform: Transform::from_translation(expand_vec2(info.position)),
+		..default()
+	};
+	
+	let mut entity_commands = match &info.ai {
+		EnemyAI::NoAI(ai) => commands.spawn_bundle(EnemyBundle::with_state(
+			ai.clone(), info.max_health, info.contact_damage, info.knockback_factor, collider, spatial, global_rng
+		)),
+		EnemyAI::PeriodicCharge(ai) => commands.spawn_bundle(EnemyBundle::with_state(
+			ai.clone(), info.max_health, info.contact_damage, info.knockback_factor, collider, spatial, global_rng
+		)),
+		EnemyAI::RotateAround(ai) => commands.spawn_bundle(EnemyBundle::with_state(
+			ai.clone(), info.max_health, info.contact_damage, info.knockback_factor, collider, spatial, global_rng
+		)),
+		EnemyAI::Shielded(ai) => commands.spawn_bundle(EnemyBundle::with_state(
+			ai.clone(), info.max_health, info.contact_damage, info.knockback_factor, collider, spatial, global_rng
+		)),
+		EnemyAI::Teleporter(ai) => commands.spawn_bundle(EnemyBundle::with_state(
+			ai.clone(), info.max_health, info.contact_damage, info.knockback_factor, collider, spatial, global_rng
+		)),
+		EnemyAI::Summoner(ai) => commands.spawn_bundle(EnemyBundle::with_state(
+			ai.clone(), info.max_health, info.contact_damage, info.knockback_factor, collider, spatial, global_rng
+		)),
+		EnemyAI::Turret(ai) => commands.spawn_bundle(EnemyBundle::with_state(
+			ai.clone(), info.max_health, info.contact_damage, info.knockback_factor, collider, spatial, global_rng
+		)),
+	};
+	
+	// The newer enemy types reuse the existing sprites with a tint
+	let (texture_atlas, animations, reversed, hover, color) = match info.sprite {
+		EnemySpriteKind::Flame => (
+			spell_textures.get_atlas_from_type(spells::SpellElement::Fire, spells::SpellSize::Large),
+			enemy_textures.get_animations("flame"),
+			false,
+			sprite::SpriteHover::new(3.0, 2.0),
+			Color::WHITE,
+		),
+		EnemySpriteKind::Spiky { reversed } => (
+			enemy_textures.get_sprite("spiky"),
+			enemy_textures.get_animations("spiky"),
+			reversed,
+			sprite::SpriteHover::new(1.5, 3.0),
+			Color::WHITE,
+		),
+		EnemySpriteKind::Eye => (
+			enemy_textures.get_sprite("eye"),
+			enemy_textures.get_animations("eye"),
+			false,
+			sprite::SpriteHover::new(1.5, 3.0),
+			Color::WHITE,
+		),
+		EnemySpriteKind::Shielded => (
+			enemy_textures.get_sprite("spiky"),
+			enemy_textures.get_animations("spiky"),
+			false,
+			sprite::SpriteHover::new(2.0, 1.0),
+			Color::rgb(0.6, 0.7, 0.8),
+		),
+		EnemySpriteKind::Teleporter => (
+			enemy_textures.get_sprite("eye"),
+			enemy_textures.get_animations("eye"),
+			true,
+			sprite::SpriteHover::new(0.8, 3.0),
+			Color::rgb(0.8, 0.5, 1.0),
+		),
+		EnemySpriteKind::Summoner => (
+			enemy_textures.get_sprite("eye"),
+			enemy_textures.get_animations("eye"),
+			false,
+			sprite::SpriteHover::new(2.5, 5.0),
+			Color::rgb(0.5, 1.0, 0.6),
+		),
+		EnemySpriteKind::Turret => (
+			enemy_textures.get_sprite("spiky"),
+			enemy_textures.get_animations("spiky"),
+			false,
+			sprite::SpriteHover::new(3.0, 1.0),
+			Color::rgb(1.0, 0.6, 0.4),
+		),
+	};
+	
+	entity_commands
+		.insert(info.drops.clone())
+		.insert(info.on_death.clone())
+		.insert(sprite::CastsShadow::new(2, 20.0))
+		.insert(sprite::SpriteTint::new(color));
+	entity_commands.with_children(|parent| {
+		parent.spawn_bundle(sprite::AnimatedSpriteBundle::new(
+			texture_atlas,
+			animations,
+			20.0,
+			reversed
+		))
+		.insert(TextureAtlasSprite {
+			color,
+			..default()
+		})
+		.insert(hover);
+		
+		// Show which way the shield is facing
+		if let EnemySpriteKind::Shielded = info.sprite {
+			parent.spawn_bundle(sprite::SimpleAnimationBundle::new(
+				spell_textures.get_atlas_from_type(spells::SpellElement::Metal, spells::SpellSize::Small),
+				16.0,
+				true
+			))
+			.insert(ShieldSpriteMarker);
+		}
+	});
+	
+	entity_commands.id()
+}
+
+// Spawners and waves //////////////////////////////////////////////////
+
+/// Spawns enemies in waves. Despawns itself once the last wave has been sent out,
+/// so the gate stays closed as long as any spawner exists.
+#[derive(Component)]
+pub struct EnemySpawner {
+	waves: VecDeque<EnemyWave>,
+}
+impl EnemySpawner {
+	pub fn new(waves: Vec<EnemyWave>) -> Self {
+		Self {
+			waves: waves.into(),
+		}
+	}
+}
+
+pub struct EnemyWave {
+	pub trigger: WaveTrigger,
+	pub enemies: Vec<EnemySpawnInfo>,
+}
+
+/// When the next wave should be spawned.
+pub enum WaveTrigger {
+	Immediate,
+	/// After the given time has passed since the previous wave was spawned
+	OnTimer(Timer),
+	/// Once every enemy from this spawner's previous waves has been defeated
+	OnCleared,
+	/// Same as OnCleared, but waits for the given time after this spawner's enemies have been defeated
+	AfterCleared(Timer),
+}
+
+/// Marks which spawner an enemy came from
+#[derive(Component)]
+pub struct SpawnedBy(pub Entity);
+
+fn update_enemy_spawners(
+	mut commands: Commands,
+	mut spawner_query: Query<(Entity, &mut EnemySpawner)>,
+	spawned_query: Query<&SpawnedBy, With<EnemyMarker>>,
+	time: Res<Time>,
+	enemy_textures: Res<EnemySprites>,
+	spell_textures: Res<spells::AllSpellSprites>,
+	mut global_rng: ResMut<GlobalRng>,
+) {
+	for (spawner_entity, mut spawner) in spawner_query.iter_mut() {
+		let n_alive = spawned_query.iter()
+			.filter(|spawned_by| spawned_by.0 == spawner_entity)
+			.count();
+		
+		// At most one wave per frame, since newly spawned enemies don't show up until next frame
+		let should_spawn = match spawner.waves.front_mut() {
+			Some(wave) => match &mut wave.trigger {
+				WaveTrigger::Immediate => true,
+				WaveTrigger::OnTimer(ref mut timer) => {
+					timer.tick(time.delta());
+					timer.finished()
+				},
+				WaveTrigger::OnCleared => n_alive == 0,
+				WaveTrigger::AfterCleared(ref mut timer) => {
+					if n_alive == 0 {
+						timer.tick(time.delta());
+					}
+					timer.finished()
+				},
+			},
+			None => {
+				commands.get_or_spawn(spawner_entity).despawn_recursive();
+				continue;
+			}
+		};
+		
+		if should_spawn {
+			if let Some(wave) = spawner.waves.pop_front() {
+				for info in wave.enemies.iter() {
+					let enemy = spawn_enemy(
+						&mut commands,
+						info,
+						&enemy_textures,
+						&spell_textures,
+						&mut global_rng,
+					);
+					commands.entity(enemy).insert(SpawnedBy(spawner_entity));
+				}
+			}
+		}
+	}
+}
+
 // Sprite loading
-#[derive(Deref, DerefMut)]
-pub struct EnemySprites(pub HashMap<String, Handle<TextureAtlas>>);
+pub struct EnemySprites {
+	atlases: HashMap<String, Handle<TextureAtlas>>,
+	animations: HashMap<String, Handle<sprite::AnimationSet>>,
+}
 impl EnemySprites {
 	pub fn get_sprite(&self, key: &str) -> Handle<TextureAtlas> {
-		self.0.get(&key.to_string()).expect("invalid enemy sprite key encountered").clone()
+		self.atlases.get(&key.to_string()).expect("invalid enemy sprite key encountered").clone()
+	}
+	
+	pub fn get_animations(&self, key: &str) -> Handle<sprite::AnimationSet> {
+		self.animations.get(&key.to_string()).expect("invalid enemy animation key encountered").clone()
 	}
 }
 
@@ -462,5 +1468,13 @@ fn load_enemy_sprites(
 		})
 		.collect();
 	
-	commands.insert_resource(EnemySprites(handles));
+	// Flames use the spell sprites, but still need their own animations
+	let animations = ["spiky", "eye", "flame"].iter()
+		.map(|key| (key.to_string(), asset_server.load(&format!("animations/{}.anim.ron", key))))
+		.collect();
+	
+	commands.insert_resource(EnemySprites {
+		atlases: handles,
+		animations,
+	});
 }
@@ -0,0 +1,251 @@
+use super::{player, game_state, levels, sprite, expand_vec2};
+use bevy::prelude::*;
+use bevy_turborand::*;
+
+/// Makes hits feel like hits: screen shake, a short freeze on heavy hits, and damage numbers.
+pub struct FeedbackPlugin;
+
+impl Plugin for FeedbackPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.insert_resource(FeedbackSettings::default())
+			.insert_resource(CameraShake::default())
+			.insert_resource(HitStop::default())
+			.add_event::<DamageEvent>()
+			.add_startup_system(load_damage_number_font)
+			.add_system_to_stage(CoreStage::PreUpdate, update_hit_stop)
+			.add_system_to_stage(CoreStage::PostUpdate, process_damage_events)
+			.add_system_to_stage(
+				CoreStage::PostUpdate,
+				update_camera_shake
+					.with_run_criteria(game_state::is_world_running)
+					.after(process_damage_events)
+					.before(player::update_camera)
+			)
+			.add_system(update_damage_numbers.with_run_criteria(game_state::is_world_running));
+	}
+}
+
+/// Resource
+/// Knobs for how strong the feedback is
+#[derive(Debug, Clone)]
+pub struct FeedbackSettings {
+	/// Can be turned off from the settings menu, for anyone bothered by it
+	pub screen_shake: bool,
+	/// Trauma added per point of damage dealt to enemies
+	enemy_hit_trauma: f32,
+	/// Trauma added per point of damage taken by the player
+	player_hit_trauma: f32,
+	/// How far the camera moves at full trauma, in pixels
+	max_shake_offset: f32,
+	/// Trauma lost per second
+	trauma_decay: f32,
+	/// Hits doing at least this much damage freeze the game for a moment
+	hit_stop_threshold: i32,
+	/// In seconds
+	hit_stop_time: f32,
+	/// In seconds
+	damage_number_lifetime: f32,
+	/// Pixels per second
+	damage_number_rise: f32,
+}
+
+impl Default for FeedbackSettings {
+	fn default() -> Self {
+		Self {
+			screen_shake: true,
+			enemy_hit_trauma: 0.02,
+			player_hit_trauma: 0.08,
+			max_shake_offset: 6.0,
+			trauma_decay: 1.5,
+			hit_stop_threshold: 10,
+			hit_stop_time: 0.08,
+			damage_number_lifetime: 0.8,
+			damage_number_rise: 24.0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageTarget {
+	Enemy,
+	Player,
+}
+
+/// Event for something taking damage
+pub struct DamageEvent {
+	pub position: Vec2,
+	pub amount: i32,
+	pub target: DamageTarget,
+}
+
+fn process_damage_events(
+	mut commands: Commands,
+	mut damage_events: EventReader<DamageEvent>,
+	settings: Res<FeedbackSettings>,
+	mut camera_shake: ResMut<CameraShake>,
+	mut hit_stop: ResMut<HitStop>,
+	font: Res<DamageNumberFont>,
+	mut global_rng: ResMut<GlobalRng>,
+) {
+	for event in damage_events.iter() {
+		let trauma_per_damage = match event.target {
+			DamageTarget::Enemy => settings.enemy_hit_trauma,
+			DamageTarget::Player => settings.player_hit_trauma,
+		};
+		camera_shake.trauma = (camera_shake.trauma + trauma_per_damage * event.amount as f32).min(1.0);
+
+		if event.amount >= settings.hit_stop_threshold {
+			hit_stop.0 = Some(Timer::from_seconds(settings.hit_stop_time, false));
+		}
+
+		if event.target == DamageTarget::Enemy {
+			spawn_damage_number(&mut commands, &font, &settings, &mut global_rng, event.position, event.amount);
+		}
+	}
+}
+
+// Screen shake ///////////////
+/// Resource
+/// Trauma goes up with hits and wears off over time; the shake is based on its square
+#[derive(Debug, Default)]
+pub struct CameraShake {
+	trauma: f32,
+	/// Offset in screen space that update_camera adds on top of the camera position
+	offset: Vec2,
+}
+
+impl CameraShake {
+	pub fn get_offset(&self) -> Vec2 {
+		self.offset
+	}
+}
+
+fn update_camera_shake(
+	mut camera_shake: ResMut<CameraShake>,
+	settings: Res<FeedbackSettings>,
+	mut global_rng: ResMut<GlobalRng>,
+	time: Res<Time>,
+) {
+	camera_shake.trauma = (camera_shake.trauma - settings.trauma_decay * time.delta_seconds()).max(0.0);
+
+	if !settings.screen_shake || camera_shake.trauma <= 0.0 {
+		camera_shake.offset = Vec2::ZERO;
+		return;
+	}
+
+	let strength = camera_shake.trauma * camera_shake.trauma * settings.max_shake_offset;
+	let random_offset = Vec2::new(global_rng.f32() * 2.0 - 1.0, global_rng.f32() * 2.0 - 1.0);
+	// Keep to whole pixels so sprites don't get uneven
+	camera_shake.offset = (random_offset * strength).round();
+}
+
+// Hit-stop ///////////////
+/// Resource
+/// While this has a timer, the world is frozen; see game_state::is_world_running
+#[derive(Debug, Default)]
+pub struct HitStop(Option<Timer>);
+
+impl HitStop {
+	pub fn is_active(&self) -> bool {
+		self.0.is_some()
+	}
+}
+
+fn update_hit_stop(
+	mut hit_stop: ResMut<HitStop>,
+	time: Res<Time>,
+) {
+	let finished = match hit_stop.0.as_mut() {
+		Some(timer) => timer.tick(time.delta()).finished(),
+		None => false,
+	};
+	if finished {
+		hit_stop.0 = None;
+	}
+}
+
+// Damage numbers ///////////////
+struct DamageNumberFont(Handle<Font>);
+
+fn load_damage_number_font(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	commands.insert_resource(DamageNumberFont(asset_server.load("font/Mechanical-g5Y5.otf")));
+}
+
+/// Goes on the root entity, with the text as a facing child
+#[derive(Component, Debug)]
+struct DamageNumber {
+	lifetime: Timer,
+	rise: f32,
+}
+
+/// Height above the enemy's position the numbers start at
+const DAMAGE_NUMBER_START_HEIGHT: f32 = 24.0;
+
+fn spawn_damage_number(
+	commands: &mut Commands,
+	font: &DamageNumberFont,
+	settings: &FeedbackSettings,
+	global_rng: &mut GlobalRng,
+	position: Vec2,
+	amount: i32,
+) {
+	// Spread them out a bit so multiple hits don't overlap
+	let jitter = Vec2::new(global_rng.f32() * 12.0 - 6.0, 0.0);
+
+	commands
+		.spawn()
+		.insert(DamageNumber {
+			lifetime: Timer::from_seconds(settings.damage_number_lifetime, false),
+			rise: settings.damage_number_rise,
+		})
+		.insert(levels::CleanUpOnRoomLoad)
+		.insert_bundle(SpatialBundle {
+			transform: Transform::from_translation(expand_vec2(position + jitter)),
+			..default()
+		})
+		.with_children(|parent| {
+			parent
+				.spawn_bundle(Text2dBundle {
+					text: Text::from_section(
+						amount.to_string(),
+						TextStyle {
+							font: font.0.clone(),
+							font_size: 10.0,
+							color: Color::hex("F2F5E6").unwrap(),
+						},
+					).with_alignment(TextAlignment::CENTER),
+					..default()
+				})
+				.insert(sprite::SpriteOffset(Vec3::Y * DAMAGE_NUMBER_START_HEIGHT))
+				.insert(sprite::SpriteLayer::Overlay)
+				.insert(sprite::FacingSpriteMarker);
+		});
+}
+
+fn update_damage_numbers(
+	mut commands: Commands,
+	mut number_query: Query<(Entity, &mut DamageNumber, &Children)>,
+	mut text_query: Query<(&mut sprite::SpriteOffset, &mut Text)>,
+	time: Res<Time>,
+) {
+	for (e, mut number, children) in number_query.iter_mut() {
+		number.lifetime.tick(time.delta());
+		if number.lifetime.finished() {
+			commands.entity(e).despawn_recursive();
+			continue;
+		}
+
+		let remaining = number.lifetime.percent_left();
+		for child in children.iter() {
+			if let Ok((mut offset, mut text)) = text_query.get_mut(*child) {
+				offset.0.y += number.rise * time.delta_seconds();
+				// Fade out over the second half
+				text.sections[0].style.color.set_a((remaining * 2.0).min(1.0));
+			}
+		}
+	}
+}
@@ -0,0 +1,88 @@
+use super::feedback;
+use bevy::{
+	ecs::schedule::ShouldRun,
+	prelude::*,
+};
+
+/// Sets up the state machine deciding what parts of the game are running.
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_state(GameState::Title)
+			.add_event::<NewGameEvent>();
+	}
+}
+
+/// Event for starting over from the beginning
+pub struct NewGameEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+	Title,
+	/// The game world is running
+	Playing,
+	/// The spell inventory is open, which pauses the game world
+	Inventory,
+	/// The pause menu is open
+	Paused,
+	/// The player has been defeated, and the game over menu is open
+	GameOver,
+	/// The player has reached the end
+	Ending,
+}
+
+impl GameState {
+	/// Whether the game world should be moving along.
+	/// It keeps going behind the game over menu.
+	pub fn is_world_running(&self) -> bool {
+		matches!(self, GameState::Playing | GameState::GameOver)
+	}
+
+	/// Whether one of the menus with the settings pages is open
+	pub fn is_in_settings_menu(&self) -> bool {
+		matches!(self, GameState::Title | GameState::Paused)
+	}
+
+	/// Whether a menu the player can navigate around is open
+	pub fn is_in_menu(&self) -> bool {
+		matches!(self, GameState::Title | GameState::Inventory | GameState::Paused | GameState::GameOver)
+	}
+}
+
+/// Changes the state, ignoring attempts to change to the current state
+/// or to change it twice in a frame.
+pub fn set_state(state: &mut State<GameState>, new_state: GameState) {
+	if *state.current() != new_state {
+		let _ = state.set(new_state);
+	}
+}
+
+/// Run criteria for anything that moves the game world along.
+/// Unlike the ones from State::on_update, this works in any stage.
+/// The world also stands still for a moment on heavy hits.
+pub fn is_world_running(
+	state: Res<State<GameState>>,
+	hit_stop: Res<feedback::HitStop>,
+) -> ShouldRun {
+	should_run(state.current().is_world_running() && !hit_stop.is_active())
+}
+
+fn should_run(condition: bool) -> ShouldRun {
+	if condition {
+		ShouldRun::Yes
+	} else {
+		ShouldRun::No
+	}
+}
+
+/// Run criteria for the settings pages of the title and pause menus
+pub fn is_in_settings_menu(state: Res<State<GameState>>) -> ShouldRun {
+	should_run(state.current().is_in_settings_menu())
+}
+
+/// Run criteria for the spell inventory
+pub fn is_in_inventory(state: Res<State<GameState>>) -> ShouldRun {
+	should_run(*state.current() == GameState::Inventory)
+}
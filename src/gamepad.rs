@@ -0,0 +1,100 @@
+use super::player;
+use bevy::{
+	prelude::*,
+	window::CursorMoved,
+};
+use leafwing_input_manager::prelude::*;
+
+/// Handles the analog parts of gamepad input.
+/// Buttons are handled by the input map in bindings.rs like everything else.
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.insert_resource(GamepadState::default())
+			.add_system(update_gamepad_state);
+	}
+}
+
+/// Stick inputs below this are ignored
+const STICK_DEAD_ZONE: f32 = 0.25;
+
+/// Resource
+#[derive(Debug, Default)]
+pub struct GamepadState {
+	pub active_gamepad: Option<Gamepad>,
+	/// Left stick, in the same space as movement (y is down the screen)
+	pub movement: Vec2,
+	/// Last direction the right stick was held in, in the same space as movement
+	pub aim: Option<Vec2>,
+	/// Whether the gamepad was used more recently than the mouse
+	pub in_use: bool,
+}
+
+fn read_stick(axes: &Axis<GamepadAxis>, gamepad: Gamepad, x_axis: GamepadAxisType, y_axis: GamepadAxisType) -> Vec2 {
+	let stick = Vec2::new(
+		axes.get(GamepadAxis::new(gamepad, x_axis)).unwrap_or(0.0),
+		// Sticks have y going up
+		-axes.get(GamepadAxis::new(gamepad, y_axis)).unwrap_or(0.0),
+	);
+
+	if stick.length() < STICK_DEAD_ZONE {
+		Vec2::ZERO
+	} else {
+		// Rescale so movement starts from zero at the edge of the dead zone
+		let length = ((stick.length() - STICK_DEAD_ZONE) / (1.0 - STICK_DEAD_ZONE)).min(1.0);
+		stick.normalize() * length
+	}
+}
+
+pub fn update_gamepad_state(
+	mut gamepad_state: ResMut<GamepadState>,
+	gamepads: Res<Gamepads>,
+	axes: Res<Axis<GamepadAxis>>,
+	buttons: Res<Input<GamepadButton>>,
+	mut cursor_events: EventReader<CursorMoved>,
+	mut input_map_query: Query<&mut InputMap<player::Action>, With<player::Player>>,
+) {
+	// Keep track of which gamepad to use
+	let connected_gamepad = gamepads.iter().next().copied();
+	if connected_gamepad != gamepad_state.active_gamepad {
+		gamepad_state.active_gamepad = connected_gamepad;
+		gamepad_state.in_use = false;
+		gamepad_state.aim = None;
+
+		let mut input_map = input_map_query.single_mut();
+		match connected_gamepad {
+			Some(gamepad) => {
+				input_map.set_gamepad(gamepad);
+			}
+			None => {
+				input_map.clear_gamepad();
+			}
+		}
+	}
+
+	let gamepad = match gamepad_state.active_gamepad {
+		Some(gamepad) => gamepad,
+		None => {
+			gamepad_state.movement = Vec2::ZERO;
+			return;
+		}
+	};
+
+	gamepad_state.movement = read_stick(&axes, gamepad, GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+	let aim = read_stick(&axes, gamepad, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+	if aim != Vec2::ZERO {
+		gamepad_state.aim = Some(aim.normalize());
+	}
+
+	// Switch between gamepad and mouse based on which was touched last
+	if gamepad_state.movement != Vec2::ZERO
+		|| aim != Vec2::ZERO
+		|| buttons.get_just_pressed().any(|button| button.gamepad == gamepad)
+	{
+		gamepad_state.in_use = true;
+	} else if cursor_events.iter().count() > 0 {
+		gamepad_state.in_use = false;
+	}
+}
@@ -1,10 +1,11 @@
 
 use bevy::{
 	prelude::*,
-	utils::HashMap
+	utils::{Duration, HashMap},
 };
 use bevy_turborand::*;
-use super::{enemy, sprite, spells, physics, player, ui, expand_vec2};
+use leafwing_input_manager::prelude::*;
+use super::{enemy, sprite, spells, physics, player, ui, difficulty, game_state, sound, lighting, room_layout, tuning, expand_vec2};
 use ui::{MessageTrigger, MessageEvent, MessageSource, MessageTriggerType};
 
 pub struct LevelsPlugin;
@@ -12,12 +13,17 @@ impl Plugin for LevelsPlugin {
 	fn build(&self, app: &mut App) {
 		app
 			.insert_resource(CurrentRoom(None))
+			.insert_resource(PlayTime(Duration::ZERO))
 			.add_startup_system(load_level_sprites)
 			.add_event::<RoomTransitionEvent>()
 			.add_system(transition_to_room)
 			.add_system(update_gate)
 			.add_system(do_player_interaction)
-			.add_system(check_delayed_room_transitions);
+			.add_system(update_pickup_lifetimes.with_run_criteria(game_state::is_world_running))
+			.add_system(update_play_time.with_run_criteria(game_state::is_world_running))
+			.add_system(start_new_game.before(transition_to_room))
+			.add_system(update_game_state_for_room.after(transition_to_room))
+			.add_system(leave_ending);
 	}
 }
 
@@ -29,41 +35,77 @@ pub struct RoomTransitionEvent(pub DestinationRoom);
 #[derive(Clone, Copy)]
 pub enum DestinationRoom {
 	NextRoom,
+	/// Reloads the room the player is in
+	SameRoom,
 	TargetRoom {
 		target: usize,
 		respawn: bool,
 	},
 }
-#[derive(Component)]
-pub struct DelayedRoomTransition {
-	event: RoomTransitionEvent,
-	timer: Timer,
+// Resource to store the current room; None if there isn't a game in progress
+pub struct CurrentRoom(pub Option<usize>);
+
+pub const ENDING_ROOM: usize = 5;
+/// Where the player goes back to after being defeated
+// TODO update this when/if savepoints are introduced
+pub const RESPAWN_ROOM: usize = 1;
+
+/// Resource
+/// How long the current game has been going, not counting time spent paused
+struct PlayTime(Duration);
+
+fn update_play_time(
+	mut play_time: ResMut<PlayTime>,
+	time: Res<Time>,
+) {
+	play_time.0 += time.delta();
 }
-impl DelayedRoomTransition {
-	pub fn new(event: RoomTransitionEvent, secs: f32) -> Self {
-		Self {
-			event,
-			timer: Timer::from_seconds(secs, false)
-		}
+
+fn start_new_game(
+	mut new_game_events: EventReader<game_state::NewGameEvent>,
+	mut transition_events: EventWriter<RoomTransitionEvent>,
+	mut play_time: ResMut<PlayTime>,
+) {
+	if new_game_events.iter().next().is_some() {
+		play_time.0 = Duration::ZERO;
+		transition_events.send(RoomTransitionEvent(DestinationRoom::TargetRoom {
+			target: 0,
+			respawn: true,
+		}));
 	}
 }
-fn check_delayed_room_transitions (
-	mut commands: Commands,
-	mut query: Query<(Entity, &mut DelayedRoomTransition)>,
-	time: Res<Time>,
-	mut events: EventWriter<RoomTransitionEvent>,
+
+/// Ends the game at the last room
+fn update_game_state_for_room(
+	current_room: Res<CurrentRoom>,
+	mut state: ResMut<State<game_state::GameState>>,
 ) {
-	for (e, mut delayed_transition) in query.iter_mut() {
-		delayed_transition.timer.tick(time.delta());
-		if delayed_transition.timer.finished() {
-			commands.get_or_spawn(e).despawn();
-			events.send(delayed_transition.event);
-		}
+	if current_room.is_changed() && current_room.0 == Some(ENDING_ROOM) {
+		game_state::set_state(&mut state, game_state::GameState::Ending);
 	}
 }
 
-// Resource to store the current room
-struct CurrentRoom(Option<usize>);
+/// Goes back to the title once the player is done looking at their time
+fn leave_ending(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mut current_room: ResMut<CurrentRoom>,
+	mut state: ResMut<State<game_state::GameState>>,
+	mut message_events: EventWriter<MessageEvent>,
+) {
+	if *state.current() != game_state::GameState::Ending
+		|| !action_query.single().just_pressed(player::Action::CastSpell)
+	{
+		return;
+	}
+
+	// The game is over, so there's nothing to continue
+	current_room.0 = None;
+	game_state::set_state(&mut state, game_state::GameState::Title);
+	message_events.send(MessageEvent {
+		message: None,
+		source: MessageSource::Ending,
+	});
+}
 
 // Things in the game environment that can interact with the player.
 #[derive(Component)]
@@ -71,6 +113,8 @@ pub enum PlayerInteraction {
 	GiveStaff,
 	RoomTransition,
 	GiveRune(usize),
+	RestoreHealth(i32),
+	RestoreMana(i32),
 }
 
 fn do_player_interaction(
@@ -82,7 +126,11 @@ fn do_player_interaction(
 	mut transition_events: EventWriter<RoomTransitionEvent>,
 	mut message_events: EventWriter<MessageEvent>,
 	mut rune_inventory: ResMut<spells::RuneInventory>,
+	mut player_query: Query<(&mut player::PlayerHealth, &mut player::PlayerMana), With<player::Player>>,
+	mut sound_events: EventWriter<sound::PlaySoundEvent>,
 ) {	
+	let (mut player_health, mut player_mana) = player_query.single_mut();
+	
 	for collision in collisions.iter() {
 		if let Ok((e, interaction)) = interact_query.get(collision.source_entity) {
 			match interaction {
@@ -96,6 +144,7 @@ fn do_player_interaction(
 				}
 				PlayerInteraction::GiveRune(i) => {
 					rune_inventory.0.get_mut(*i).expect("invalid rune id in GiveRune").unlocked = true;
+					sound_events.send(sound::PlaySoundEvent(sound::SoundEffect::RunePickup));
 					// Send a message
 					message_events.send(MessageEvent {
 						message: Some("Obtained a rune!".to_string()),
@@ -111,6 +160,21 @@ fn do_player_interaction(
 					});
 					
 					
+					commands.get_or_spawn(e).despawn_recursive();
+				}
+				PlayerInteraction::RestoreHealth(amount) => {
+					// Leave it on the ground if it wouldn't do anything
+					if player_health.health >= player_health.max_health {
+						continue;
+					}
+					player_health.health = (player_health.health + amount).min(player_health.max_health);
+					commands.get_or_spawn(e).despawn_recursive();
+				}
+				PlayerInteraction::RestoreMana(amount) => {
+					if player_mana.mana >= player_mana.max_mana {
+						continue;
+					}
+					player_mana.mana = (player_mana.mana + amount).min(player_mana.max_mana);
 					commands.get_or_spawn(e).despawn_recursive();
 				}
 			}
@@ -118,6 +182,67 @@ fn do_player_interaction(
 	}
 }
 
+// Pickups dropped by enemies
+#[derive(Clone, Copy, Debug)]
+pub enum PickupItem {
+	Heart,
+	ManaOrb,
+	RuneScroll,
+}
+
+/// Pickups disappear once this runs out
+#[derive(Component)]
+pub struct PickupLifetime(pub Timer);
+const PICKUP_LIFETIME: f32 = 10.0;
+const PICKUP_BLINK_TIME: f32 = 3.0;
+
+pub fn spawn_pickup(
+	commands: &mut Commands,
+	item: PickupItem,
+	interaction: PlayerInteraction,
+	position: Vec2,
+	level_textures: &LevelSprites,
+) -> Entity {
+	let (sprite_key, y_offset, hover, shadow_index) = match item {
+		PickupItem::Heart => ("heart", 14.0, sprite::SpriteHover::new(1.0, 2.0), 0),
+		PickupItem::ManaOrb => ("mana_orb", 14.0, sprite::SpriteHover::new(1.0, 2.0), 0),
+		PickupItem::RuneScroll => ("scroll", 20.0, sprite::SpriteHover::new(2.0, 6.0), 1),
+	};
+	
+	commands.spawn_bundle(at_location_vec(position))
+		.insert(physics::CollisionSource::<physics::InteractsWithPlayer>::new(physics::Collider::Circle {
+			center: Vec2::ZERO,
+			radius: 8.0,
+		}))
+		.insert(interaction)
+		.insert(PickupLifetime(Timer::from_seconds(PICKUP_LIFETIME, false)))
+		.insert(CleanUpOnRoomLoad)
+		.insert(sprite::CastsShadow::new(shadow_index, y_offset))
+		.with_children(|parent| {
+			parent.spawn_bundle(sprite::FacingSpriteBundle::new(level_textures.get_sprite(sprite_key), y_offset))
+				.insert(hover);
+		})
+		.id()
+}
+
+fn update_pickup_lifetimes(
+	mut commands: Commands,
+	mut query: Query<(Entity, &mut PickupLifetime, &mut Visibility)>,
+	time: Res<Time>,
+) {
+	for (e, mut lifetime, mut visibility) in query.iter_mut() {
+		lifetime.0.tick(time.delta());
+		if lifetime.0.finished() {
+			commands.get_or_spawn(e).despawn_recursive();
+			continue;
+		}
+		
+		// Blink when about to disappear
+		let remaining = lifetime.0.duration().as_secs_f32() - lifetime.0.elapsed_secs();
+		visibility.is_visible = remaining > PICKUP_BLINK_TIME || (remaining * 8.0) as i32 % 2 == 0;
+	}
+}
+
 // Gate that opens if there are no enemies
 #[derive(Component)]
 pub struct GateMarker;
@@ -126,8 +251,10 @@ fn update_gate(
 	mut commands: Commands,
 	gate_query: Query<Entity, With<GateMarker>>,
 	enemy_query: Query<(), With<enemy::EnemyMarker>>,
+	spawner_query: Query<(), With<enemy::EnemySpawner>>,
 ) {
-	if enemy_query.is_empty() {
+	// Spawners despawn themselves once their last wave is out
+	if enemy_query.is_empty() && spawner_query.is_empty() {
 		for e in gate_query.iter() {
 			commands.get_or_spawn(e).despawn_recursive();
 		}
@@ -149,6 +276,8 @@ fn load_level_sprites(
 	let handles = [
 		("gate", "level/gate.png"),
 		("scroll", "level/scroll.png"),
+		("heart", "ui/health-bar-4.png"),
+		("mana_orb", "ui/mana-bar-6.png"),
 		("bg0", "level/bg-forest.png"),
 	].iter()
 		.map(|(key, path)| {
@@ -164,6 +293,7 @@ use enemy::*;
 use physics::*;
 use spells::*;
 use player::*;
+use room_layout::{RoomLayout, EdgeStyle};
 
 fn at_location(x: f32, y: f32) -> SpatialBundle {
 	at_location_vec(Vec2::new(x,y))
@@ -174,13 +304,13 @@ fn at_location_vec(loc: Vec2) -> SpatialBundle {
 		..default()
 	}
 }
-fn at_origin() -> SpatialBundle {
-	at_location(0.0, 0.0)
-}
+
+/// How much the trees in the distance follow the camera
+const FAR_TREE_PARALLAX: f32 = 0.4;
 
 // Transition system.
 // Is absolutely atrociously long.
-fn transition_to_room(
+pub fn transition_to_room(
 	mut commands: Commands,
 	asset_server: Res<AssetServer>,
 	mut transition_events: EventReader<RoomTransitionEvent>,
@@ -189,27 +319,18 @@ fn transition_to_room(
 	// Things needed for setup
 	mut player_query: Query<&mut Transform, With<Player>>,
 	mut camera_bounds: ResMut<CameraBounds>,
-	mut clear_color: ResMut<ClearColor>,
+	mut room_lighting: ResMut<lighting::RoomLighting>,
 	// Things needed to spawn the destination room
 	mut message_events: EventWriter<MessageEvent>,
 	mut global_rng: ResMut<GlobalRng>,
 	level_textures: Res<LevelSprites>,
 	enemy_textures: Res<EnemySprites>,
-	shadow_texture: Res<ShadowTexture>,
 	spell_textures: Res<AllSpellSprites>,
-	time: Res<Time>,
+	play_time: Res<PlayTime>,
+	difficulty: Res<difficulty::Difficulty>,
+	tuning: Res<tuning::Tuning>,
 ) {
-	let maybe_transition_event = if current_room.0.is_none() {
-		// Go to room 0 at the start
-		Some(&RoomTransitionEvent(DestinationRoom::TargetRoom {
-			target: 0,
-			respawn: false
-		}))
-	} else {
-		transition_events.iter().next()
-	};
-	
-	if let Some(transition_event) = maybe_transition_event {	
+	if let Some(transition_event) = transition_events.iter().next() {	
 		// Clean up from previous room 
 		for entity in cleanup_query.iter() {
 			commands.get_or_spawn(entity).despawn_recursive();
@@ -222,6 +343,11 @@ fn transition_to_room(
 			} else {
 				panic!("cannot transition to next room if not in a room")
 			},
+			DestinationRoom::SameRoom => if let Some(index) = current_room.0 {
+				(index, false)
+			} else {
+				panic!("cannot reload room if not in a room")
+			},
 			DestinationRoom::TargetRoom {target, respawn} => (*target, *respawn),
 		};
 		
@@ -234,10 +360,10 @@ fn transition_to_room(
 		// Do spawning
 		// TIP: Alt+5 does a good code folding level for this to actually be readable
 		// What kind of things might we want to return from this match statement?
-		// - Camera boundaries (x1, x2)
+		// - Camera boundaries (on the ground plane)
 		// - Player start position (x,y)
-		// - screen clear color
-		let (new_player_pos, (cam_min_x, cam_max_x), new_clear_color) = match room_index {
+		// - lighting (including the background colour)
+		let (new_player_pos, new_camera_bounds, new_lighting) = match room_index {
 			0 => { // ////////////////////////////////////////////////////////////////////////////////
 				// starting room
 				// The staff
@@ -248,17 +374,17 @@ fn transition_to_room(
 					}))
 					.insert(PlayerInteraction::GiveStaff)
 					.insert_bundle(at_location(100.0,16.0))
+					.insert(CastsShadow::new(0, 22.0))
 					.with_children(|parent| {
 						parent.spawn_bundle(FacingSpriteBundle::new(
 							asset_server.load("player/staff.png"),
 							22.0
 						))
 						.insert(SpriteHover::new(1.3, 2.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(0));
 					});
 				// Set up some tutorial messages
 				message_events.send(MessageEvent {
-					message: Some("You begin your quest to reach the Tower of the Moon.\nUse WASD to walk.".to_string()),
+					message: Some("You begin your quest to reach the Tower of the Moon.\nUse [Up][Left][Down][Right] to walk.".to_string()),
 					source: MessageSource::Tutorial,
 				});
 				// (sorry this is mildly atrocious but I didn't want it wandering five tabs to the right)
@@ -271,28 +397,28 @@ fn transition_to_room(
 					next_message: Some(Box::new(MessageTrigger {
 					///////////////////////////////////////////////////////////////////////
 					message_event: MessageEvent {
-						message: Some("With your staff, you can cast spells. But first, you must equip runes.\nPress TAB to open your inventory.".to_string()),
+						message: Some("With your staff, you can cast spells. But first, you must equip runes.\nPress [OpenInventory] to open your inventory.".to_string()),
 						source: MessageSource::Tutorial,
 					},
 					trigger_type: MessageTriggerType::OnCollectStaff,
 					next_message: Some(Box::new(MessageTrigger {
 					///////////////////////////////////////////////////////////////////////
 					message_event: MessageEvent {
-						message: Some("Hover the mouse over a rune and press 1-4 or E to equip.".to_string()),
+						message: Some("Hover the mouse over a rune and press [SpellComp0]-[SpellComp3] or [SpellComp4] to equip.".to_string()),
 						source: MessageSource::Tutorial,
 					},
 					trigger_type: MessageTriggerType::OnSpellUi(true),
 					next_message: Some(Box::new(MessageTrigger {
 					///////////////////////////////////////////////////////////////////////
 					message_event: MessageEvent {
-						message: Some("Once you are done selecting runes, press TAB again to close your inventory.".to_string()),
+						message: Some("Once you are done selecting runes, press [OpenInventory] again to close your inventory.".to_string()),
 						source: MessageSource::Tutorial,
 					},
 					trigger_type: MessageTriggerType::OnRuneEqipped,
 					next_message: Some(Box::new(MessageTrigger {
 					///////////////////////////////////////////////////////////////////////
 					message_event: MessageEvent {
-						message: Some("Use 1-4 and E to prepare runes. When you are ready, LEFT CLICK to cast\nyour spell.\nExtinguish the flames to open the gate.".to_string()),
+						message: Some("Use [SpellComp0]-[SpellComp3] and [SpellComp4] to prepare runes. When you are ready, [CastSpell] to cast\nyour spell.\nExtinguish the flames to open the gate.".to_string()),
 						source: MessageSource::Tutorial,
 					},
 					trigger_type: MessageTriggerType::OnSpellUi(false),
@@ -333,48 +459,15 @@ fn transition_to_room(
 					});
 				
 				// "enemies"
-				commands
-					.spawn_bundle(EnemyBundle::<NoAI>::with_state(
-						NoAI,
-						1, 
-						1, 
-						0.0,
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 8.0
-						}, 
-						at_location(-32.0,-54.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							spell_textures.get_atlas_from_type(SpellElement::Fire, SpellSize::Large), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(3.0, 2.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
-				commands
-					.spawn_bundle(EnemyBundle::<NoAI>::with_state(
-						NoAI,
-						1, 
-						1, 
-						0.0,
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 8.0
-						}, 
-						at_location(32.0,-54.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							spell_textures.get_atlas_from_type(SpellElement::Fire, SpellSize::Large), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(3.0, 2.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
+				for x in [-32.0, 32.0] {
+					spawn_enemy(
+						&mut commands,
+						&EnemySpawnInfo::flame(x, -54.0),
+						&enemy_textures,
+						&spell_textures,
+						&mut global_rng,
+					);
+				}
 				
 				// Room transition
 				commands.spawn()
@@ -386,43 +479,21 @@ fn transition_to_room(
 					.insert(CleanUpOnRoomLoad)
 					.insert_bundle(at_location(0.0,-74.0));
 				
-				// Background
-				commands.spawn()
-					.insert_bundle(at_location(16.0,-64.0))
-					.insert(CleanUpOnRoomLoad)
-					.with_children(|parent| {
-						for i in -5..=5 {
-							if i == 0 || i == -1 {
-								continue;
-							}
-							parent.spawn_bundle(FacingSpriteBundle::new_vec(
-								level_textures.get_sprite("bg0"), 
-								Vec3::new(32.0 * i as f32, 45.0, -10.0)
-							));
-						}
-					});
-				// Walls
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, -64.0), Vec2::new(-32.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(32.0, -64.0), Vec2::new(128.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(128.0, -64.0), Vec2::new(128.0, 160.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(128.0, 160.0), Vec2::new(-128.0, 160.0), true))
-					.insert_bundle(at_origin());
+				// Walls, and the trees along the back
+				RoomLayout::new()
+					.with_edge(Vec2::new(-128.0, -64.0), Vec2::new(-32.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(32.0, -64.0), Vec2::new(128.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(128.0, -64.0), Vec2::new(128.0, 160.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(128.0, 160.0), Vec2::new(-128.0, 160.0), EdgeStyle::Invisible)
+					.with_far_trees(FAR_TREE_PARALLAX)
+					.spawn(&mut commands, &level_textures);
 				
 				// Data needed for all the things
 				(
 					Vec2::new(-100.0, 0.0),
-					(0.0, 0.0),
-					Color::hex("75A743").unwrap()
+					CameraBounds::new(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0)),
+					lighting::RoomLighting::daylight(Color::hex("75A743").unwrap())
 				)
 			}
 			1 => { // ////////////////////////////////////////////////////////////////////////////////
@@ -442,42 +513,23 @@ fn transition_to_room(
 					});
 				}
 				// Enemy
-				commands
-					.spawn_bundle(EnemyBundle::<AIPeriodicCharge>::new(
-						40, 
-						2, 
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 10.0
-						}, 
-						at_location(120.0,0.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							enemy_textures.get_sprite("spiky"), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(1.5, 3.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
+				spawn_enemy(
+					&mut commands,
+					&EnemySpawnInfo::spiky(AIPeriodicCharge::from_tuning(&tuning.ai), 40, 2, 120.0, 0.0),
+					&enemy_textures,
+					&spell_textures,
+					&mut global_rng,
+				);
 				
-				// Walls
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, -64.0), Vec2::new(96.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(160.0, -64.0), Vec2::new(208.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(208.0, -64.0), Vec2::new(208.0, 160.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(188.0, 160.0), Vec2::new(-128.0, 160.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), true))
-					.insert_bundle(at_origin());
+				// Walls, and the trees along the back
+				RoomLayout::new()
+					.with_edge(Vec2::new(-128.0, -64.0), Vec2::new(96.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(160.0, -64.0), Vec2::new(208.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(208.0, -64.0), Vec2::new(208.0, 160.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(188.0, 160.0), Vec2::new(-128.0, 160.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), EdgeStyle::Invisible)
+					.with_far_trees(FAR_TREE_PARALLAX)
+					.spawn(&mut commands, &level_textures);
 				
 				
 				// Gate
@@ -503,27 +555,12 @@ fn transition_to_room(
 					.insert_bundle(at_location(128.0,-74.0));
 				
 				
-				// Background
-				commands.spawn()
-					.insert_bundle(at_location(16.0,-64.0))
-					.insert(CleanUpOnRoomLoad)
-					.with_children(|parent| {
-						for i in -5..=12 {
-							if i == 3 || i == 4 {
-								continue;
-							}
-							parent.spawn_bundle(FacingSpriteBundle::new_vec(
-								level_textures.get_sprite("bg0"), 
-								Vec3::new(32.0 * i as f32, 45.0, -10.0)
-							));
-						}
-					});
 					
 				// Metadata
 				(
 					Vec2::new(-100.0, 120.0),
-					(0.0, 80.0),
-					Color::hex("75A743").unwrap()
+					CameraBounds::new(Vec2::new(0.0, 0.0), Vec2::new(80.0, 0.0)),
+					lighting::RoomLighting::daylight(Color::hex("75A743").unwrap())
 				)
 			},
 			2 => { // ////////////////////////////////////////////////////////////////////////////////
@@ -542,44 +579,32 @@ fn transition_to_room(
 				});
 					
 				// Enemy
-				commands
-					.spawn_bundle(EnemyBundle::<AIPeriodicCharge>::new(
-						50, 
-						1, 
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 10.0
-						}, 
-						at_location(80.0,-40.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							enemy_textures.get_sprite("spiky"), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(1.5, 3.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
-				commands
-					.spawn_bundle(EnemyBundle::<AIPeriodicCharge>::new(
-						50, 
-						1, 
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 10.0
-						}, 
-						at_location(100.0, 20.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							enemy_textures.get_sprite("spiky"), 
-							20.0,
-							true
-						))
-						.insert(SpriteHover::new(1.5, 3.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
+				// The first one splits into two weaker, faster ones
+				let split_ai = AIPeriodicCharge {
+					speed: 200.0,
+					timer: Timer::from_seconds(0.7, true),
+					..AIPeriodicCharge::from_tuning(&tuning.ai)
+				};
+				for enemy_info in [
+					EnemySpawnInfo::spiky(AIPeriodicCharge::from_tuning(&tuning.ai), 50, 1, 80.0, -40.0)
+						.with_on_death(OnDeathEffect::Split(vec![
+							EnemySpawnInfo::spiky(split_ai.clone(), 15, 1, -8.0, 0.0)
+								.with_drops(DropTable::default()),
+							EnemySpawnInfo::spiky(split_ai, 15, 1, 8.0, 0.0)
+								.with_drops(DropTable::default())
+								.with_sprite(EnemySpriteKind::Spiky { reversed: true }),
+						])),
+					EnemySpawnInfo::spiky(AIPeriodicCharge::from_tuning(&tuning.ai), 50, 1, 100.0, 20.0)
+						.with_sprite(EnemySpriteKind::Spiky { reversed: true }),
+				] {
+					spawn_enemy(
+						&mut commands,
+						&enemy_info,
+						&enemy_textures,
+						&spell_textures,
+						&mut global_rng,
+					);
+				}
 				
 				// Scroll
 				commands.spawn_bundle(at_location(0.0, 40.0))
@@ -589,28 +614,21 @@ fn transition_to_room(
 					}))
 					.insert(PlayerInteraction::GiveRune(6)) // Burst
 					.insert(CleanUpOnRoomLoad)
+					.insert(CastsShadow::new(1, 20.0))
 					.with_children(|parent| {
 						parent.spawn_bundle(FacingSpriteBundle::new(level_textures.get_sprite("scroll"), 20.0))
 							.insert(SpriteHover::new(2.0, 6.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(1));
 					});
 				
-				// Walls
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, -64.0), Vec2::new(96.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(160.0, -64.0), Vec2::new(208.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(208.0, -64.0), Vec2::new(208.0, 160.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(208.0, 160.0), Vec2::new(-128.0, 160.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), true))
-					.insert_bundle(at_origin());
+				// Walls, and the trees along the back
+				RoomLayout::new()
+					.with_edge(Vec2::new(-128.0, -64.0), Vec2::new(96.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(160.0, -64.0), Vec2::new(208.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(208.0, -64.0), Vec2::new(208.0, 160.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(208.0, 160.0), Vec2::new(-128.0, 160.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), EdgeStyle::Invisible)
+					.with_far_trees(FAR_TREE_PARALLAX)
+					.spawn(&mut commands, &level_textures);
 				
 				
 				// Gate
@@ -636,27 +654,12 @@ fn transition_to_room(
 					.insert_bundle(at_location(128.0,-74.0));
 				
 				
-				// Background
-				commands.spawn()
-					.insert_bundle(at_location(16.0,-64.0))
-					.insert(CleanUpOnRoomLoad)
-					.with_children(|parent| {
-						for i in -5..=12 {
-							if i == 3 || i == 4 {
-								continue;
-							}
-							parent.spawn_bundle(FacingSpriteBundle::new_vec(
-								level_textures.get_sprite("bg0"), 
-								Vec3::new(32.0 * i as f32, 45.0, -10.0)
-							));
-						}
-					});
 					
 				// Metadata
 				(
 					Vec2::new(-100.0, 120.0),
-					(0.0, 80.0),
-					Color::hex("75A743").unwrap()
+					CameraBounds::new(Vec2::new(0.0, 0.0), Vec2::new(80.0, 0.0)),
+					lighting::RoomLighting::daylight(Color::hex("75A743").unwrap())
 				)
 			}
 			3 => { // ////////////////////////////////////////////////////////////////////////////////
@@ -673,22 +676,15 @@ fn transition_to_room(
 						));
 					});
 					
-				// Walls
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, -64.0), Vec2::new(-32.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(32.0, -64.0), Vec2::new(128.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(128.0, -64.0), Vec2::new(128.0, 160.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(128.0, 160.0), Vec2::new(-128.0, 160.0), true))
-					.insert_bundle(at_origin());
+				// Walls, and the trees along the back
+				RoomLayout::new()
+					.with_edge(Vec2::new(-128.0, -64.0), Vec2::new(-32.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(32.0, -64.0), Vec2::new(128.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(128.0, -64.0), Vec2::new(128.0, 160.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(128.0, 160.0), Vec2::new(-128.0, 160.0), EdgeStyle::Invisible)
+					.with_far_trees(FAR_TREE_PARALLAX)
+					.spawn(&mut commands, &level_textures);
 				
 				// Room transition
 				commands.spawn()
@@ -699,123 +695,39 @@ fn transition_to_room(
 					.insert(PlayerInteraction::RoomTransition)
 					.insert(CleanUpOnRoomLoad)
 					.insert_bundle(at_location(0.0,-74.0));
-				// Background
-				commands.spawn()
-					.insert_bundle(at_location(16.0,-64.0))
-					.insert(CleanUpOnRoomLoad)
-					.with_children(|parent| {
-						for i in -5..=5 {
-							if i == 0 || i == -1 {
-								continue;
-							}
-							parent.spawn_bundle(FacingSpriteBundle::new_vec(
-								level_textures.get_sprite("bg0"), 
-								Vec3::new(32.0 * i as f32, 45.0, -10.0)
-							));
-						}
-					});
 				
-				// Enemies
-				commands
-					.spawn_bundle(EnemyBundle::<AIPeriodicCharge>::with_state(
-						AIPeriodicCharge {
-							speed: 180.0,
-							max_dev_angle: std::f32::consts::PI / 8.0,
-							..default()
+				// Enemies; this room is a small arena with three waves
+				let charge_ai = AIPeriodicCharge {
+					speed: 180.0,
+					max_dev_angle: std::f32::consts::PI / 8.0,
+					..AIPeriodicCharge::from_tuning(&tuning.ai)
+				};
+				commands.spawn()
+					.insert(EnemySpawner::new(vec![
+						EnemyWave {
+							trigger: WaveTrigger::Immediate,
+							enemies: vec![
+								EnemySpawnInfo::spiky(charge_ai.clone(), 50, 2, 40.0, -40.0),
+								EnemySpawnInfo::spiky(charge_ai.clone(), 50, 2, -40.0, -40.0),
+							],
 						},
-						50, 
-						2, 
-						1.0,
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 10.0
-						}, 
-						at_location(40.0,-40.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							enemy_textures.get_sprite("spiky"), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(1.5, 3.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
-				commands
-					.spawn_bundle(EnemyBundle::<AIPeriodicCharge>::with_state(
-						AIPeriodicCharge {
-							speed: 180.0,
-							max_dev_angle: std::f32::consts::PI / 8.0,
-							..default()
+						EnemyWave {
+							trigger: WaveTrigger::AfterCleared(Timer::from_seconds(1.0, false)),
+							enemies: vec![
+								EnemySpawnInfo::spiky(charge_ai.clone(), 50, 2, 60.0, -20.0),
+								EnemySpawnInfo::spiky(charge_ai, 50, 2, -60.0, -20.0)
+									.with_knockback_factor(0.75),
+							],
 						},
-						50, 
-						2, 
-						1.0,
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 10.0
-						}, 
-						at_location(-40.0,-40.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							enemy_textures.get_sprite("spiky"), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(1.5, 3.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
-				commands
-					.spawn_bundle(EnemyBundle::<AIPeriodicCharge>::with_state(
-						AIPeriodicCharge {
-							speed: 180.0,
-							max_dev_angle: std::f32::consts::PI / 8.0,
-							..default()
+						EnemyWave {
+							trigger: WaveTrigger::AfterCleared(Timer::from_seconds(1.5, false)),
+							enemies: vec![
+								EnemySpawnInfo::shielded(AIShielded::from_tuning(&tuning.ai), 60, 2, 0.0, 60.0),
+								EnemySpawnInfo::turret(AITurret::from_tuning(&tuning.ai), 40, 1, -80.0, 40.0),
+							],
 						},
-						50, 
-						2, 
-						1.0,
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 10.0
-						}, 
-						at_location(60.0,-20.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							enemy_textures.get_sprite("spiky"), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(1.5, 3.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
-				commands
-					.spawn_bundle(EnemyBundle::<AIPeriodicCharge>::with_state(
-						AIPeriodicCharge {
-							speed: 180.0,
-							max_dev_angle: std::f32::consts::PI / 8.0,
-							..default()
-						},
-						50, 
-						2, 
-						0.75,
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 10.0
-						}, 
-						at_location(-60.0,-20.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							enemy_textures.get_sprite("spiky"), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(1.5, 3.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
+					]))
+					.insert(CleanUpOnRoomLoad);
 				
 				
 				// Scroll
@@ -826,10 +738,10 @@ fn transition_to_room(
 					}))
 					.insert(PlayerInteraction::GiveRune(2)) // Earth
 					.insert(CleanUpOnRoomLoad)
+					.insert(CastsShadow::new(1, 20.0))
 					.with_children(|parent| {
 						parent.spawn_bundle(FacingSpriteBundle::new(level_textures.get_sprite("scroll"), 20.0))
 							.insert(SpriteHover::new(2.0, 6.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(1));
 					});
 				
 				commands.spawn_bundle(at_location(0.0, 40.0))
@@ -839,17 +751,17 @@ fn transition_to_room(
 					}))
 					.insert(PlayerInteraction::GiveRune(7)) // Scatter
 					.insert(CleanUpOnRoomLoad)
+					.insert(CastsShadow::new(1, 20.0))
 					.with_children(|parent| {
 						parent.spawn_bundle(FacingSpriteBundle::new(level_textures.get_sprite("scroll"), 20.0))
 							.insert(SpriteHover::new(2.0, 6.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(1));
 					});
 				
 				
 				(
 					Vec2::new(0.0, 130.0),
-					(0.0, 0.0),
-					Color::hex("75A743").unwrap()
+					CameraBounds::new(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0)),
+					lighting::RoomLighting::daylight(Color::hex("75A743").unwrap())
 				)
 			}
 			4 => { // ////////////////////////////////////////////////////////////////////////////////
@@ -866,22 +778,15 @@ fn transition_to_room(
 						));
 					});
 					
-				// Walls
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, -64.0), Vec2::new(-32.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(32.0, -64.0), Vec2::new(128.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(128.0, -64.0), Vec2::new(128.0, 160.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), true))
-					.insert_bundle(at_origin());
-				commands
-					.spawn_bundle(Wall::new(Vec2::new(128.0, 160.0), Vec2::new(-128.0, 160.0), true))
-					.insert_bundle(at_origin());
+				// Walls, and the trees along the back
+				RoomLayout::new()
+					.with_edge(Vec2::new(-128.0, -64.0), Vec2::new(-32.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(32.0, -64.0), Vec2::new(128.0, -64.0), EdgeStyle::Trees)
+					.with_edge(Vec2::new(128.0, -64.0), Vec2::new(128.0, 160.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(-128.0, 160.0), Vec2::new(-128.0, -64.0), EdgeStyle::Invisible)
+					.with_edge(Vec2::new(128.0, 160.0), Vec2::new(-128.0, 160.0), EdgeStyle::Invisible)
+					.with_far_trees(FAR_TREE_PARALLAX)
+					.spawn(&mut commands, &level_textures);
 				
 				// Room transition
 				commands.spawn()
@@ -892,44 +797,32 @@ fn transition_to_room(
 					.insert(PlayerInteraction::RoomTransition)
 					.insert(CleanUpOnRoomLoad)
 					.insert_bundle(at_location(0.0,-74.0));
-				// Background
-				commands.spawn()
-					.insert_bundle(at_location(16.0,-64.0))
-					.insert(CleanUpOnRoomLoad)
-					.with_children(|parent| {
-						for i in -5..=5 {
-							if i == 0 || i == -1 {
-								continue;
-							}
-							parent.spawn_bundle(FacingSpriteBundle::new_vec(
-								level_textures.get_sprite("bg0"), 
-								Vec3::new(32.0 * i as f32, 45.0, -10.0)
-							));
-						}
-					});
 				
-				// Enemies
-				commands
-					.spawn_bundle(EnemyBundle::<AIRotateAround>::with_state(
-						AIRotateAround::default(),
-						100, 
-						3, 
-						0.5,
-						Collider::Circle {
-							center: Vec2::ZERO,
-							radius: 8.0
-						}, 
-						at_location(0.0,-40.0),
-						&mut global_rng
-					)).with_children(|parent| {
-						parent.spawn_bundle(SimpleAnimationBundle::new(
-							enemy_textures.get_sprite("eye"), 
-							20.0,
-							false
-						))
-						.insert(SpriteHover::new(1.5, 3.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
-					});
+				// Enemies; the eye first, then its helpers
+				commands.spawn()
+					.insert(EnemySpawner::new(vec![
+						EnemyWave {
+							trigger: WaveTrigger::Immediate,
+							enemies: vec![
+								EnemySpawnInfo::eye(AIRotateAround::from_tuning(&tuning.ai), 100, 3, 0.0, -40.0)
+									.with_on_death(OnDeathEffect::Explode(
+										SpellData::from_runes(&[
+											Rune::ShapeRune(SpellShape::Burst),
+											Rune::ElementRune(SpellElement::Fire),
+											Rune::ElementRune(SpellElement::Fire),
+										], &tuning.spells).expect("eye death spell should have an effect")
+									)),
+							],
+						},
+						EnemyWave {
+							trigger: WaveTrigger::AfterCleared(Timer::from_seconds(1.0, false)),
+							enemies: vec![
+								EnemySpawnInfo::teleporter(AITeleporter::from_tuning(&tuning.ai), 40, 2, -60.0, 0.0),
+								EnemySpawnInfo::summoner(AISummoner::from_tuning(&tuning.ai), 60, 1, 60.0, 40.0),
+							],
+						},
+					]))
+					.insert(CleanUpOnRoomLoad);
 				
 				// Scroll
 				commands.spawn_bundle(at_location(60.0, 0.0))
@@ -939,10 +832,10 @@ fn transition_to_room(
 					}))
 					.insert(PlayerInteraction::GiveRune(5)) // Line
 					.insert(CleanUpOnRoomLoad)
+					.insert(CastsShadow::new(1, 20.0))
 					.with_children(|parent| {
 						parent.spawn_bundle(FacingSpriteBundle::new(level_textures.get_sprite("scroll"), 20.0))
 							.insert(SpriteHover::new(2.0, 6.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(1));
 					});
 				
 				// Scroll
@@ -953,36 +846,38 @@ fn transition_to_room(
 					}))
 					.insert(PlayerInteraction::GiveRune(0)) // Fire
 					.insert(CleanUpOnRoomLoad)
+					.insert(CastsShadow::new(1, 20.0))
 					.with_children(|parent| {
 						parent.spawn_bundle(FacingSpriteBundle::new(level_textures.get_sprite("scroll"), 20.0))
 							.insert(SpriteHover::new(2.0, 6.0));
-						parent.spawn_bundle(shadow_texture.get_shadow_bundle(1));
 					});
 				
 				
 				(
 					Vec2::new(0.0, 130.0),
-					(0.0, 0.0),
-					Color::hex("75A743").unwrap()
+					CameraBounds::new(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0)),
+					// Dusk is falling by the time you reach the tower
+					lighting::RoomLighting::new(Color::hex("75A743").unwrap(), Color::rgb(0.45, 0.45, 0.65))
 				)
 			}
-			5 => { // ////////////////////////////////////////////////////////////////////////////////
+			ENDING_ROOM => { // ////////////////////////////////////////////////////////////////////////
 				// Ending
-				let total_time = time.time_since_startup().as_secs_f32();
+				let total_time = play_time.0.as_secs_f32();
 				let n_minutes = total_time as i32 / 60;
 				let n_seconds = total_time % 60.0;
 				message_events.send(MessageEvent {
 						message: Some(format!(
-							"You have reached the Tower of the Moon! Congratulations!\nYour time: {}:{:0>5}",
+							"You have reached the Tower of the Moon! Congratulations!\nYour time: {}:{:0>5}    Difficulty: {}\nPress [CastSpell] to return to the title.",
 							n_minutes,
 							format!("{:.3}", n_seconds),
+							difficulty.describe(),
 						).to_string()),
 						source: MessageSource::Ending,
 					});
 				(
 					Vec2::new(0.0, 0.0),
-					(0.0, 0.0),
-					Color::hex("000000").unwrap()
+					CameraBounds::new(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0)),
+					lighting::RoomLighting::daylight(Color::hex("000000").unwrap())
 				)
 			}
 			_ => panic!("attempted to transition to non-existent room {}", room_index)
@@ -993,11 +888,10 @@ fn transition_to_room(
 		player_transform.translation = expand_vec2(new_player_pos);
 		
 		// Update camera bounds
-		camera_bounds.min_x = cam_min_x;
-		camera_bounds.max_x = cam_max_x;
+		*camera_bounds = new_camera_bounds;
 		
-		// Update clear color
-		clear_color.0 = new_clear_color;
+		// Update lighting
+		*room_lighting = new_lighting;
 		
 		current_room.0 = Some(room_index);
 	}
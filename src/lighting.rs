@@ -0,0 +1,242 @@
+use super::{spells, sprite, player};
+use bevy::{
+	prelude::*,
+	reflect::TypeUuid,
+	render::{
+		mesh::MeshVertexBufferLayout,
+		render_resource::{
+			AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState,
+			RenderPipelineDescriptor, ShaderRef, ShaderType, SpecializedMeshPipelineError,
+		},
+	},
+	sprite::{Material2d, Material2dKey, Material2dPlugin, MaterialMesh2dBundle},
+	transform::transform_propagate_system,
+};
+
+/// Lights up the area around glowing spells, and lets rooms be darker than daylight.
+/// Everything drawn before the Lighting layer gets multiplied by a light map, drawn on one big quad in front of the camera.
+/// Only uses a uniform buffer, so it works on WebGL2 as well.
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_plugin(Material2dPlugin::<LightingMaterial>::default())
+			.insert_resource(RoomLighting::daylight(Color::BLACK))
+			.add_startup_system(lighting_setup)
+			.add_system(add_spell_lights)
+			.add_system(update_clear_color)
+			.add_system_to_stage(
+				CoreStage::PostUpdate,
+				update_lighting
+					.after(player::update_camera)
+					.before(transform_propagate_system)
+			);
+	}
+}
+
+/// Resource
+/// How a room is lit. The background is lit like everything else, so it gets darker along with the room.
+#[derive(Debug, Clone, Copy)]
+pub struct RoomLighting {
+	pub background: Color,
+	/// Light everywhere in the room, before any light sources
+	pub ambient: Color,
+}
+
+impl RoomLighting {
+	pub fn new(background: Color, ambient: Color) -> Self {
+		Self {
+			background,
+			ambient,
+		}
+	}
+
+	pub fn daylight(background: Color) -> Self {
+		Self::new(background, Color::WHITE)
+	}
+}
+
+fn update_clear_color(
+	room_lighting: Res<RoomLighting>,
+	mut clear_color: ResMut<ClearColor>,
+) {
+	if room_lighting.is_changed() {
+		clear_color.0 = room_lighting.background;
+	}
+}
+
+/// Component for things that give off light.
+/// Goes on a root entity; the light is at the given height above it, like a facing sprite.
+#[derive(Component, Debug, Clone)]
+pub struct LightSource {
+	pub color: Color,
+	/// In pixels
+	pub radius: f32,
+	pub intensity: f32,
+	pub height: f32,
+}
+
+/// Only elements that glow give off light
+fn get_spell_light(element: spells::SpellElement, size: spells::SpellSize) -> Option<LightSource> {
+	let (color, intensity) = match element {
+		spells::SpellElement::Fire => ("F3A833", 1.0),
+		spells::SpellElement::Electric => ("FFF67A", 0.8),
+		spells::SpellElement::Light => ("F2F5E6", 1.2),
+		_ => return None,
+	};
+	let radius = match size {
+		spells::SpellSize::Tiny => 24.0,
+		spells::SpellSize::Small => 40.0,
+		spells::SpellSize::Normal => 64.0,
+		spells::SpellSize::Large => 96.0,
+	};
+
+	Some(LightSource {
+		color: Color::hex(color).unwrap(),
+		radius,
+		intensity,
+		height: SPELL_LIGHT_HEIGHT,
+	})
+}
+
+/// Roughly where spell sprites float
+const SPELL_LIGHT_HEIGHT: f32 = 32.0;
+
+fn add_spell_lights(
+	mut commands: Commands,
+	spell_query: Query<(Entity, &spells::SpellData), Added<spells::SpellMarker>>,
+) {
+	for (e, spell_data) in spell_query.iter() {
+		if let Some(light) = get_spell_light(spell_data.get_element(), spell_data.get_size()) {
+			commands.entity(e).insert(light);
+		}
+	}
+}
+
+// Rendering ///////////////
+/// Keep in sync with the shader
+const MAX_LIGHTS: usize = 16;
+/// Big enough to cover the screen at any window size
+const LIGHTING_QUAD_SIZE: f32 = 4096.0;
+
+#[derive(ShaderType, Debug, Clone)]
+struct LightingUniform {
+	/// Linear colour
+	ambient: Vec4,
+	/// xy is the position in camera space, z is the radius and w is the intensity
+	lights: [Vec4; MAX_LIGHTS],
+	/// Linear colours
+	colors: [Vec4; MAX_LIGHTS],
+	size: Vec2,
+	count: u32,
+}
+
+#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
+#[uuid = "3c1b7e5a-92d4-4f0b-8a61-5e0d2f7c9b14"]
+pub struct LightingMaterial {
+	#[uniform(0)]
+	uniform: LightingUniform,
+}
+
+impl Material2d for LightingMaterial {
+	fn fragment_shader() -> ShaderRef {
+		"shaders/lighting.wgsl".into()
+	}
+
+	/// Multiplies whatever is already on screen by the light map
+	fn specialize(
+		descriptor: &mut RenderPipelineDescriptor,
+		_layout: &MeshVertexBufferLayout,
+		_key: Material2dKey<Self>,
+	) -> Result<(), SpecializedMeshPipelineError> {
+		if let Some(fragment) = descriptor.fragment.as_mut() {
+			for target in fragment.targets.iter_mut().flatten() {
+				target.blend = Some(BlendState {
+					color: BlendComponent {
+						src_factor: BlendFactor::Dst,
+						dst_factor: BlendFactor::Zero,
+						operation: BlendOperation::Add,
+					},
+					alpha: BlendComponent {
+						src_factor: BlendFactor::Zero,
+						dst_factor: BlendFactor::One,
+						operation: BlendOperation::Add,
+					},
+				});
+			}
+		}
+		Ok(())
+	}
+}
+
+struct LightingMaterialHandle(Handle<LightingMaterial>);
+
+#[derive(Component, Debug)]
+struct LightingQuad;
+
+fn lighting_setup(
+	mut commands: Commands,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut materials: ResMut<Assets<LightingMaterial>>,
+) {
+	let material = materials.add(LightingMaterial {
+		uniform: LightingUniform {
+			ambient: Vec4::ONE,
+			lights: [Vec4::ZERO; MAX_LIGHTS],
+			colors: [Vec4::ZERO; MAX_LIGHTS],
+			size: Vec2::splat(LIGHTING_QUAD_SIZE),
+			count: 0,
+		},
+	});
+
+	commands
+		.spawn_bundle(MaterialMesh2dBundle {
+			mesh: meshes.add(Mesh::from(shape::Quad::new(Vec2::splat(LIGHTING_QUAD_SIZE)))).into(),
+			material: material.clone(),
+			..default()
+		})
+		.insert(LightingQuad);
+	commands.insert_resource(LightingMaterialHandle(material));
+}
+
+fn update_lighting(
+	mut quad_query: Query<&mut Transform, (With<LightingQuad>, Without<Camera>)>,
+	camera_query: Query<&Transform, (With<Camera>, Without<LightingQuad>)>,
+	light_query: Query<(&LightSource, &Transform), Without<LightingQuad>>,
+	room_lighting: Res<RoomLighting>,
+	material_handle: Res<LightingMaterialHandle>,
+	mut materials: ResMut<Assets<LightingMaterial>>,
+) {
+	let camera_transform = camera_query.single();
+	let camera_inverse = Transform::from_matrix(camera_transform.compute_matrix().inverse());
+
+	// Put the quad right in front of the camera, facing it, at the depth of the Lighting layer.
+	// The view is orthographic, so how far away it is doesn't change what it covers.
+	let forward = camera_transform.forward();
+	if forward.z.abs() > f32::EPSILON {
+		let mut quad_transform = quad_query.single_mut();
+		let distance = sprite::get_sort_depth(sprite::SpriteLayer::Lighting, 0.0) / forward.z;
+		quad_transform.translation = camera_transform.translation + forward * distance;
+		quad_transform.rotation = camera_transform.rotation;
+	}
+
+	let material = match materials.get_mut(&material_handle.0) {
+		Some(material) => material,
+		None => return,
+	};
+	let uniform = &mut material.uniform;
+	uniform.ambient = Vec4::from(room_lighting.ambient.as_linear_rgba_f32());
+	uniform.count = 0;
+	for (light, transform) in light_query.iter() {
+		if uniform.count as usize >= MAX_LIGHTS {
+			break;
+		}
+
+		let position = camera_inverse * (transform.translation + camera_transform.rotation * Vec3::Y * light.height);
+		let i = uniform.count as usize;
+		uniform.lights[i] = Vec4::new(position.x, position.y, light.radius, light.intensity);
+		uniform.colors[i] = Vec4::from(light.color.as_linear_rgba_f32());
+		uniform.count += 1;
+	}
+}
@@ -19,14 +19,40 @@ mod sprite;
 mod ui;
 mod enemy;
 mod levels;
+mod difficulty;
+mod viewport;
+mod ray;
+mod gamepad;
+mod bindings;
+mod game_state;
+mod sound;
+mod particles;
+mod feedback;
+mod lighting;
+mod room_layout;
+mod tuning;
+#[cfg(feature = "debug-overlay")]
+mod debug_overlay;
+#[cfg(feature = "dev-console")]
+mod dev_console;
 
 // theme = combine
 fn main() {
-    App::new()
+    let mut app = App::new();
+    #[cfg(feature = "hot-reload")]
+    app.insert_resource(bevy::asset::AssetServerSettings {
+        watch_for_changes: true,
+        ..default()
+    });
+    app
         .insert_resource(WindowDescriptor {
-            width: 640.0,
-            height: 400.0,
-			resizable: false,
+            width: viewport::BASE_WIDTH as f32,
+            height: viewport::BASE_HEIGHT as f32,
+			resize_constraints: bevy::window::WindowResizeConstraints {
+				min_width: viewport::BASE_WIDTH as f32,
+				min_height: viewport::BASE_HEIGHT as f32,
+				..default()
+			},
             ..default()
         })
         .insert_resource(ImageSettings::default_nearest())
@@ -40,9 +66,27 @@ fn main() {
 		.add_plugin(enemy::EnemyPlugin)
         .add_plugin(ui::UIPlugin)
 		.add_plugin(levels::LevelsPlugin)
+		.add_plugin(difficulty::DifficultyPlugin)
+		.add_plugin(viewport::ViewportPlugin)
+		.add_plugin(gamepad::GamepadPlugin)
+		.add_plugin(bindings::BindingsPlugin)
+		.add_plugin(game_state::GameStatePlugin)
+		.add_plugin(sound::SoundPlugin)
+		.add_plugin(particles::ParticlePlugin)
+		.add_plugin(feedback::FeedbackPlugin)
+		.add_plugin(lighting::LightingPlugin)
+		.add_plugin(room_layout::RoomLayoutPlugin)
+		.add_plugin(tuning::TuningPlugin)
         //.add_plugin(LogDiagnosticsPlugin::default())
         //.add_plugin(FrameTimeDiagnosticsPlugin::default())
-        .run();
+        ;
+
+    #[cfg(feature = "debug-overlay")]
+    app.add_plugin(debug_overlay::DebugOverlayPlugin);
+    #[cfg(feature = "dev-console")]
+    app.add_plugin(dev_console::DevConsolePlugin);
+
+    app.run();
 }
 
 
@@ -0,0 +1,326 @@
+use super::{spells, sprite, levels, game_state, expand_vec2, collapse_vec3};
+use bevy::prelude::*;
+use bevy_turborand::*;
+
+/// Small bits of flair for spells: trails behind projectiles and bursts where they end.
+/// Particles are facing sprites like everything else, so they sit in the world properly.
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_system(add_spell_trails)
+			.add_system(update_trail_emitters.with_run_criteria(game_state::is_world_running))
+			.add_system(update_particles.with_run_criteria(game_state::is_world_running))
+			// Spells are still around at this point, they only go away at the end of the stage
+			.add_system_to_stage(CoreStage::PostUpdate, spawn_impact_particles);
+	}
+}
+
+/// How a group of particles looks and moves
+#[derive(Debug, Clone, Copy)]
+struct ParticleStyle {
+	/// Picked from at random for each particle
+	colors: &'static [&'static str],
+	/// Number of particles per burst
+	count: usize,
+	size: f32,
+	/// Speed along the ground
+	speed: f32,
+	/// Initial upwards speed; this is randomized a bit
+	rise: f32,
+	gravity: f32,
+	/// In seconds
+	lifetime: f32,
+}
+
+/// Particles following a projectile around
+fn get_trail_style(element: spells::SpellElement) -> Option<ParticleStyle> {
+	match element {
+		// Embers
+		spells::SpellElement::Fire => Some(ParticleStyle {
+			colors: &["F3A833", "EC5B2A", "FFE478"],
+			count: 1,
+			size: 2.0,
+			speed: 8.0,
+			rise: 24.0,
+			gravity: -8.0,
+			lifetime: 0.6,
+		}),
+		// Sparks
+		spells::SpellElement::Electric => Some(ParticleStyle {
+			colors: &["FFF67A", "F2F5E6"],
+			count: 1,
+			size: 1.0,
+			speed: 40.0,
+			rise: 30.0,
+			gravity: 120.0,
+			lifetime: 0.25,
+		}),
+		spells::SpellElement::Ice => Some(ParticleStyle {
+			colors: &["B8EEEB", "F2F5E6"],
+			count: 1,
+			size: 1.0,
+			speed: 4.0,
+			rise: 0.0,
+			gravity: 20.0,
+			lifetime: 0.5,
+		}),
+		_ => None,
+	}
+}
+
+/// Particles for when a spell hits something or runs out
+fn get_impact_style(element: spells::SpellElement) -> ParticleStyle {
+	match element {
+		// Embers
+		spells::SpellElement::Fire => ParticleStyle {
+			colors: &["F3A833", "EC5B2A", "FFE478"],
+			count: 10,
+			size: 2.0,
+			speed: 30.0,
+			rise: 40.0,
+			gravity: 10.0,
+			lifetime: 0.7,
+		},
+		// Splashes
+		spells::SpellElement::Water => ParticleStyle {
+			colors: &["4B80CA", "68C2D3", "B8EEEB"],
+			count: 14,
+			size: 2.0,
+			speed: 40.0,
+			rise: 70.0,
+			gravity: 240.0,
+			lifetime: 0.6,
+		},
+		// Dust
+		spells::SpellElement::Earth => ParticleStyle {
+			colors: &["7A444A", "A05B53", "BF7958"],
+			count: 12,
+			size: 3.0,
+			speed: 24.0,
+			rise: 12.0,
+			gravity: 10.0,
+			lifetime: 0.9,
+		},
+		// Sparks
+		spells::SpellElement::Electric => ParticleStyle {
+			colors: &["FFF67A", "F2F5E6"],
+			count: 12,
+			size: 1.0,
+			speed: 90.0,
+			rise: 60.0,
+			gravity: 200.0,
+			lifetime: 0.3,
+		},
+		spells::SpellElement::Air => ParticleStyle {
+			colors: &["F2F5E6", "B8EEEB"],
+			count: 8,
+			size: 2.0,
+			speed: 50.0,
+			rise: 10.0,
+			gravity: 0.0,
+			lifetime: 0.4,
+		},
+		spells::SpellElement::Metal => ParticleStyle {
+			colors: &["C7DCD0", "9BABB2", "F2F5E6"],
+			count: 8,
+			size: 1.0,
+			speed: 60.0,
+			rise: 50.0,
+			gravity: 200.0,
+			lifetime: 0.4,
+		},
+		spells::SpellElement::Plant => ParticleStyle {
+			colors: &["5AC54F", "99E65F", "33984B"],
+			count: 8,
+			size: 2.0,
+			speed: 20.0,
+			rise: 30.0,
+			gravity: 30.0,
+			lifetime: 0.9,
+		},
+		spells::SpellElement::Ice => ParticleStyle {
+			colors: &["B8EEEB", "F2F5E6", "68C2D3"],
+			count: 10,
+			size: 2.0,
+			speed: 36.0,
+			rise: 50.0,
+			gravity: 220.0,
+			lifetime: 0.5,
+		},
+		spells::SpellElement::Light => ParticleStyle {
+			colors: &["FFE478", "F2F5E6"],
+			count: 10,
+			size: 1.0,
+			speed: 40.0,
+			rise: 20.0,
+			gravity: -20.0,
+			lifetime: 0.6,
+		},
+		spells::SpellElement::Neutral => ParticleStyle {
+			colors: &["F2F5E6", "9BABB2"],
+			count: 6,
+			size: 1.0,
+			speed: 30.0,
+			rise: 30.0,
+			gravity: 120.0,
+			lifetime: 0.3,
+		},
+	}
+}
+
+/// Goes on the root entity of a particle, which moves along the ground.
+/// The sprite is a child, with its height kept in its SpriteOffset.
+#[derive(Component, Debug)]
+struct Particle {
+	velocity: Vec2,
+	height: f32,
+	vertical_velocity: f32,
+	gravity: f32,
+	lifetime: Timer,
+}
+
+fn spawn_particles(
+	commands: &mut Commands,
+	global_rng: &mut GlobalRng,
+	style: &ParticleStyle,
+	position: Vec2,
+	height: f32,
+) {
+	for _ in 0..style.count {
+		let direction = collapse_vec3(Quat::from_rotation_y(global_rng.f32() * std::f32::consts::TAU) * Vec3::X);
+		let color = style.colors[global_rng.usize(0..style.colors.len())];
+
+		commands
+			.spawn()
+			.insert(Particle {
+				velocity: direction * style.speed * (0.5 + global_rng.f32() * 0.5),
+				height,
+				vertical_velocity: style.rise * (0.5 + global_rng.f32() * 0.5),
+				gravity: style.gravity,
+				lifetime: Timer::from_seconds(style.lifetime * (0.75 + global_rng.f32() * 0.5), false),
+			})
+			.insert(levels::CleanUpOnRoomLoad)
+			.insert_bundle(SpatialBundle {
+				transform: Transform::from_translation(expand_vec2(position)),
+				..default()
+			})
+			.with_children(|parent| {
+				parent
+					.spawn_bundle(SpriteBundle {
+						sprite: Sprite {
+							color: Color::hex(color).unwrap(),
+							custom_size: Some(Vec2::splat(style.size)),
+							..default()
+						},
+						..default()
+					})
+					.insert(sprite::SpriteOffset(Vec3::Y * height))
+					.insert(sprite::SpriteLayer::Air)
+					.insert(sprite::FacingSpriteMarker);
+			});
+	}
+}
+
+/// Component for spells that leave a trail behind them
+#[derive(Component, Debug)]
+struct TrailEmitter {
+	style: ParticleStyle,
+	timer: Timer,
+}
+
+/// Seconds between trail particles
+const TRAIL_INTERVAL: f32 = 0.05;
+
+fn add_spell_trails(
+	mut commands: Commands,
+	spell_query: Query<(Entity, &spells::SpellData), Added<spells::SpellMarker>>,
+) {
+	for (e, spell_data) in spell_query.iter() {
+		if let Some(style) = get_trail_style(spell_data.get_element()) {
+			commands.entity(e).insert(TrailEmitter {
+				style,
+				timer: Timer::from_seconds(TRAIL_INTERVAL, true),
+			});
+		}
+	}
+}
+
+/// How high up the spell's sprite is, so particles come out of it and not the ground
+fn get_spell_height(
+	children: &Children,
+	offset_query: &Query<&sprite::SpriteOffset, With<sprite::SimpleAnimationMarker>>,
+) -> f32 {
+	children.iter()
+		.find_map(|child| offset_query.get(*child).ok())
+		.map(|offset| offset.0.y)
+		.unwrap_or(0.0)
+}
+
+fn update_trail_emitters(
+	mut commands: Commands,
+	mut emitter_query: Query<(&mut TrailEmitter, &Transform, &Children)>,
+	offset_query: Query<&sprite::SpriteOffset, With<sprite::SimpleAnimationMarker>>,
+	mut global_rng: ResMut<GlobalRng>,
+	time: Res<Time>,
+) {
+	for (mut emitter, transform, children) in emitter_query.iter_mut() {
+		emitter.timer.tick(time.delta());
+		if emitter.timer.just_finished() {
+			let height = get_spell_height(children, &offset_query);
+			spawn_particles(&mut commands, &mut global_rng, &emitter.style, collapse_vec3(transform.translation), height);
+		}
+	}
+}
+
+fn spawn_impact_particles(
+	mut commands: Commands,
+	mut despawn_events: EventReader<spells::SpellDespawnEvent>,
+	spell_query: Query<(&spells::SpellData, &Transform, &Children), With<spells::SpellMarker>>,
+	offset_query: Query<&sprite::SpriteOffset, With<sprite::SimpleAnimationMarker>>,
+	mut global_rng: ResMut<GlobalRng>,
+) {
+	for event in despawn_events.iter() {
+		if let Ok((spell_data, transform, children)) = spell_query.get(event.0) {
+			let height = get_spell_height(children, &offset_query);
+			let style = get_impact_style(spell_data.get_element());
+			spawn_particles(&mut commands, &mut global_rng, &style, collapse_vec3(transform.translation), height);
+		}
+	}
+}
+
+fn update_particles(
+	mut commands: Commands,
+	mut particle_query: Query<(Entity, &mut Particle, &mut Transform, &Children)>,
+	mut sprite_query: Query<(&mut sprite::SpriteOffset, &mut Sprite)>,
+	time: Res<Time>,
+) {
+	let dt = time.delta_seconds();
+	for (e, mut particle, mut transform, children) in particle_query.iter_mut() {
+		particle.lifetime.tick(time.delta());
+		if particle.lifetime.finished() {
+			commands.entity(e).despawn_recursive();
+			continue;
+		}
+
+		transform.translation += expand_vec2(particle.velocity * dt);
+		particle.vertical_velocity -= particle.gravity * dt;
+		particle.height += particle.vertical_velocity * dt;
+		// Land on the ground
+		if particle.height < 0.0 {
+			particle.height = 0.0;
+			particle.vertical_velocity = 0.0;
+			particle.velocity = Vec2::ZERO;
+		}
+
+		let remaining = particle.lifetime.percent_left();
+		for child in children.iter() {
+			if let Ok((mut offset, mut particle_sprite)) = sprite_query.get_mut(*child) {
+				offset.0.y = particle.height;
+				// Fade out over the second half of its life
+				particle_sprite.color.set_a((remaining * 2.0).min(1.0));
+			}
+		}
+	}
+}
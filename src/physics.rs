@@ -1,4 +1,4 @@
-use super::{ui, levels, expand_vec2, collapse_vec3};
+use super::{game_state, levels, expand_vec2, collapse_vec3};
 use bevy::{
 	prelude::*,
 	transform::transform_propagate_system,
@@ -12,7 +12,7 @@ pub struct GeneralPhysicsPlugin;
 impl Plugin for GeneralPhysicsPlugin {
     fn build(&self, app: &mut App) {
         app
-			.add_system(update_movement)
+			.add_system(update_movement.with_run_criteria(game_state::is_world_running))
 			.add_system(do_takes_space_collisions.after(update_movement))
 			.add_system(do_wall_collisions.after(do_takes_space_collisions))
 			.add_plugin(CollisionPlugin::<WallCollidable>::default())
@@ -44,7 +44,7 @@ pub struct Wall {
 }
 // Normal vector of the wall
 #[derive(Component)]
-struct WallInsideDirection(Vec2);
+pub struct WallInsideDirection(pub Vec2);
 impl Wall {
 	pub fn new(point1: Vec2, point2: Vec2, rhs_inside: bool) -> Self {
 		let wall_tangent = (point2 - point1).try_normalize().expect("degenerate wall attempted to be created");
@@ -147,11 +147,7 @@ pub struct Speed(pub Vec2);
 pub fn update_movement(
     time: Res<Time>,
     mut query: Query<(&Speed, &mut Transform)>,
-    spell_ui_active: Res<ui::SpellUiActive>,
 ) {
-    if spell_ui_active.0 {
-        return;
-    }
     for (speed, mut transform) in query.iter_mut() {
         // Update position
         transform.translation += expand_vec2(speed.0 * time.delta_seconds());
@@ -191,7 +187,7 @@ fn rotate_in_plane(point: Vec2, transform: &Transform) -> Vec2 {
 impl Collider {
 	/// Applies a transformation to the given collider.
 	/// Scaling is not supported (although it probably behaves somewhat ok sometimes)
-	fn with_transform(&self, transform: &Transform) -> Self {
+	pub fn with_transform(&self, transform: &Transform) -> Self {
 		match *self {
 			Self::Circle { center, radius } =>
 				Self::Circle {
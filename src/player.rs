@@ -1,9 +1,11 @@
-use super::{physics, spells, sprite, ui, enemy, levels, collapse_vec3};
+use super::{physics, spells, sprite, ui, enemy, levels, difficulty, gamepad, bindings, game_state, sound, feedback, tuning, expand_vec2, collapse_vec3};
 use bevy::{
 	prelude::*,
-	render::camera::ScalingMode
+	render::camera::ScalingMode,
+	utils::Duration,
 };
 use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub struct PlayerPlugin;
 
@@ -16,14 +18,17 @@ impl Plugin for PlayerPlugin {
 			.add_startup_system(camera_setup)
 			.add_system(do_give_staff)
 			.add_system(do_respawn_events)
-			.add_system(flicker_if_intangible)
-            .add_system(update_spell_casting)
-			.add_system(update_player_state.after(update_spell_casting).before(player_movement))
-            .add_system(player_movement.before(physics::update_movement))
-			.add_system(update_take_damage.before(update_spell_casting).before(player_movement).before(update_player_state))
-			.add_system(regen_player_mana.before(update_spell_casting))
-            .add_system(update_player_animation.after(player_movement).after(update_player_state))
-			.add_system_to_stage(CoreStage::PostUpdate, update_camera.before(sprite::facing_sprite_update));
+			.add_system(do_new_game)
+			.add_system(update_low_health_tint.with_run_criteria(game_state::is_world_running).after(update_take_damage))
+            .add_system(update_spell_casting.with_run_criteria(game_state::is_world_running).after(gamepad::update_gamepad_state))
+			.add_system(update_player_state.with_run_criteria(game_state::is_world_running).after(update_spell_casting).before(player_movement))
+            .add_system(player_movement.with_run_criteria(game_state::is_world_running).before(physics::update_movement).after(gamepad::update_gamepad_state))
+			.add_system(update_take_damage.with_run_criteria(game_state::is_world_running).before(update_spell_casting).before(player_movement).before(update_player_state))
+			.add_system(regen_player_mana.with_run_criteria(game_state::is_world_running).before(update_spell_casting))
+			.add_system(regen_player_stamina.with_run_criteria(game_state::is_world_running).after(player_movement))
+			.add_system(apply_player_tuning.after(tuning::apply_tuning))
+            .add_system(update_player_animation.with_run_criteria(game_state::is_world_running).after(player_movement).after(update_player_state).before(sprite::update_animators))
+			.add_system_to_stage(CoreStage::PostUpdate, update_camera.with_run_criteria(game_state::is_world_running).before(sprite::facing_sprite_update));
     }
 }
 
@@ -38,6 +43,7 @@ pub enum PlayerState {
 	Normal,
 	Casting,
 	Knockback,
+	Dodging,
 }
 #[derive(Component, Debug)]
 pub struct PlayerSpriteMarker;
@@ -46,19 +52,36 @@ pub struct PlayerVulnerability {
 	tangible: bool,
 	hit_timer: Timer,
 	knockback_timer: Timer,
+	/// Also intangible while this is running, but without flickering
+	dodge_timer: Timer,
 }
 #[derive(Component, Debug)]
 pub struct PlayerHealth {
 	pub health: i32,
 	pub max_health: i32,
+	/// The UI draws health in hearts of this much each
+	health_per_heart: i32,
 }
 #[derive(Component, Debug)]
 pub struct PlayerMana {
 	pub mana: i32,
 	pub max_mana: i32,
+	/// The UI draws mana in orbs of this much each
+	mana_per_orb: i32,
 	recharge_rate: f32,
 	recharge_spillover: f32,
 }
+/// Never takes damage; toggled from the developer console
+#[cfg(feature = "dev-console")]
+#[derive(Component, Debug)]
+pub struct GodMode;
+#[derive(Component, Debug)]
+pub struct PlayerStamina {
+	pub stamina: f32,
+	pub max_stamina: f32,
+	/// Stamina only starts coming back a little after it was last used
+	regen_delay: Timer,
+}
 
 // Event for giving the staff
 pub struct GiveStaffEvent;
@@ -82,23 +105,38 @@ fn do_give_staff(
 	}
 }
 
+/// Takes away everything the player picked up, for starting over.
+/// Respawning when going to the first room takes care of the rest.
+fn do_new_game(
+	mut player_query: Query<&mut PlayerHasStaff, With<Player>>,
+	mut player_sprite_query: Query<&mut Handle<TextureAtlas>, With<PlayerSpriteMarker>>,
+	mut equipped_runes: ResMut<spells::EquippedRunes>,
+	mut new_game_events: EventReader<game_state::NewGameEvent>,
+	sprite_sheets: Res<PlayerSpriteSheets>,
+) {
+	if new_game_events.iter().next().is_none() {
+		return;
+	}
+	
+	player_query.single_mut().0 = false;
+	*player_sprite_query.single_mut() = sprite_sheets.no_staff.clone();
+	for i in 0..5 {
+		equipped_runes.set(i, None);
+	}
+}
+
 // resource for sprite sheets
 pub struct PlayerSpriteSheets {
-	//no_staff: Handle<TextureAtlas>,
+	no_staff: Handle<TextureAtlas>,
 	with_staff: Handle<TextureAtlas>,
 }
 
-const HEALTH_PER_HEART: i32 = 4;
-const MANA_PER_ORB: i32 = 20;
-const BASE_MANA_REGEN: f32 = 5.0;
-const MANA_REGEN_DEFACTOR: f32 = 10.0;
-const PLAYER_KNOCKBACK_SPEED: f32 = 50.0;
-
 fn player_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-	shadow_texture: Res<sprite::ShadowTexture>,
+	bindings: Res<bindings::KeyBindings>,
+	tuning: Res<tuning::Tuning>,
 ) {
     // Player sprite info
 	// TODO put in resource
@@ -128,13 +166,14 @@ fn player_setup(
         .insert(Player)
 		.insert(PlayerHasStaff(false))
 		.insert(CurrentPlayerState(PlayerState::Normal))
-		.insert(PlayerVulnerability::new())
-		.insert(PlayerHealth::new(4))
-		.insert(PlayerMana::new(4))
+		.insert(PlayerVulnerability::new(&tuning.player))
+		.insert(PlayerHealth::new(4, tuning.player.health_per_heart))
+		.insert(PlayerMana::new(4, tuning.player.mana_per_orb))
+		.insert(PlayerStamina::new(&tuning.player))
         .insert(physics::Speed(Vec2::ZERO))
         .insert_bundle(InputManagerBundle::<Action> {
             action_state: ActionState::default(),
-            input_map: get_input_map(),
+            input_map: bindings.to_input_map(),
         })
         .insert_bundle(SpatialBundle::default())
         .insert(spells::RuneCastQueue::new())
@@ -142,28 +181,27 @@ fn player_setup(
 		.insert(physics::CollisionRecipient::<physics::InteractsWithPlayer>::new(collider.clone()))
 		.insert(physics::ColliderActive::<physics::InteractsWithPlayer>::new(true))
 		.insert(physics::SymmetricCollisionSource::<physics::TakesSpace>::new(collider))
+		.insert(sprite::CastsShadow::new(2, 22.0))
+		.insert(sprite::SpriteTint::new(Color::WHITE))
         .with_children(|parent| {
             // Manage the sprite properly
             parent
                 .spawn()
                 .insert(PlayerSpriteMarker)
                 .insert(sprite::FacingSpriteMarker)
-                .insert(sprite::AnimationTimer(Timer::from_seconds(1.0 / 7.0, true)))
+                .insert(sprite::Animator::new(asset_server.load("animations/player.anim.ron")))
                 .insert(PlayerAnimationState {
-                    anim_state: AnimationState::Idle,
                     facing_dir: FacingDir::Right,
-                    index: 0,
                 })
                 .insert(sprite::SpriteOffset(Vec3::new(0.0, 22.0, 0.0)))
                 .insert_bundle(SpriteSheetBundle {
-                    texture_atlas: player_texture_atlas,
+                    texture_atlas: player_texture_atlas.clone(),
                     ..default()
                 });
-			parent.spawn_bundle(shadow_texture.get_shadow_bundle(2));
         });
 		
 	commands.insert_resource(PlayerSpriteSheets {
-		//no_staff: player_texture_atlas,
+		no_staff: player_texture_atlas,
 		with_staff: player_staff_texture_atlas,
 	});
 }
@@ -181,6 +219,7 @@ fn update_player_state(
 	
 	player_vulnerability.hit_timer.tick(time.delta());
 	player_vulnerability.knockback_timer.tick(time.delta());
+	player_vulnerability.dodge_timer.tick(time.delta());
 	
 	// Update state
 	player_state.0 = match player_state.0 {
@@ -198,6 +237,13 @@ fn update_player_state(
 				PlayerState::Knockback
 			}
 		}
+		PlayerState::Dodging => {
+			if player_vulnerability.is_dodging() {
+				PlayerState::Dodging
+			} else {
+				PlayerState::Normal
+			}
+		}
 	};
 	// Update vulnerability
 	if player_vulnerability.hit_timer.finished() {
@@ -205,32 +251,33 @@ fn update_player_state(
 	}
 }
 
-const FLICKER_TIME: f32 = 0.1;
-fn flicker_if_intangible(
-	mut query: Query<(&mut Visibility, &PlayerVulnerability, &PlayerHealth), With<Player>>,
-	time: Res<Time>,
-    spell_ui_active: Res<ui::SpellUiActive>,
+const FLICKER_PERIOD: f32 = 0.2;
+/// At or below one heart, the player pulses red
+const LOW_HEALTH_PULSE_PERIOD: f32 = 0.8;
+
+fn update_low_health_tint(
+	mut query: Query<(&mut sprite::SpriteTint, &PlayerHealth), With<Player>>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
+	let (mut tint, health) = query.single_mut();
+	let is_low = health.health > 0 && health.health <= health.health_per_heart;
 	
-	let (mut visibility, vulnerability, health) = query.single_mut();
-	let current_time = time.time_since_startup().as_secs_f32();
-	
-	visibility.is_visible = (vulnerability.tangible && health.health > 0)
-		|| (current_time % (2.0 * FLICKER_TIME)) < FLICKER_TIME;
+	if is_low && !tint.has(sprite::TintSource::LowHealth) {
+		tint.push(sprite::Tint::new(
+			sprite::TintSource::LowHealth,
+			sprite::TintEffect::Mix(Color::hex("E83B3B").unwrap(), 0.5),
+			sprite::TintEnvelope::Pulse(LOW_HEALTH_PULSE_PERIOD),
+		));
+	} else if !is_low {
+		tint.remove(sprite::TintSource::LowHealth);
+	}
 }
 
 fn regen_player_mana(
 	mut query: Query<&mut PlayerMana, With<Player>>,
 	time: Res<Time>,
-    spell_ui_active: Res<ui::SpellUiActive>,
+	difficulty: Res<difficulty::Difficulty>,
+	tuning: Res<tuning::Tuning>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
-	
 	let mut player_mana = query.single_mut();
 	
 	if player_mana.mana == player_mana.max_mana {
@@ -238,7 +285,8 @@ fn regen_player_mana(
 	}
 	
 	let mana_increase = player_mana.recharge_spillover
-		+ BASE_MANA_REGEN * (1.0 + player_mana.recharge_rate / MANA_REGEN_DEFACTOR).sqrt() * time.delta_seconds();
+		+ tuning.player.base_mana_regen * difficulty.mana_regen
+		* (1.0 + player_mana.recharge_rate / tuning.player.mana_regen_defactor).sqrt() * time.delta_seconds();
 	
 	
 	let mana_increase_rounded = mana_increase.floor();
@@ -263,121 +311,193 @@ fn regen_player_mana(
 
 // Health/mana stuff
 impl PlayerHealth {
-	fn new(n_hearts: u8) -> Self {
-		let health = n_hearts as i32 * HEALTH_PER_HEART;
+	fn new(n_hearts: u8, health_per_heart: i32) -> Self {
+		let health = n_hearts as i32 * health_per_heart;
 		PlayerHealth {
 			health,
-			max_health: health
+			max_health: health,
+			health_per_heart,
 		}
 	}
 	pub fn get_heart_count(&self) -> usize {
-		(self.max_health / HEALTH_PER_HEART) as usize
+		(self.max_health / self.health_per_heart) as usize
 	}
 	pub fn get_filled_heart_count(&self) -> usize {
 		if self.health < 0 {
 			0
 		} else {
-			(self.health / HEALTH_PER_HEART) as usize
+			(self.health / self.health_per_heart) as usize
 		}
 	}
-	pub fn get_last_heart_state(&self) -> usize {
-		if self.health < 0 {
+	pub fn get_last_heart_state(&self, n_heart_states: usize) -> usize {
+		if self.health <= 0 {
 			0
 		} else {
-			(self.health % HEALTH_PER_HEART) as usize
+			let leftover = self.health % self.health_per_heart;
+			let float_amt = (leftover as f32 / self.health_per_heart as f32) * (n_heart_states-1) as f32;
+			float_amt.round() as usize
 		}
 	}
+	/// Keeps the number of hearts, and current health in proportion
+	fn set_health_per_heart(&mut self, health_per_heart: i32) {
+		let new_max = self.get_heart_count() as i32 * health_per_heart;
+		self.health = rescale(self.health, self.max_health, new_max);
+		self.max_health = new_max;
+		self.health_per_heart = health_per_heart;
+	}
 }
 impl PlayerMana {
-	fn new(n_orbs: u8) -> Self {
-		let mana = n_orbs as i32 * MANA_PER_ORB;
+	fn new(n_orbs: u8, mana_per_orb: i32) -> Self {
+		let mana = n_orbs as i32 * mana_per_orb;
 		PlayerMana {
 			mana,
 			max_mana: mana,
+			mana_per_orb,
 			recharge_rate: 0.0,
 			recharge_spillover: 0.0,
 		}
 	}
 	
 	pub fn get_orb_count(&self) -> usize {
-		(self.max_mana / MANA_PER_ORB) as usize
+		(self.max_mana / self.mana_per_orb) as usize
 	}
 	pub fn get_filled_orb_count(&self) -> usize {
 		if self.mana < 0 {
 			0
 		} else {
-			(self.mana / MANA_PER_ORB) as usize
+			(self.mana / self.mana_per_orb) as usize
 		}
 	}
 	pub fn get_last_orb_state(&self, n_orb_states: usize) -> usize {
 		if self.mana <= 0 {
 			0
 		} else {
-			let leftover = self.mana % MANA_PER_ORB;
-			let float_amt = (leftover as f32 / MANA_PER_ORB as f32) * (n_orb_states-1) as f32;
+			let leftover = self.mana % self.mana_per_orb;
+			let float_amt = (leftover as f32 / self.mana_per_orb as f32) * (n_orb_states-1) as f32;
 			float_amt.round() as usize
 		}
 	}
+	/// Keeps the number of orbs, and current mana in proportion
+	fn set_mana_per_orb(&mut self, mana_per_orb: i32) {
+		let new_max = self.get_orb_count() as i32 * mana_per_orb;
+		self.mana = rescale(self.mana, self.max_mana, new_max);
+		self.max_mana = new_max;
+		self.mana_per_orb = mana_per_orb;
+	}
+}
+
+/// Scales value from being out of old_max to being out of new_max, rounding up so nothing drops to 0
+fn rescale(value: i32, old_max: i32, new_max: i32) -> i32 {
+	if old_max <= 0 || value <= 0 {
+		return value.min(new_max);
+	}
+	(value as f32 * new_max as f32 / old_max as f32).ceil() as i32
 }
 
 impl PlayerVulnerability {
-	fn new() -> Self {
+	fn new(tuning: &tuning::PlayerTuning) -> Self {
+		let mut dodge_timer = Timer::from_seconds(tuning.dodge_time, false);
+		// Not dodging until the first dodge
+		dodge_timer.tick(Duration::from_secs_f32(tuning.dodge_time));
 		Self {
 			tangible: true,
 			hit_timer: Timer::from_seconds(1.0, false),
 			knockback_timer: Timer::from_seconds(0.5, false),
+			dodge_timer,
+		}
+	}
+	
+	fn is_dodging(&self) -> bool {
+		!self.dodge_timer.finished()
+	}
+}
+
+impl PlayerStamina {
+	fn new(tuning: &tuning::PlayerTuning) -> Self {
+		Self {
+			stamina: tuning.max_stamina,
+			max_stamina: tuning.max_stamina,
+			regen_delay: Timer::from_seconds(tuning.stamina_regen_delay, false),
+		}
+	}
+	
+	/// Uses up stamina if there's any left. Returns whether there was.
+	fn try_use(&mut self, amount: f32) -> bool {
+		if self.stamina <= 0.0 {
+			return false;
+		}
+		
+		self.stamina = (self.stamina - amount).max(0.0);
+		self.regen_delay.reset();
+		true
+	}
+
+	/// Uses up stamina only if there's at least the whole amount left. Returns whether there was.
+	fn try_use_all(&mut self, amount: f32) -> bool {
+		if self.stamina < amount {
+			return false;
 		}
+		
+		self.try_use(amount)
 	}
 }
 
 fn update_take_damage(
-	mut commands: Commands,
 	mut player_query: Query<(
 		&mut CurrentPlayerState, 
 		&mut PlayerHealth, 
 		&mut PlayerVulnerability, 
 		&mut physics::Speed, 
-		&Transform
+		&Transform,
+		&mut sprite::SpriteTint,
 	), With<Player>>,
 	mut message_events: EventWriter<ui::MessageEvent>,
 	damage_query: Query<(&enemy::DamagePlayerComponent, &Transform)>,
 	collisions: Res<physics::ActiveCollisions<physics::InteractsWithPlayer>>,
-    spell_ui_active: Res<ui::SpellUiActive>,
+	difficulty: Res<difficulty::Difficulty>,
+	mut state: ResMut<State<game_state::GameState>>,
+	mut sound_events: EventWriter<sound::PlaySoundEvent>,
+	mut damage_events: EventWriter<feedback::DamageEvent>,
+	tuning: Res<tuning::Tuning>,
+	#[cfg(feature = "dev-console")]
+	god_query: Query<(), (With<Player>, With<GodMode>)>,
 ) {
-	if spell_ui_active.0 {
+	#[cfg(feature = "dev-console")]
+	if !god_query.is_empty() {
 		return;
 	}
-	
-	let (mut current_state, mut player_health, mut player_vulnerability, mut speed, player_transform) = player_query.single_mut();
+
+	let (mut current_state, mut player_health, mut player_vulnerability, mut speed, player_transform, mut tint) = player_query.single_mut();
 	// Only process if tangible
-	if !player_vulnerability.tangible {
+	if !player_vulnerability.tangible || player_vulnerability.is_dodging() {
 		return;
 	}
 	
 	for collision in collisions.iter() {
 		if let Ok((damage_component, enemy_transform)) = damage_query.get(collision.source_entity) {
-			if damage_component.0 <= 0 {
+			let damage = difficulty.scale_enemy_damage(damage_component.0);
+			if damage <= 0 {
 				continue;
 			}
 			
 			// take damage
-			player_health.health -= damage_component.0;
+			player_health.health -= damage;
+			damage_events.send(feedback::DamageEvent {
+				position: collapse_vec3(player_transform.translation),
+				amount: damage,
+				target: feedback::DamageTarget::Player,
+			});
 			// Check if we just died
-			if player_health.health <= 0 && player_health.health + damage_component.0 > 0 {
-				// we just did; send an event
-				commands.spawn()
-					.insert(levels::CleanUpOnRoomLoad)
-					.insert(levels::DelayedRoomTransition::new(
-						levels::RoomTransitionEvent(levels::DestinationRoom::TargetRoom {
-							target: 1, // TODO update this when/if savepoints are introduced
-							respawn: true
-						}),
-						3.0
-					));
+			if player_health.health <= 0 && player_health.health + damage > 0 {
+				// we just did; the game over screen takes it from here
 				message_events.send(ui::MessageEvent {
 					message: Some("You have been defeated.".to_string()),
 					source: ui::MessageSource::Defeated,
 				});
+				game_state::set_state(&mut state, game_state::GameState::GameOver);
+				sound_events.send(sound::PlaySoundEvent(sound::SoundEffect::PlayerDefeated));
+			} else if player_health.health > 0 {
+				sound_events.send(sound::PlaySoundEvent(sound::SoundEffect::PlayerHurt));
 			}
 			
 			// knockback
@@ -388,13 +508,26 @@ fn update_take_damage(
 				Some(d) => d,
 				None => Vec2::X,
 			};
-			speed.0 = knockback_direction * PLAYER_KNOCKBACK_SPEED;
+			speed.0 = knockback_direction * tuning.player.knockback_speed;
 			
 			// intangibility
 			player_vulnerability.tangible = false;
+			player_vulnerability.hit_timer.set_duration(difficulty.get_invulnerability_duration());
 			player_vulnerability.hit_timer.reset();
 			player_vulnerability.knockback_timer.reset();
 			
+			// flash, then flicker until tangible again
+			tint.push(sprite::Tint::new(
+				sprite::TintSource::Hit,
+				sprite::TintEffect::Mix(Color::WHITE, 0.9),
+				sprite::TintEnvelope::FadeOut,
+			).with_duration(sprite::HIT_FLASH_TIME));
+			tint.push(sprite::Tint::new(
+				sprite::TintSource::Intangible,
+				sprite::TintEffect::Opacity(0.3),
+				sprite::TintEnvelope::Pulse(FLICKER_PERIOD),
+			).with_duration(difficulty.get_invulnerability_duration().as_secs_f32()));
+			
 			// only get hit once
 			return;
 		}
@@ -413,92 +546,54 @@ pub enum AnimationState {
     Walk,
     Casting,
 	Knockback,
+	Dodge,
+	Defeated,
 }
-struct AnimationDescription {
-	start_index: usize,
-	length: usize,
-	reversed: bool,
-	priority: i32, // knockback > casting > walk = idle
-}
-// and a function that turns one into the other
-fn get_animation_description(facing_dir: FacingDir, anim_state: AnimationState) -> AnimationDescription {
-	let priority = match anim_state {
-		AnimationState::Idle | AnimationState::Walk => 0,
-		AnimationState::Casting => 5,
-		AnimationState::Knockback => 10,
-	};
-	
-	let reversed = match facing_dir {
-		FacingDir::Left => true,
-		FacingDir::Right => false,
-	};
-	
-	let length = match anim_state {
-		AnimationState::Idle | AnimationState::Knockback => 1,
-		AnimationState::Walk => 8,
-		AnimationState::Casting => 3,
-	};
-	
-	let start_index = match (facing_dir, anim_state) {
-		(FacingDir::Left, AnimationState::Idle) => 2,
-		(FacingDir::Left, AnimationState::Walk) => 16,
-		(FacingDir::Left, AnimationState::Casting) => 29,
-		(FacingDir::Left, AnimationState::Knockback) => 0,
-		(FacingDir::Right, AnimationState::Idle) => 0,
-		(FacingDir::Right, AnimationState::Walk) => 8,
-		(FacingDir::Right, AnimationState::Casting) => 24,
-		(FacingDir::Right, AnimationState::Knockback) => 2,
-	};
-	
-	AnimationDescription {
-		start_index,
-		length,
-		reversed,
-		priority,
+
+impl AnimationState {
+	fn get_name(&self) -> &'static str {
+		match self {
+			AnimationState::Idle => "idle",
+			AnimationState::Walk => "walk",
+			AnimationState::Casting => "casting",
+			AnimationState::Knockback => "hurt",
+			AnimationState::Dodge => "dodge",
+			AnimationState::Defeated => "defeated",
+		}
+	}
+}
+
+/// The clips in player.anim.ron are named like walk_left
+fn get_clip_name(name: &str, facing_dir: FacingDir) -> String {
+	match facing_dir {
+		FacingDir::Left => format!("{}_left", name),
+		FacingDir::Right => format!("{}_right", name),
 	}
 }
 
 #[derive(Component)]
 pub struct PlayerAnimationState {
 	facing_dir: FacingDir,
-	anim_state: AnimationState,
-	index: usize,
 }
 
+/// Picks which clip the player's sprite should be playing; sprite::update_animators handles the rest
 fn update_player_animation (
-    time: Res<Time>,
-    mut anim_query: Query<
-		(&mut sprite::AnimationTimer, &mut TextureAtlasSprite, &mut PlayerAnimationState),
-        With<PlayerSpriteMarker>>,
-	player_query: Query<(&CurrentPlayerState, &physics::Speed), With<Player>>,
-    spell_ui_active: Res<ui::SpellUiActive>,
+    mut anim_query: Query<(&mut sprite::Animator, &mut PlayerAnimationState), With<PlayerSpriteMarker>>,
+	player_query: Query<(&CurrentPlayerState, &physics::Speed, &PlayerHealth), With<Player>>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
+	let (player_state, player_speed, player_health) = player_query.single();
+	let (mut animator, mut current_anim_state) = anim_query.single_mut();
 	
-	let (player_state, player_speed) = player_query.single();
-	let (mut timer, mut sprite, mut current_anim_state) = anim_query.single_mut();
-	
-	timer.tick(time.delta());
-	
-	let anim_desc = get_animation_description(
-		current_anim_state.facing_dir,
-		current_anim_state.anim_state,
-	);
-	
-	// Sort of trying to deal with the next state
 	// What the facing direction should be
-	let next_facing_dir = if player_speed.0.x > 0.1 {
-		FacingDir::Right
+	if player_speed.0.x > 0.1 {
+		current_anim_state.facing_dir = FacingDir::Right;
 	} else if player_speed.0.x < -0.1 {
-		FacingDir::Left
-	} else {
-		current_anim_state.facing_dir
-	};
+		current_anim_state.facing_dir = FacingDir::Left;
+	}
 	
 	// What the animation state should be
-	let next_anim_state = match player_state.0 {
+	let anim_state = match player_state.0 {
+		_ if player_health.health <= 0 => AnimationState::Defeated,
 		PlayerState::Normal => if player_speed.0.length() > 10.0 {
 				AnimationState::Walk
 			} else {
@@ -506,93 +601,134 @@ fn update_player_animation (
 			}
 		PlayerState::Casting => AnimationState::Casting,
 		PlayerState::Knockback => AnimationState::Knockback,
+		PlayerState::Dodging => AnimationState::Dodge,
 	};
 	
-	let next_anim_desc = get_animation_description(
-		next_facing_dir,
-		next_anim_state,
-	);
-	
-	// Determine if we need to change animation, and if we should without waiting for the next timer tick
-	let change_animation = current_anim_state.facing_dir != next_facing_dir 
-		|| current_anim_state.anim_state != next_anim_state;
-	let interrupt_immediate = change_animation && anim_desc.priority < next_anim_desc.priority;
-		
-	if change_animation {
-		if timer.just_finished() || interrupt_immediate {
-			if interrupt_immediate {
-				timer.reset();
-			}
-			current_anim_state.index = 0;
-			current_anim_state.facing_dir = next_facing_dir;
-			current_anim_state.anim_state = next_anim_state;
-			update_sprite(&mut sprite, current_anim_state.index, next_anim_desc);
-		}
-	} else {
-		// Increment the index if the timer is done
-		if timer.just_finished() {
-			current_anim_state.index = (current_anim_state.index + 1) % anim_desc.length;
-			update_sprite(&mut sprite, current_anim_state.index, anim_desc);
-		}
-	};
-}
-
-/// Updates the TextureAtlasSprite to have the proper texture index
-fn update_sprite(sprite: &mut TextureAtlasSprite, index: usize, anim_desc: AnimationDescription) {
-	sprite.index = if anim_desc.reversed {
-		anim_desc.start_index
-			+ (anim_desc.length - index - 1)
-	} else {
-		anim_desc.start_index + index
-	};
+	animator.request(&get_clip_name(anim_state.get_name(), current_anim_state.facing_dir));
 }
 
 // Movement
 #[derive(Component, Default, Debug)]
 struct PlayerSpeed(Vec3);
 
+/// Direction the player is holding, with length up to 1
+fn get_movement_input(action_state: &ActionState<Action>, gamepad_state: &gamepad::GamepadState) -> Vec2 {
+	let mut total_offset = Vec2::splat(0.0);
+	
+	if action_state.pressed(Action::Up) {
+		total_offset.y -= 1.0;
+	}
+	if action_state.pressed(Action::Down) {
+		total_offset.y += 1.0;
+	}
+	if action_state.pressed(Action::Right) {
+		total_offset.x += 1.0;
+	}
+	if action_state.pressed(Action::Left) {
+		total_offset.x -= 1.0;
+	}
+	
+	if total_offset == Vec2::ZERO {
+		// Analog movement
+		gamepad_state.movement
+	} else {
+		total_offset.normalize()
+	}
+}
+
 fn player_movement(
     action_state: Query<&ActionState<Action>, With<Player>>,
-    mut player_query: Query<(&mut physics::Speed, &CurrentPlayerState, &PlayerHealth), With<Player>>,
+    mut player_query: Query<(
+		&mut physics::Speed,
+		&mut CurrentPlayerState,
+		&PlayerHealth,
+		&mut PlayerStamina,
+		&mut PlayerVulnerability,
+	), With<Player>>,
+	anim_query: Query<&PlayerAnimationState, With<PlayerSpriteMarker>>,
     time: Res<Time>,
-    spell_ui_active: Res<ui::SpellUiActive>,
+    gamepad_state: Res<gamepad::GamepadState>,
+	tuning: Res<tuning::Tuning>,
 ) {
-    if spell_ui_active.0 {
-        return;
-    }
     let action_state = action_state.single();
-    let (mut speed, player_state, player_health) = player_query.single_mut();
-
-	if player_state.0 != PlayerState::Knockback {
-		let mut total_offset = Vec2::splat(0.0);
+    let (mut speed, mut player_state, player_health, mut stamina, mut vulnerability) = player_query.single_mut();
+	
+	let can_move = player_state.0 == PlayerState::Normal && player_health.health > 0;
+	let input = if can_move {
+		get_movement_input(action_state, &gamepad_state)
+	} else {
+		Vec2::ZERO
+	};
+	
+	// Start a dodge
+	if can_move && action_state.just_pressed(Action::Dodge) && stamina.try_use_all(tuning.player.dodge_stamina_cost) {
+		let direction = input.try_normalize().unwrap_or(match anim_query.single().facing_dir {
+			FacingDir::Right => Vec2::X,
+			FacingDir::Left => -Vec2::X,
+		});
 		
-		if player_state.0 == PlayerState::Normal && player_health.health > 0 {
-			if action_state.pressed(Action::Up) {
-				total_offset.y -= 1.0;
-			}
-			if action_state.pressed(Action::Down) {
-				total_offset.y += 1.0;
-			}
-			if action_state.pressed(Action::Right) {
-				total_offset.x += 1.0;
-			}
-			if action_state.pressed(Action::Left) {
-				total_offset.x -= 1.0;
-			}
+		speed.0 = direction * tuning.player.dodge_speed;
+		player_state.0 = PlayerState::Dodging;
+		vulnerability.dodge_timer = Timer::from_seconds(tuning.player.dodge_time, false);
+		return;
+	}
+	
+	match player_state.0 {
+		// Keep moving the way we were going
+		PlayerState::Knockback | PlayerState::Dodging => {}
+		PlayerState::Normal | PlayerState::Casting => {
+			let is_running = input != Vec2::ZERO
+				&& action_state.pressed(Action::Run)
+				&& stamina.try_use(tuning.player.run_stamina_cost * time.delta_seconds());
+			let max_speed = if is_running {
+				tuning.player.speed * tuning.player.run_speed_multiplier
+			} else {
+				tuning.player.speed
+			};
+			
+			// Update speed
+			let target_speed = input * max_speed;
+			
+			speed.0.x = update_speed(speed.0.x, target_speed.x, time.delta_seconds(), &tuning.player);
+			speed.0.y = update_speed(speed.0.y, target_speed.y, time.delta_seconds(), &tuning.player);
 		}
+	}
+}
 
-		// Update speed
-		let target_speed = total_offset.normalize_or_zero() * SPEED;
-
-		speed.0.x = update_speed(speed.0.x, target_speed.x, time.delta_seconds());
-		speed.0.y = update_speed(speed.0.y, target_speed.y, time.delta_seconds());
+fn regen_player_stamina(
+	mut query: Query<&mut PlayerStamina, With<Player>>,
+	time: Res<Time>,
+	tuning: Res<tuning::Tuning>,
+) {
+	let mut stamina = query.single_mut();
+	stamina.regen_delay.tick(time.delta());
+	if stamina.regen_delay.finished() {
+		stamina.stamina = (stamina.stamina + tuning.player.stamina_regen * time.delta_seconds()).min(stamina.max_stamina);
 	}
 }
 
-const SPEED: f32 = 70.0;
-const ACCEL_FORWARD: f32 = 560.0;
-const ACCEL_NEUTRAL: f32 = 360.0;
-const ACCEL_DECEL: f32 = 480.0;
+/// Brings the numbers that were set when the player spawned up to date when the tuning file is reloaded
+fn apply_player_tuning(
+	mut query: Query<(&mut PlayerHealth, &mut PlayerMana, &mut PlayerStamina), With<Player>>,
+	tuning: Res<tuning::Tuning>,
+) {
+	if !tuning.is_changed() {
+		return;
+	}
+	
+	let tuning = &tuning.player;
+	for (mut health, mut mana, mut stamina) in query.iter_mut() {
+		if health.health_per_heart != tuning.health_per_heart {
+			health.set_health_per_heart(tuning.health_per_heart);
+		}
+		if mana.mana_per_orb != tuning.mana_per_orb {
+			mana.set_mana_per_orb(tuning.mana_per_orb);
+		}
+		stamina.max_stamina = tuning.max_stamina;
+		stamina.stamina = stamina.stamina.min(stamina.max_stamina);
+		stamina.regen_delay.set_duration(Duration::from_secs_f32(tuning.stamina_regen_delay));
+	}
+}
 
 /// Moves from start towards limit up to amt.
 fn move_not_past(start: f32, amt: f32, limit: f32) -> f32 {
@@ -610,24 +746,24 @@ fn move_not_past(start: f32, amt: f32, limit: f32) -> f32 {
     }
 }
 
-fn update_speed(current_speed: f32, target_speed: f32, delta: f32) -> f32 {
+fn update_speed(current_speed: f32, target_speed: f32, delta: f32, tuning: &tuning::PlayerTuning) -> f32 {
     // Determine which acceleration to use
     let accel = if target_speed.abs() < 1e-4 {
         // Target speed is zero; decelerate w/ neutral amount
-        ACCEL_NEUTRAL
+        tuning.accel_neutral
     } else {
         // Figure out
         if current_speed.signum() == target_speed.signum() {
             if current_speed.abs() >= target_speed.abs() {
                 // we're going too fast
-                ACCEL_NEUTRAL
+                tuning.accel_neutral
             } else {
                 // speed up
-                ACCEL_FORWARD
+                tuning.accel_forward
             }
         } else {
             // we're going in the wrong direction
-            ACCEL_DECEL
+            tuning.accel_decel
         }
     };
 
@@ -637,19 +773,17 @@ fn update_speed(current_speed: f32, target_speed: f32, delta: f32) -> f32 {
 // Spellcasting
 pub fn update_spell_casting(
     mut query: Query<(&Transform, &ActionState<Action>, &CurrentPlayerState, &PlayerHasStaff, &PlayerHealth, &mut spells::RuneCastQueue, &mut PlayerMana), With<Player>>,
-    anim_query: Query<&PlayerAnimationState, With<PlayerSpriteMarker>>,
+    mut anim_query: Query<(&PlayerAnimationState, &mut sprite::Animator), With<PlayerSpriteMarker>>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
     equipped: Res<spells::EquippedRunes>,
-    spell_ui_active: Res<ui::SpellUiActive>,
     ui_mouse_target: Res<ui::CurrentMouseoverTarget>,
     windows: Res<Windows>,
 	mut create_spell_events: EventWriter<spells::CreateSpellEvent>,
+	mut sound_events: EventWriter<sound::PlaySoundEvent>,
+	difficulty: Res<difficulty::Difficulty>,
+	gamepad_state: Res<gamepad::GamepadState>,
+	tuning: Res<tuning::Tuning>,
 ) {
-    // Don't do anything if the spell UI is open
-    if spell_ui_active.0 {
-        return;
-    }
-
     let (transform, action_state, player_state, has_staff, player_health, mut spell_queue, mut player_mana) = query.single_mut();
 	
 	// Don't do anything if we don't have the staff yet or if we are dead
@@ -674,34 +808,39 @@ pub fn update_spell_casting(
 
     // Check if we want to cast a spell (and aren't clicking on UI)
     if ui_mouse_target.0.is_none() && action_state.just_pressed(Action::CastSpell) {
-		if let Some(spell_data) = spell_queue.generate_spell() {
+		if let Some(spell_data) = spell_queue.generate_spell(&tuning.spells) {
 			// Determine if we have enough mana
-			if player_mana.mana >= spell_data.get_mana_cost() {
-				player_mana.mana -= spell_data.get_mana_cost();
-				player_mana.recharge_rate += spell_data.get_mana_cost() as f32;
+			let mana_cost = difficulty.scale_spell_cost(spell_data.get_mana_cost());
+			if player_mana.mana >= mana_cost {
+				player_mana.mana -= mana_cost;
+				player_mana.recharge_rate += mana_cost as f32;
 			
 				// Figure out where the mouse is pointing
 				let offset = Vec3::new(0.0, 12.0, 0.0);
 				let (camera, camera_transform) = camera_query.single();
-				let anim_state = anim_query.single();
+				let (anim_state, mut animator) = anim_query.single_mut();
 
 				let maybe_world_mouse_position = ui::get_cursor_world_position(
-					windows,
+					&windows,
 					camera,
 					camera_transform,
 					offset,
 					Vec3::Y,
 				);
 
-				let maybe_aim_dir = match maybe_world_mouse_position {
-					Some(mouse_pos) => {
-						(mouse_pos - transform.translation).try_normalize()
+				let maybe_aim_dir = if gamepad_state.in_use {
+					gamepad_state.aim
+				} else {
+					match maybe_world_mouse_position {
+						Some(mouse_pos) => {
+							collapse_vec3(mouse_pos - transform.translation).try_normalize()
+						}
+						None => None,
 					}
-					None => None,
 				};
 
 				let aim_dir = match maybe_aim_dir {
-					Some(aim_dir) => collapse_vec3(aim_dir),
+					Some(aim_dir) => aim_dir,
 					None => match anim_state.facing_dir {
 						FacingDir::Right => Vec2::new(1.0, 0.0),
 						FacingDir::Left => Vec2::new(-1.0, 0.0),
@@ -709,6 +848,8 @@ pub fn update_spell_casting(
 				};
 				
 				let start_pos = collapse_vec3(transform.translation) + 24.0 * aim_dir;
+				animator.trigger(&get_clip_name("cast", anim_state.facing_dir));
+				sound_events.send(sound::PlaySoundEvent(sound::SoundEffect::Cast(spell_data.get_element())));
 				
 				create_spell_events.send(spells::CreateSpellEvent {
 					spell_data,
@@ -729,7 +870,7 @@ pub fn update_spell_casting(
 
 
 pub fn do_respawn_events(
-	mut player_respawn_query: Query<(&mut PlayerHealth, &mut PlayerMana, &mut spells::RuneCastQueue), With<Player>>,
+	mut player_respawn_query: Query<(&mut PlayerHealth, &mut PlayerMana, &mut PlayerStamina, &mut spells::RuneCastQueue), With<Player>>,
 	mut rune_inventory: ResMut<spells::RuneInventory>,
 	mut equipped_runes: ResMut<spells::EquippedRunes>,
 	mut events: EventReader<levels::RoomTransitionEvent>,
@@ -739,10 +880,11 @@ pub fn do_respawn_events(
 		respawn
 	})) = events.iter().next() {
 		if *respawn {
-			let (mut player_health, mut player_mana, mut spell_queue) = player_respawn_query.single_mut();
+			let (mut player_health, mut player_mana, mut player_stamina, mut spell_queue) = player_respawn_query.single_mut();
 			// TODO reset max health/mana to value from save point
 			player_health.health = player_health.max_health;
 			player_mana.mana = player_mana.max_mana;
+			player_stamina.stamina = player_stamina.max_stamina;
 			spell_queue.clear();
 			
 			// Reset runes
@@ -768,7 +910,7 @@ pub fn do_respawn_events(
 }
 
 // Input handling
-#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Serialize, Deserialize)]
 pub enum Action {
     Left,
     Right,
@@ -779,6 +921,8 @@ pub enum Action {
     CastSpell,
     CancelSpell,
     OpenInventory,
+    Pause,
+    ToggleFullscreen,
     SpellComp0,
     SpellComp1,
     SpellComp2,
@@ -786,30 +930,6 @@ pub enum Action {
     SpellComp4,
 }
 
-fn get_input_map() -> InputMap<Action> {
-    InputMap::new([
-        (KeyCode::W, Action::Up),
-        (KeyCode::A, Action::Left),
-        (KeyCode::S, Action::Down),
-        (KeyCode::D, Action::Right),
-        (KeyCode::Up, Action::Up),
-        (KeyCode::Left, Action::Left),
-        (KeyCode::Down, Action::Down),
-        (KeyCode::Right, Action::Right),
-        (KeyCode::LShift, Action::Run),
-        // Spells
-        (KeyCode::Tab, Action::OpenInventory),
-        (KeyCode::Key1, Action::SpellComp0),
-        (KeyCode::Key2, Action::SpellComp1),
-        (KeyCode::Key3, Action::SpellComp2),
-        (KeyCode::Key4, Action::SpellComp3),
-        (KeyCode::E, Action::SpellComp4),
-    ])
-    .insert(MouseButton::Left, Action::CastSpell)
-    .insert(MouseButton::Right, Action::CancelSpell)
-    .build()
-}
-
 pub const SPELL_COMP_ACTIONS: [Action; 5] = [
     Action::SpellComp0,
     Action::SpellComp1,
@@ -819,52 +939,118 @@ pub const SPELL_COMP_ACTIONS: [Action; 5] = [
 ];
 
 // Camera handling
+/// Resource
+/// The area the camera's focus point can be in, on the ground plane (x, z)
+#[derive(Debug, Clone, Copy)]
 pub struct CameraBounds {
-	pub min_x: f32,
-	pub max_x: f32,
+	pub min: Vec2,
+	pub max: Vec2,
 }
 
-fn camera_setup(mut commands: Commands) {
-    let orthographic_projection = OrthographicProjection {
+impl CameraBounds {
+	pub fn new(min: Vec2, max: Vec2) -> Self {
+		Self { min, max }
+	}
+	
+	fn clamp(&self, point: Vec2) -> Vec2 {
+		point.clamp(self.min, self.max)
+	}
+}
+
+/// Resource
+/// Where the camera is looking, on the ground plane
+#[derive(Debug, Default)]
+pub struct CameraFollow {
+	/// Follows the player around, but only once they leave the dead zone
+	anchor: Vec2,
+	/// Smoothly follows the anchor plus the look-ahead
+	focus: Vec2,
+}
+
+/// Where the camera sits relative to the point it looks at
+pub const CAMERA_OFFSET: Vec3 = Vec3::new(0.0, 100.0, 200.0);
+/// How far the player can move from the camera's anchor before it starts following, in each direction
+const CAMERA_DEAD_ZONE: Vec2 = Vec2::new(16.0, 12.0);
+/// How far ahead the camera looks in the aiming direction
+const CAMERA_LOOK_AHEAD: f32 = 32.0;
+/// How quickly the camera catches up; higher is snappier
+const CAMERA_FOLLOW_SPEED: f32 = 6.0;
+
+pub fn camera_projection() -> OrthographicProjection {
+    OrthographicProjection {
         scale: 0.5,
         scaling_mode: ScalingMode::Auto {
             min_width: 640.0,
             min_height: 400.0,
         },
         ..default()
-    };
+    }
+}
 
+fn camera_setup(mut commands: Commands) {
     commands.spawn_bundle(Camera2dBundle {
-        projection: orthographic_projection,
-        transform: Transform::from_xyz(0.0, 100., 200.0).looking_at(Vec3::new(0., 0., 0.), Vec3::Y),
+        projection: camera_projection(),
+        transform: Transform::from_translation(CAMERA_OFFSET).looking_at(Vec3::new(0., 0., 0.), Vec3::Y),
         ..default()
     });
 	
-	commands.insert_resource(CameraBounds {
-		min_x: 0.0,
-		max_x: 100.0
-	});
+	commands.insert_resource(CameraBounds::new(Vec2::ZERO, Vec2::new(100.0, 0.0)));
+	commands.insert_resource(CameraFollow::default());
 	commands.insert_resource(ClearColor(Color::BLACK));
 }
 
-fn update_camera (
-	mut camera_query: Query<&mut Transform, (With<Camera>, Without<Player>)>,
-	player_query: Query<&Transform, (With<Player>, Without<Camera>)>,
+pub fn update_camera (
+	mut camera_query: Query<(&mut Transform, &Camera, &GlobalTransform), Without<Player>>,
+	player_query: Query<(&Transform, &PlayerHasStaff), (With<Player>, Without<Camera>)>,
 	camera_bounds: Res<CameraBounds>,
-    spell_ui_active: Res<ui::SpellUiActive>,
+	mut camera_follow: ResMut<CameraFollow>,
+	camera_shake: Res<feedback::CameraShake>,
+	windows: Res<Windows>,
+	gamepad_state: Res<gamepad::GamepadState>,
+	time: Res<Time>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
+	let (mut camera_transform, camera, camera_global_transform) = camera_query.single_mut();
+	let (player_transform, has_staff) = player_query.single();
+	let player_pos = collapse_vec3(player_transform.translation);
 	
-	let mut camera_transform = camera_query.single_mut();
-	let player_x = player_query.single().translation.x;
+	if camera_bounds.is_changed() {
+		// New room; jump straight to the player
+		camera_follow.anchor = player_pos;
+		camera_follow.focus = camera_bounds.clamp(player_pos);
+	} else {
+		// Drag the anchor along once the player leaves the dead zone
+		let anchor = camera_follow.anchor;
+		camera_follow.anchor = anchor.clamp(player_pos - CAMERA_DEAD_ZONE, player_pos + CAMERA_DEAD_ZONE);
+	}
 	
-	let new_camera_x = match player_x {
-		x if x < camera_bounds.min_x => camera_bounds.min_x,
-		x if x > camera_bounds.max_x => camera_bounds.max_x,
-		x => x
+	// Look ahead wherever the player is aiming
+	let maybe_aim_dir = if !has_staff.0 {
+		None
+	} else if gamepad_state.in_use {
+		gamepad_state.aim
+	} else {
+		ui::get_cursor_world_position(
+			&windows,
+			camera,
+			camera_global_transform,
+			Vec3::new(0.0, 12.0, 0.0),
+			Vec3::Y,
+		).and_then(|mouse_pos| collapse_vec3(mouse_pos - player_transform.translation).try_normalize())
 	};
+	let look_ahead = maybe_aim_dir.unwrap_or(Vec2::ZERO) * CAMERA_LOOK_AHEAD;
+	
+	let target = camera_bounds.clamp(camera_follow.anchor + look_ahead);
+	let follow_amount = 1.0 - (-CAMERA_FOLLOW_SPEED * time.delta_seconds()).exp();
+	let focus = camera_follow.focus;
+	camera_follow.focus = focus + (target - focus) * follow_amount;
+	
+	// Keep the camera on whole pixels in screen space, so facing_sprite_update's rounding still lines up.
+	// Moving along the view direction doesn't change anything on screen, so that part is left alone.
+	let mut screen_focus = camera_transform.rotation.inverse() * expand_vec2(camera_follow.focus);
+	screen_focus.x = screen_focus.x.round();
+	screen_focus.y = screen_focus.y.round();
+	// Shake happens in screen space, on top of the usual position
+	screen_focus += camera_shake.get_offset().extend(0.0);
 	
-	camera_transform.translation.x = new_camera_x;
+	camera_transform.translation = camera_transform.rotation * screen_focus + CAMERA_OFFSET;
 }
\ No newline at end of file
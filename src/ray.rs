@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+
+// Utilities for going between screen space and world space.
+// Everything here works off of the camera's actual matrices, so it keeps working
+// no matter how the camera is positioned, rotated or zoomed.
+
+/// A half-line in world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+	pub origin: Vec3,
+	/// Always normalized
+	pub direction: Vec3,
+}
+
+impl Ray {
+	/// Returns None if the direction is zero
+	pub fn new(origin: Vec3, direction: Vec3) -> Option<Self> {
+		Some(Self {
+			origin,
+			direction: direction.try_normalize()?,
+		})
+	}
+
+	/// Gets the ray going through a point in normalized device coordinates
+	/// (-1 to 1 on both axes, y pointing up).
+	/// Starts on the camera's near plane, and works for both orthographic and perspective projections.
+	pub fn from_ndc(camera: &Camera, camera_transform: &GlobalTransform, ndc: Vec2) -> Option<Self> {
+		Self::from_ndc_with_matrices(camera.projection_matrix(), camera_transform.compute_matrix(), ndc)
+	}
+
+	fn from_ndc_with_matrices(projection: Mat4, camera_matrix: Mat4, ndc: Vec2) -> Option<Self> {
+		let ndc_to_world = camera_matrix * projection.inverse();
+		// Bevy uses reversed z, so the near plane is at 1.
+		// The far plane is at 0, which is infinitely far away with a perspective projection, so stop just short of it
+		let near = ndc_to_world.project_point3(ndc.extend(1.0));
+		let far = ndc_to_world.project_point3(ndc.extend(f32::EPSILON));
+
+		if far.is_finite() {
+			Self::new(near, far - near)
+		} else {
+			// Shouldn't happen with the projections bevy has, but a perspective ray still goes out from the camera
+			let camera_pos = camera_matrix.transform_point3(Vec3::ZERO);
+			Self::new(near, near - camera_pos)
+		}
+	}
+
+	/// Gets the ray going through a position in a viewport of the given size.
+	/// The position is measured from the top left, in the same units as the size.
+	pub fn from_viewport_position(
+		camera: &Camera,
+		camera_transform: &GlobalTransform,
+		viewport_size: Vec2,
+		position: Vec2,
+	) -> Option<Self> {
+		let relative_pos = position / viewport_size;
+		let ndc = Vec2::new(relative_pos.x * 2.0 - 1.0, 1.0 - relative_pos.y * 2.0);
+		Self::from_ndc(camera, camera_transform, ndc)
+	}
+
+	pub fn get_point(&self, distance: f32) -> Vec3 {
+		self.origin + self.direction * distance
+	}
+
+	/// Distance along the ray at which it meets the plane containing `plane_point` with normal `plane_normal`.
+	/// Returns None if they are parallel.
+	/// Hits behind the origin count too, since things can be behind the camera's near plane
+	/// with an orthographic projection and still be visible.
+	pub fn intersect_plane(&self, plane_point: Vec3, plane_normal: Vec3) -> Option<f32> {
+		let denominator = self.direction.dot(plane_normal);
+		if denominator.abs() < f32::EPSILON {
+			return None;
+		}
+
+		Some((plane_point - self.origin).dot(plane_normal) / denominator)
+	}
+}
+
+/// Projects a point into normalized device coordinates.
+/// Returns None if it's outside of the camera's depth range.
+pub fn world_to_ndc(camera: &Camera, camera_transform: &GlobalTransform, world_pos: Vec3) -> Option<Vec3> {
+	world_to_ndc_with_matrices(camera.projection_matrix(), camera_transform.compute_matrix(), world_pos)
+}
+
+fn world_to_ndc_with_matrices(projection: Mat4, camera_matrix: Mat4, world_pos: Vec3) -> Option<Vec3> {
+	let ndc = (projection * camera_matrix.inverse()).project_point3(world_pos);
+
+	if ndc.is_finite() && (0.0..=1.0).contains(&ndc.z) {
+		Some(ndc)
+	} else {
+		None
+	}
+}
+
+/// The inverse of Ray::from_viewport_position; gets where a point shows up in a viewport of the given size,
+/// measured from the top left.
+pub fn world_to_viewport_position(
+	camera: &Camera,
+	camera_transform: &GlobalTransform,
+	viewport_size: Vec2,
+	world_pos: Vec3,
+) -> Option<Vec2> {
+	let ndc = world_to_ndc(camera, camera_transform, world_pos)?;
+	let relative_pos = Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) / 2.0;
+	Some(relative_pos * viewport_size)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bevy::render::camera::CameraProjection;
+
+	fn perspective_projection() -> Mat4 {
+		let mut projection = PerspectiveProjection::default();
+		projection.update(640.0, 400.0);
+		projection.get_projection_matrix()
+	}
+
+	fn assert_close(a: Vec3, b: Vec3) {
+		assert!(a.distance(b) < 0.01, "{} is not close to {}", a, b);
+	}
+
+	/// Projects the point onto the screen, casts a ray back through it and checks that it lands on the point again
+	fn assert_round_trip(projection: Mat4, camera_transform: Transform, point: Vec3, plane_normal: Vec3) {
+		let camera_matrix = camera_transform.compute_matrix();
+		let ndc = world_to_ndc_with_matrices(projection, camera_matrix, point)
+			.unwrap_or_else(|| panic!("{} should be in view", point));
+		let ray = Ray::from_ndc_with_matrices(projection, camera_matrix, ndc.truncate()).unwrap();
+		let distance = ray.intersect_plane(point, plane_normal).unwrap();
+		assert_close(ray.get_point(distance), point);
+	}
+
+	fn game_camera() -> (Mat4, Transform) {
+		let mut projection = crate::player::camera_projection();
+		projection.update(640.0, 400.0);
+		let transform = Transform::from_translation(crate::player::CAMERA_OFFSET).looking_at(Vec3::ZERO, Vec3::Y);
+		(projection.get_projection_matrix(), transform)
+	}
+
+	const POINTS: [Vec3; 4] = [
+		Vec3::ZERO,
+		Vec3::new(50.0, 0.0, -30.0),
+		Vec3::new(-70.0, 0.0, 40.0),
+		Vec3::new(80.0, 20.0, 10.0),
+	];
+
+	#[test]
+	fn round_trip_game_camera() {
+		let (projection, transform) = game_camera();
+		for point in POINTS {
+			assert_round_trip(projection, transform, point, Vec3::Y);
+		}
+	}
+
+	#[test]
+	fn round_trip_rotated_camera() {
+		let transform = Transform::from_xyz(60.0, 90.0, 180.0)
+			.looking_at(Vec3::new(10.0, 0.0, 0.0), Vec3::new(0.4, 1.0, 0.0).normalize());
+		for point in POINTS {
+			assert_round_trip(perspective_projection(), transform, point, Vec3::Y);
+		}
+	}
+
+	#[test]
+	fn round_trip_scaled_orthographic_camera() {
+		let mut projection = OrthographicProjection {
+			scale: 2.0,
+			..default()
+		};
+		projection.update(1280.0, 720.0);
+		let transform = Transform::from_xyz(-40.0, 150.0, 120.0)
+			.looking_at(Vec3::ZERO, Vec3::Y);
+		for point in POINTS {
+			assert_round_trip(projection.get_projection_matrix(), transform, point, Vec3::Y);
+		}
+	}
+
+	#[test]
+	fn round_trip_other_planes() {
+		let (projection, transform) = game_camera();
+		for normal in [Vec3::Z, Vec3::new(1.0, 1.0, 1.0).normalize(), Vec3::new(-0.5, 0.2, 1.0).normalize()] {
+			for point in POINTS {
+				assert_round_trip(projection, transform, point, normal);
+			}
+		}
+	}
+
+	#[test]
+	fn parallel_plane_has_no_intersection() {
+		let ray = Ray::new(Vec3::ZERO, Vec3::X).unwrap();
+		assert_eq!(ray.intersect_plane(Vec3::Y, Vec3::Y), None);
+	}
+
+	#[test]
+	fn perspective_ray_goes_out_from_the_camera() {
+		let camera_matrix = Transform::from_xyz(0.0, 0.0, 10.0).compute_matrix();
+		let ray = Ray::from_ndc_with_matrices(perspective_projection(), camera_matrix, Vec2::ZERO).unwrap();
+
+		assert_close(ray.direction, Vec3::NEG_Z);
+		// Starts on the near plane, not the far one
+		assert!(ray.origin.z < 10.0 && ray.origin.z > 9.0, "origin {} is not near the camera", ray.origin);
+	}
+
+	#[test]
+	fn perspective_ray_goes_through_the_given_point() {
+		let camera_matrix = Transform::from_xyz(0.0, 0.0, 10.0).compute_matrix();
+		let ray = Ray::from_ndc_with_matrices(perspective_projection(), camera_matrix, Vec2::new(0.5, -0.5)).unwrap();
+
+		assert!(ray.direction.x > 0.0 && ray.direction.y < 0.0 && ray.direction.z < 0.0);
+		let camera_pos = Vec3::new(0.0, 0.0, 10.0);
+		assert_close(ray.direction, (ray.origin - camera_pos).normalize());
+	}
+
+	#[test]
+	fn orthographic_rays_are_parallel() {
+		let mut projection = OrthographicProjection::default();
+		projection.update(640.0, 400.0);
+		let projection = projection.get_projection_matrix();
+		let camera_matrix = Transform::from_xyz(0.0, 0.0, 10.0).compute_matrix();
+
+		for ndc in [Vec2::ZERO, Vec2::new(1.0, 1.0), Vec2::new(-0.3, 0.8)] {
+			let ray = Ray::from_ndc_with_matrices(projection, camera_matrix, ndc).unwrap();
+			assert_close(ray.direction, Vec3::NEG_Z);
+		}
+	}
+}
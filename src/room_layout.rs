@@ -0,0 +1,203 @@
+use super::{levels, physics, player, sprite, expand_vec2};
+use bevy::prelude::*;
+
+/// Rooms describe their boundary once, and both the walls and the scenery along them are spawned from that,
+/// so what you see and what you bump into always line up.
+pub struct RoomLayoutPlugin;
+
+impl Plugin for RoomLayoutPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_resource::<RoomBounds>()
+			.add_system_to_stage(
+				CoreStage::PostUpdate,
+				update_parallax
+					.after(player::update_camera)
+					.before(sprite::facing_sprite_update)
+			);
+	}
+}
+
+/// What a stretch of the room's boundary looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeStyle {
+	/// Just a wall; for edges that are off screen or already look like something
+	Invisible,
+	/// A row of trees standing on the edge
+	Trees,
+}
+
+#[derive(Debug, Clone)]
+struct RoomEdge {
+	start: Vec2,
+	end: Vec2,
+	style: EdgeStyle,
+}
+
+/// Resource
+/// The rectangle around the edges of the room that was last spawned
+#[derive(Debug, Clone, Copy)]
+pub struct RoomBounds {
+	pub min: Vec2,
+	pub max: Vec2,
+}
+
+impl Default for RoomBounds {
+	fn default() -> Self {
+		Self {
+			min: Vec2::splat(f32::MIN),
+			max: Vec2::splat(f32::MAX),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RoomLayout {
+	edges: Vec<RoomEdge>,
+	/// How much a row of trees in the distance follows the camera, if there is one.
+	/// 0 is fixed in the world, 1 moves along with the camera.
+	far_trees: Option<f32>,
+}
+
+const TREE_SPACING: f32 = 32.0;
+/// Where the tree sprites sit relative to their spot on the edge, in camera space
+const TREE_OFFSET: Vec3 = Vec3::new(0.0, 45.0, -10.0);
+const FAR_TREE_OFFSET: Vec3 = Vec3::new(0.0, 60.0, -20.0);
+/// How far past the room's edges the far trees go, so the ends never come into view
+const FAR_TREE_MARGIN: f32 = 160.0;
+
+impl RoomLayout {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The inside of the room is on the right going from start to end, like with Wall
+	pub fn with_edge(mut self, start: Vec2, end: Vec2, style: EdgeStyle) -> Self {
+		self.edges.push(RoomEdge {
+			start,
+			end,
+			style,
+		});
+		self
+	}
+
+	pub fn with_far_trees(mut self, parallax: f32) -> Self {
+		self.far_trees = Some(parallax);
+		self
+	}
+
+	pub fn spawn(&self, commands: &mut Commands, level_textures: &levels::LevelSprites) {
+		for (i, edge) in self.edges.iter().enumerate() {
+			commands
+				.spawn_bundle(physics::Wall::new(edge.start, edge.end, true))
+				.insert_bundle(SpatialBundle::default());
+
+			if edge.style == EdgeStyle::Trees {
+				for position in self.get_tree_positions(i) {
+					commands.spawn_bundle(SpatialBundle {
+						transform: Transform::from_translation(expand_vec2(position)),
+						..default()
+					})
+					.insert(levels::CleanUpOnRoomLoad)
+					.with_children(|parent| {
+						parent.spawn_bundle(sprite::FacingSpriteBundle::new_vec(
+							level_textures.get_sprite("bg0"),
+							TREE_OFFSET
+						)).insert(sprite::SpriteLayer::Background);
+					});
+				}
+			}
+		}
+
+		if let Some(bounds) = self.get_bounds() {
+			commands.insert_resource(bounds);
+			if let Some(factor) = self.far_trees {
+				self.spawn_far_trees(commands, level_textures, factor, bounds);
+			}
+		}
+	}
+
+	fn get_bounds(&self) -> Option<RoomBounds> {
+		let points = self.edges.iter().flat_map(|edge| [edge.start, edge.end]);
+		let (min, max) = points.fold(
+			(Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+			|(min, max), point| (min.min(point), max.max(point))
+		);
+		if min.x > max.x {
+			return None;
+		}
+		Some(RoomBounds { min, max })
+	}
+
+	/// Whether another edge starts or ends at this point
+	fn is_corner(&self, point: Vec2, edge_index: usize) -> bool {
+		self.edges.iter()
+			.enumerate()
+			.filter(|(i, _)| *i != edge_index)
+			.any(|(_, edge)| edge.start.distance(point) < 0.5 || edge.end.distance(point) < 0.5)
+	}
+
+	/// Evenly spaced along the edge, carrying on one more past any corners so the room's corners don't show
+	fn get_tree_positions(&self, edge_index: usize) -> Vec<Vec2> {
+		let edge = &self.edges[edge_index];
+		let direction = (edge.end - edge.start).normalize_or_zero();
+		let n_trees = (edge.start.distance(edge.end) / TREE_SPACING).ceil() as i32;
+
+		let first = if self.is_corner(edge.start, edge_index) { -1 } else { 0 };
+		let last = if self.is_corner(edge.end, edge_index) { n_trees } else { n_trees - 1 };
+
+		(first..=last)
+			.map(|k| edge.start + direction * TREE_SPACING * (k as f32 + 0.5))
+			.collect()
+	}
+
+	fn spawn_far_trees(&self, commands: &mut Commands, level_textures: &levels::LevelSprites, factor: f32, bounds: RoomBounds) {
+		let RoomBounds { min, max } = bounds;
+
+		// Behind the back of the room
+		let anchor = expand_vec2(Vec2::new(0.0, min.y));
+		let n_trees = ((max.x - min.x + 2.0 * FAR_TREE_MARGIN) / TREE_SPACING).ceil() as i32;
+		commands
+			.spawn_bundle(SpatialBundle {
+				transform: Transform::from_translation(anchor),
+				..default()
+			})
+			.insert(ParallaxLayer {
+				anchor,
+				factor,
+			})
+			.insert(levels::CleanUpOnRoomLoad)
+			.with_children(|parent| {
+				for k in 0..=n_trees {
+					let x = min.x - FAR_TREE_MARGIN + TREE_SPACING * k as f32;
+					parent.spawn_bundle(sprite::FacingSpriteBundle::new_vec(
+						level_textures.get_sprite("bg0"),
+						FAR_TREE_OFFSET + Vec3::X * x
+					))
+					.insert(Sprite {
+						// Hazy in the distance
+						color: Color::rgb(0.6, 0.7, 0.65),
+						..default()
+					})
+					.insert(sprite::SpriteLayer::Background);
+				}
+			});
+	}
+}
+
+/// Goes on the root of something in the distance, which follows the camera sideways by some amount
+#[derive(Component, Debug)]
+struct ParallaxLayer {
+	anchor: Vec3,
+	factor: f32,
+}
+
+fn update_parallax(
+	mut layer_query: Query<(&ParallaxLayer, &mut Transform), Without<Camera>>,
+	camera_query: Query<&Transform, (With<Camera>, Without<ParallaxLayer>)>,
+) {
+	let camera_transform = camera_query.single();
+	for (layer, mut transform) in layer_query.iter_mut() {
+		transform.translation = layer.anchor + Vec3::X * camera_transform.translation.x * layer.factor;
+	}
+}
@@ -0,0 +1,344 @@
+use super::{player, spells, sprite, levels, game_state};
+use bevy::{prelude::*, utils::HashMap};
+
+/// Plays sound effects and the background music.
+/// Sounds that haven't loaded (or don't exist) are skipped, and without an audio device
+/// bevy just drops everything, so the game runs the same with or without sound.
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.insert_resource(VolumeSettings::default())
+			.insert_resource(MusicPlayer::default())
+			.add_event::<PlaySoundEvent>()
+			.add_startup_system(load_sounds)
+			.add_system_to_stage(CoreStage::PostUpdate, play_spell_sounds)
+			.add_system(play_staff_pickup_sounds)
+			.add_system(play_animation_sounds.after(sprite::update_animators))
+			.add_system_to_stage(CoreStage::PostUpdate, play_sound_effects.after(play_spell_sounds))
+			.add_system(update_music.after(levels::transition_to_room));
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+	Cast(spells::SpellElement),
+	Impact(spells::SpellElement),
+	EnemyHit,
+	EnemyDeath,
+	PlayerHurt,
+	PlayerDefeated,
+	RunePickup,
+	StaffPickup,
+	Footstep,
+}
+
+impl SoundEffect {
+	fn get_path(&self) -> String {
+		match self {
+			SoundEffect::Cast(element) => format!("sounds/cast-{}.wav", get_element_name(*element)),
+			SoundEffect::Impact(element) => format!("sounds/impact-{}.wav", get_element_name(*element)),
+			SoundEffect::EnemyHit => "sounds/enemy-hit.wav".to_string(),
+			SoundEffect::EnemyDeath => "sounds/enemy-death.wav".to_string(),
+			SoundEffect::PlayerHurt => "sounds/player-hurt.wav".to_string(),
+			SoundEffect::PlayerDefeated => "sounds/player-defeated.wav".to_string(),
+			SoundEffect::RunePickup => "sounds/pickup-rune.wav".to_string(),
+			SoundEffect::StaffPickup => "sounds/pickup-staff.wav".to_string(),
+			SoundEffect::Footstep => "sounds/footstep.wav".to_string(),
+		}
+	}
+}
+
+fn get_element_name(element: spells::SpellElement) -> &'static str {
+	match element {
+		spells::SpellElement::Neutral => "neutral",
+		spells::SpellElement::Fire => "fire",
+		spells::SpellElement::Water => "water",
+		spells::SpellElement::Earth => "earth",
+		spells::SpellElement::Air => "air",
+		spells::SpellElement::Metal => "metal",
+		spells::SpellElement::Plant => "plant",
+		spells::SpellElement::Electric => "electric",
+		spells::SpellElement::Ice => "ice",
+		spells::SpellElement::Light => "light",
+	}
+}
+
+const ALL_SOUND_EFFECTS: [SoundEffect; 7] = [
+	SoundEffect::EnemyHit,
+	SoundEffect::EnemyDeath,
+	SoundEffect::PlayerHurt,
+	SoundEffect::PlayerDefeated,
+	SoundEffect::RunePickup,
+	SoundEffect::StaffPickup,
+	SoundEffect::Footstep,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MusicTrack {
+	Title,
+	Forest,
+	Tower,
+	Ending,
+}
+
+impl MusicTrack {
+	const ALL: [MusicTrack; 4] = [
+		MusicTrack::Title,
+		MusicTrack::Forest,
+		MusicTrack::Tower,
+		MusicTrack::Ending,
+	];
+
+	fn get_path(&self) -> &'static str {
+		match self {
+			MusicTrack::Title => "music/title.wav",
+			MusicTrack::Forest => "music/forest.wav",
+			MusicTrack::Tower => "music/tower.wav",
+			MusicTrack::Ending => "music/ending.wav",
+		}
+	}
+
+	fn for_room(room: usize) -> Self {
+		match room {
+			0..=2 => MusicTrack::Forest,
+			3..=4 => MusicTrack::Tower,
+			_ => MusicTrack::Ending,
+		}
+	}
+}
+
+/// Resource
+struct SoundHandles {
+	effects: HashMap<SoundEffect, Handle<AudioSource>>,
+	music: HashMap<MusicTrack, Handle<AudioSource>>,
+}
+
+fn load_sounds(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	let element_effects = spells::ALL_ELEMENTS.iter()
+		.flat_map(|element| [SoundEffect::Cast(*element), SoundEffect::Impact(*element)]);
+
+	commands.insert_resource(SoundHandles {
+		effects: ALL_SOUND_EFFECTS.into_iter()
+			.chain(element_effects)
+			.map(|effect| (effect, asset_server.load(effect.get_path().as_str())))
+			.collect(),
+		music: MusicTrack::ALL.iter()
+			.map(|track| (*track, asset_server.load(track.get_path())))
+			.collect(),
+	});
+}
+
+/// Event for playing a sound effect
+pub struct PlaySoundEvent(pub SoundEffect);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeChannel {
+	Music,
+	Effects,
+}
+
+impl VolumeChannel {
+	pub const ALL: [VolumeChannel; 2] = [
+		VolumeChannel::Music,
+		VolumeChannel::Effects,
+	];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			VolumeChannel::Music => "Music",
+			VolumeChannel::Effects => "Sound effects",
+		}
+	}
+}
+
+/// Resource
+/// Volumes from 0 to 1, adjustable from the settings menu
+#[derive(Debug, Clone)]
+pub struct VolumeSettings {
+	music: f32,
+	effects: f32,
+}
+
+impl Default for VolumeSettings {
+	fn default() -> Self {
+		Self {
+			music: 0.6,
+			effects: 0.8,
+		}
+	}
+}
+
+const VOLUME_STEP: f32 = 0.1;
+
+impl VolumeSettings {
+	pub fn get(&self, channel: VolumeChannel) -> f32 {
+		match channel {
+			VolumeChannel::Music => self.music,
+			VolumeChannel::Effects => self.effects,
+		}
+	}
+
+	/// Moves a volume one step up or down
+	pub fn step(&mut self, channel: VolumeChannel, increase: bool) {
+		let value = match channel {
+			VolumeChannel::Music => &mut self.music,
+			VolumeChannel::Effects => &mut self.effects,
+		};
+		let step = if increase { VOLUME_STEP } else { -VOLUME_STEP };
+		// Round so repeated steps don't drift
+		*value = ((*value + step) / VOLUME_STEP).round() * VOLUME_STEP;
+		*value = value.clamp(0.0, 1.0);
+	}
+}
+
+/// Plays the impact sounds for spells, including enemy ones.
+/// Cast sounds are played by the player's casting, so sub-spells and enemy spells don't get one.
+fn play_spell_sounds(
+	mut despawn_events: EventReader<spells::SpellDespawnEvent>,
+	spell_query: Query<&spells::SpellData, With<spells::SpellMarker>>,
+	mut sound_events: EventWriter<PlaySoundEvent>,
+) {
+	// Runs before despawn_spells gets a chance to actually despawn them
+	for event in despawn_events.iter() {
+		if let Ok(spell_data) = spell_query.get(event.0) {
+			sound_events.send(PlaySoundEvent(SoundEffect::Impact(spell_data.get_element())));
+		}
+	}
+}
+
+fn play_staff_pickup_sounds(
+	mut staff_events: EventReader<player::GiveStaffEvent>,
+	mut sound_events: EventWriter<PlaySoundEvent>,
+) {
+	if staff_events.iter().next().is_some() {
+		sound_events.send(PlaySoundEvent(SoundEffect::StaffPickup));
+	}
+}
+
+/// Sounds tied to animation frames, see the events in the .anim.ron files
+fn play_animation_sounds(
+	mut animation_events: EventReader<sprite::AnimationEvent>,
+	mut sound_events: EventWriter<PlaySoundEvent>,
+	player_sprite_query: Query<(), With<player::PlayerSpriteMarker>>,
+) {
+	for event in animation_events.iter() {
+		// Only the player's footsteps, a room full of them would get noisy
+		if event.name == "footstep" && player_sprite_query.get(event.entity).is_ok() {
+			sound_events.send(PlaySoundEvent(SoundEffect::Footstep));
+		}
+	}
+}
+
+fn play_sound_effects(
+	mut sound_events: EventReader<PlaySoundEvent>,
+	audio: Option<Res<Audio>>,
+	audio_sources: Option<Res<Assets<AudioSource>>>,
+	handles: Res<SoundHandles>,
+	volume: Res<VolumeSettings>,
+) {
+	let (audio, audio_sources) = match (audio, audio_sources) {
+		(Some(audio), Some(audio_sources)) => (audio, audio_sources),
+		// No audio plugin at all
+		_ => return,
+	};
+
+	// Lots of projectiles can hit at once; only play each sound once per frame
+	let mut played = Vec::new();
+	for PlaySoundEvent(effect) in sound_events.iter() {
+		if played.contains(effect) || volume.effects <= 0.0 {
+			continue;
+		}
+		played.push(*effect);
+
+		// Bevy would hold on to sounds that haven't loaded until they do,
+		// which would pile up forever for missing files
+		if let Some(handle) = handles.effects.get(effect) {
+			if audio_sources.get(handle).is_some() {
+				audio.play_with_settings(handle.clone(), PlaybackSettings::ONCE.with_volume(volume.effects));
+			}
+		}
+	}
+}
+
+// Music ///////////////
+/// How long it takes to fade between tracks, in seconds
+const MUSIC_FADE_TIME: f32 = 1.5;
+
+struct PlayingTrack {
+	track: MusicTrack,
+	sink: Handle<AudioSink>,
+	/// How far it is faded in, from 0 to 1
+	fade: f32,
+}
+
+/// Resource
+#[derive(Default)]
+struct MusicPlayer {
+	current: Option<PlayingTrack>,
+	fading_out: Vec<PlayingTrack>,
+}
+
+/// Crossfades to the track for the current room, and keeps volumes up to date
+fn update_music(
+	mut music_player: ResMut<MusicPlayer>,
+	audio: Option<Res<Audio>>,
+	audio_sources: Option<Res<Assets<AudioSource>>>,
+	audio_sinks: Option<Res<Assets<AudioSink>>>,
+	handles: Res<SoundHandles>,
+	volume: Res<VolumeSettings>,
+	current_room: Res<levels::CurrentRoom>,
+	state: Res<State<game_state::GameState>>,
+	time: Res<Time>,
+) {
+	let (audio, audio_sources, audio_sinks) = match (audio, audio_sources, audio_sinks) {
+		(Some(audio), Some(audio_sources), Some(audio_sinks)) => (audio, audio_sources, audio_sinks),
+		_ => return,
+	};
+
+	let wanted_track = match (state.current(), current_room.0) {
+		(game_state::GameState::Title, _) | (_, None) => MusicTrack::Title,
+		(_, Some(room)) => MusicTrack::for_room(room),
+	};
+
+	// Start the new track if it changed
+	if music_player.current.as_ref().map(|playing| playing.track) != Some(wanted_track) {
+		let handle = &handles.music[&wanted_track];
+		if audio_sources.get(handle).is_some() {
+			let sink = audio.play_with_settings(handle.clone(), PlaybackSettings::LOOP.with_volume(0.0));
+			let new_track = PlayingTrack {
+				track: wanted_track,
+				// The handle that gets returned is weak
+				sink: audio_sinks.get_handle(sink),
+				fade: 0.0,
+			};
+			if let Some(old_track) = music_player.current.replace(new_track) {
+				music_player.fading_out.push(old_track);
+			}
+		}
+	}
+
+	let fade_step = time.delta_seconds() / MUSIC_FADE_TIME;
+	if let Some(current) = music_player.current.as_mut() {
+		current.fade = (current.fade + fade_step).min(1.0);
+	}
+	for old_track in music_player.fading_out.iter_mut() {
+		old_track.fade = (old_track.fade - fade_step).max(0.0);
+	}
+
+	// There won't be any sinks without an audio device
+	let music_player = &mut *music_player;
+	for playing in music_player.current.iter().chain(music_player.fading_out.iter()) {
+		if let Some(sink) = audio_sinks.get(&playing.sink) {
+			sink.set_volume(playing.fade * volume.music);
+			if playing.fade <= 0.0 {
+				sink.pause();
+			}
+		}
+	}
+	music_player.fading_out.retain(|old_track| old_track.fade > 0.0);
+}
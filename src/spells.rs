@@ -1,9 +1,7 @@
-use super::{physics, sprite, ui, enemy, levels, expand_vec2, collapse_vec3};
+use super::{physics, sprite, ui, enemy, levels, game_state, sound, feedback, tuning, expand_vec2, collapse_vec3};
 use bevy::{prelude::*, utils::HashMap};
 use bevy_turborand::*;
-
-const SPELL_RUNE_COST: f32 = 8.0;
-const SPELL_BASE_DAMAGE: f32 = 5.0;
+use serde::Deserialize;
 
 pub struct SpellPlugin;
 
@@ -17,7 +15,7 @@ impl Plugin for SpellPlugin {
             .add_startup_system(setup_spell_sprites)
 			.add_startup_system(setup_rune_sprites)
 			.add_system(process_spell_enemy_collisions)
-			.add_system(update_spell_lifetimes)
+			.add_system(update_spell_lifetimes.with_run_criteria(game_state::is_world_running))
 			.add_system(create_spells_from_events
 				.after(process_spell_enemy_collisions))
 			.add_system_to_stage(
@@ -37,14 +35,14 @@ impl RuneCastQueue {
         RuneCastQueue(Vec::<Rune>::new())
     }
 
-    pub fn generate_spell(&self) -> Option<SpellData> {
-        create_spell_recursive(&self.0[..], 1.0)
+    pub fn generate_spell(&self, tuning: &tuning::SpellTuning) -> Option<SpellData> {
+        create_spell_recursive(&self.0[..], 1.0, tuning)
     }
 }
 
 /// Turns a rune queue into SpellData, spawned recursively.
 /// Returns None if the runes evaluate to a spell with no effect.
-fn create_spell_recursive(runes: &[Rune], power_factor: f32) -> Option<SpellData> {
+fn create_spell_recursive(runes: &[Rune], power_factor: f32, tuning: &tuning::SpellTuning) -> Option<SpellData> {
     if runes.is_empty() {
 		return None;
 	}
@@ -72,8 +70,8 @@ fn create_spell_recursive(runes: &[Rune], power_factor: f32) -> Option<SpellData
 			Rune::ShapeRune(_) => {
 				// Recursively determine the rest of the spell
 				// Result is only None if the rest of it does not evaluate to a spell with a proper effect
-				let sub_spell_power_factor = power_factor * layer_shape.get_power_multiplier();
-				if let Some(sub_spell) = create_spell_recursive(&runes[i..], sub_spell_power_factor) {
+				let sub_spell_power_factor = power_factor * tuning.shape(layer_shape).power;
+				if let Some(sub_spell) = create_spell_recursive(&runes[i..], sub_spell_power_factor, tuning) {
 					match layer_shape {
 						SpellShape::NoShape | SpellShape::Line => {
 							maybe_on_impact = Some(Box::new(sub_spell));
@@ -125,10 +123,10 @@ fn create_spell_recursive(runes: &[Rune], power_factor: f32) -> Option<SpellData
 		_ => Vec2::new(fire_ct as f32 - water_ct as f32, earth_ct as f32 - air_ct as f32).length()
 	};
 	
-	let damage = SPELL_BASE_DAMAGE
+	let damage = tuning.base_damage
 		* spell_magnitude 
-		* layer_shape.get_damage_multiplier() 
-		* element.get_damage_multiplier()
+		* tuning.shape(layer_shape).damage
+		* tuning.element(element).damage
 		* power_factor.sqrt();
 	
 	// Determine mana cost //////////////////////////////////////////////
@@ -140,8 +138,8 @@ fn create_spell_recursive(runes: &[Rune], power_factor: f32) -> Option<SpellData
 	} else {
 		0.0
 	};
-	let mana_cost = SPELL_RUNE_COST * total_runes as f32
-		+ layer_shape.get_cost_multiplier() * sub_cost;
+	let mana_cost = tuning.rune_cost * total_runes as f32
+		+ tuning.shape(layer_shape).cost * sub_cost;
 	
 	// Determine spell size //////////////////////////////////////////////
 	let size_factor = spell_magnitude * power_factor * (
@@ -153,10 +151,10 @@ fn create_spell_recursive(runes: &[Rune], power_factor: f32) -> Option<SpellData
 	let spell_size = SpellSize::from_size_factor(size_factor);
 	
 	// Speed //////////////////////////////////////////////
-	let speed = layer_shape.get_base_speed() * element.get_speed_multiplier();
+	let speed = tuning.shape(layer_shape).speed * tuning.element(element).speed;
 	
 	// Knockback //////////////////////////////////////////////
-	let knockback = layer_shape.get_base_knockback() * element.get_knockback_multiplier();
+	let knockback = tuning.base_knockback * tuning.shape(layer_shape).knockback * tuning.element(element).knockback;
 	
 	// Assemble everything together //////////////////////////////////////
 	Some(SpellData {
@@ -279,6 +277,11 @@ fn update_spell_lifetimes(
 }
 
 impl SpellData {
+	/// Builds a spell directly from a rune sequence, without needing a RuneCastQueue
+	pub fn from_runes(runes: &[Rune], tuning: &tuning::SpellTuning) -> Option<Self> {
+		create_spell_recursive(runes, 1.0, tuning)
+	}
+	
 	pub fn get_damage(&self) -> i32 {
 		if self.damage > 0.0 {
 			self.damage.round() as i32
@@ -287,6 +290,14 @@ impl SpellData {
 		}
 	}
 	
+	pub fn get_element(&self) -> SpellElement {
+		self.element
+	}
+	
+	pub fn get_size(&self) -> SpellSize {
+		self.size
+	}
+	
 	pub fn get_mana_cost(&self) -> i32 {
 		if self.mana_cost > 1.0 {
 			self.mana_cost.round() as i32
@@ -297,7 +308,7 @@ impl SpellData {
 }
 
 #[derive(Debug)]
-pub struct SpellDespawnEvent(Entity);
+pub struct SpellDespawnEvent(pub Entity);
 #[derive(Debug)]
 pub struct CreateSpellEvent {
 	pub spell_data: SpellData, 
@@ -305,7 +316,7 @@ pub struct CreateSpellEvent {
 	pub move_direction: Vec2,
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Deserialize)]
 pub enum SpellElement {
     Neutral,
     Fire,
@@ -320,49 +331,21 @@ pub enum SpellElement {
 }
 
 impl SpellElement {
-	fn get_speed_multiplier(&self) -> f32 {
-		match self {
-			Self::Neutral => 1.0,
-			Self::Fire => 1.0,
-			Self::Water => 0.7,
-			Self::Earth => 1.4,
-			Self::Air => 1.2,
-			Self::Metal => 1.5,
-			Self::Plant => 0.85,
-			Self::Electric => 1.8,
-			Self::Ice => 1.0,
-			Self::Light => 2.0,
-		}
-	}
-	
-	fn get_damage_multiplier(&self) -> f32 {
-		match self {
-			Self::Neutral => 0.0,
-			Self::Fire => 1.0,
-			Self::Water => 0.8,
-			Self::Earth => 0.8,
-			Self::Air => 0.7,
-			Self::Metal => 1.4,
-			Self::Plant => 0.8,
-			Self::Electric => 1.8,
-			Self::Ice => 1.4,
-			Self::Light => 5.0,
-		}
-	}
-	
-	fn get_knockback_multiplier(&self) -> f32 {
-		match self {
-			Self::Neutral => 0.1,
-			Self::Fire => 0.7,
-			Self::Water => 1.3,
-			Self::Earth => 1.0,
-			Self::Air => 1.5,
-			Self::Metal => 1.0,
-			Self::Plant => 2.0,
-			Self::Electric => 0.1,
-			Self::Ice => 1.5,
-			Self::Light => 0.4,
-		}
+	/// What enemies get tinted after being hit by this element
+	pub fn get_tint_color(&self) -> Color {
+		let hex = match self {
+			Self::Neutral => "F2F5E6",
+			Self::Fire => "EC5B2A",
+			Self::Water => "4B80CA",
+			Self::Earth => "A05B53",
+			Self::Air => "B8EEEB",
+			Self::Metal => "9BABB2",
+			Self::Plant => "5AC54F",
+			Self::Electric => "FFF67A",
+			Self::Ice => "68C2D3",
+			Self::Light => "FFE478",
+		};
+		Color::hex(hex).unwrap()
 	}
 	
     fn as_vec(&self) -> Vec2 {
@@ -417,7 +400,7 @@ impl SpellElement {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 pub enum SpellShape {
     NoShape,
     Orb,
@@ -427,63 +410,6 @@ pub enum SpellShape {
 }
 
 impl SpellShape {
-	/// Applies to the current layer
-	fn get_damage_multiplier(&self) -> f32 {
-		match self {
-			Self::NoShape => 2.0,
-			Self::Orb => 1.0,
-			Self::Line => 1.0,
-			Self::Burst => 0.7,
-			Self::Scatter => 0.5,
-		}
-	}
-	
-	/// Applies to the layer below
-	fn get_cost_multiplier(&self) -> f32 {
-		match self {
-			Self::NoShape => 1.0,
-			Self::Orb => 1.1,
-			Self::Line => 1.3,
-			Self::Burst => 1.2,
-			Self::Scatter => 1.3,
-		}
-	}
-	
-	/// Applies to the layer below
-	fn get_power_multiplier(&self) -> f32 {
-		match self {
-			Self::NoShape => 1.5,
-			Self::Orb => 1.0,
-			Self::Line => 0.6,
-			Self::Burst => 0.6,
-			Self::Scatter => 0.3,
-		}
-	}
-	
-	/// Applies to the current layer
-	fn get_base_speed(&self) -> f32 {
-		match self {
-			Self::NoShape => 0.0,
-			Self::Orb => 90.0,
-			Self::Line => 110.0,
-			Self::Burst => 130.0,
-			Self::Scatter => 130.0,
-		}
-	}
-	
-	/// Applies to the current layer
-	fn get_base_knockback(&self) -> f32 {
-		let multiplier = match self {
-			Self::NoShape => 1.2,
-			Self::Orb => 1.0,
-			Self::Line => 1.0,
-			Self::Burst => 1.0,
-			Self::Scatter => 1.0,
-		};
-		
-		50.0 * multiplier
-	}
-	
 	fn get_num_projectiles(&self) -> i32 {
 		match self {
 			Self::NoShape | Self::Orb => 1,
@@ -530,31 +456,67 @@ impl SpellSize {
 	}
 }
 
+/// How long enemies stay tinted with the element of the spell that hit them, in seconds
+const ELEMENT_TINT_TIME: f32 = 0.8;
+
 /// Resolve spell-enemy collisions
 pub fn process_spell_enemy_collisions(
 	spell_query: Query<(&SpellData, &Transform, &physics::Speed), With<SpellMarker>>,
-	mut enemy_query: Query<(&mut enemy::EnemyHealth, &mut enemy::EnemyKnockbackComponent, &mut enemy::EnemyVulnerability)>,
+	mut enemy_query: Query<(
+		&mut enemy::EnemyHealth,
+		&mut enemy::EnemyKnockbackComponent,
+		&mut enemy::EnemyVulnerability,
+		&Transform,
+		Option<&enemy::AIShielded>,
+		Option<&mut sprite::SpriteTint>,
+	), Without<SpellMarker>>,
 	collisions: Res<physics::ActiveCollisions<physics::InteractsWithEnemies>>,
 	mut spell_despawn_events: EventWriter<SpellDespawnEvent>,
 	mut create_spell_events: EventWriter<CreateSpellEvent>,
+	mut sound_events: EventWriter<sound::PlaySoundEvent>,
+	mut damage_events: EventWriter<feedback::DamageEvent>,
 ) {
 	for collision in collisions.iter() {
 		if let (
-			Ok((spell_data, transform, speed)), Ok((mut enemy_health, mut enemy_knockback, mut enemy_vulnerability))
+			Ok((spell_data, transform, speed)),
+			Ok((mut enemy_health, mut enemy_knockback, mut enemy_vulnerability, enemy_transform, maybe_shield, maybe_tint)),
 		) = (
 			spell_query.get(collision.source_entity), enemy_query.get_mut(collision.recip_entity)
 		) {
 			if !enemy_vulnerability.tangible {
 				continue;
 			}
+			
+			let hit_direction = collapse_vec3(transform.translation - enemy_transform.translation);
+			let blocked = maybe_shield.map_or(false, |shield| shield.blocks_hit_from(hit_direction));
+			
 			// Do damage
-			if spell_data.get_damage() > 0 {
+			if spell_data.get_damage() > 0 && !blocked {
 				enemy_health.0 -= spell_data.get_damage();
 				enemy_vulnerability.tangible = false;
 				enemy_vulnerability.hit_timer.reset();
+				sound_events.send(sound::PlaySoundEvent(sound::SoundEffect::EnemyHit));
+				damage_events.send(feedback::DamageEvent {
+					position: collapse_vec3(enemy_transform.translation),
+					amount: spell_data.get_damage(),
+					target: feedback::DamageTarget::Enemy,
+				});
+				if let Some(mut tint) = maybe_tint {
+					tint.push(sprite::Tint::new(
+						sprite::TintSource::Hit,
+						sprite::TintEffect::Mix(Color::WHITE, 0.9),
+						sprite::TintEnvelope::FadeOut,
+					).with_duration(sprite::HIT_FLASH_TIME));
+					tint.push(sprite::Tint::new(
+						sprite::TintSource::Element,
+						sprite::TintEffect::Mix(spell_data.get_element().get_tint_color(), 0.6),
+						sprite::TintEnvelope::FadeOut,
+					).with_duration(ELEMENT_TINT_TIME));
+				}
 			}
-			// Apply knockback
-			enemy_knockback.0 = speed.normalize_or_zero() * spell_data.knockback;
+			// Apply knockback, shields absorb some of it
+			let knockback_factor = if blocked { 0.5 } else { 1.0 };
+			enemy_knockback.0 = speed.normalize_or_zero() * spell_data.knockback * knockback_factor;
 			
 			if let Some(new_spell_data) = &spell_data.on_collide {
 				create_spell_events.send(CreateSpellEvent {
@@ -596,7 +558,6 @@ fn create_spells_from_events(
     mut commands: Commands,
     all_spell_sprites: Res<AllSpellSprites>,
 	mut create_events: EventReader<CreateSpellEvent>,
-	shadow_texture: Res<sprite::ShadowTexture>,
 	mut global_rng: ResMut<GlobalRng>,
 ) {
 	for event in create_events.iter() {
@@ -659,6 +620,7 @@ fn create_spells_from_events(
 					..default()
 				})
 				.insert(physics::Speed(movement_direction * speed))
+				.insert(sprite::CastsShadow::new(spell_data.size.get_shadow_index(), texture_data.y_offset))
 				.with_children(|parent| {
 					parent
 						.spawn()
@@ -666,16 +628,12 @@ fn create_spells_from_events(
 						.insert(sprite::SimpleAnimationMarker(true))
 						.insert(sprite::AnimationTimer(Timer::from_seconds(1.0 / 7.0, true)))
 						.insert(sprite::SpriteOffset(Vec3::Y * texture_data.y_offset))
+						.insert(sprite::SpriteLayer::Air)
 						.insert_bundle(SpriteSheetBundle {
 							texture_atlas: texture_data.texture_atlas.clone(),
 							..default()
 						})
 						.insert(sprite::SpriteHover::new(3.0, 3.0));
-					parent.spawn_bundle(
-						shadow_texture.get_shadow_bundle(
-							spell_data.size.get_shadow_index()
-						)
-					);
 				});
 		}		
 	}
@@ -839,7 +797,7 @@ fn setup_spell_sprites(
     commands.insert_resource(AllSpellSprites(sprite_map));
 }
 
-const ALL_ELEMENTS: [SpellElement; 10] = [
+pub const ALL_ELEMENTS: [SpellElement; 10] = [
     SpellElement::Neutral,
     SpellElement::Fire,
     SpellElement::Water,
@@ -1,11 +1,45 @@
-use super::ui;
-use bevy::{prelude::*, transform::transform_propagate_system, utils::Duration};
+use super::game_state;
+use bevy::{
+	prelude::*,
+	asset::{AssetLoader, LoadContext, LoadedAsset},
+	reflect::TypeUuid,
+	transform::transform_propagate_system,
+	utils::{BoxedFuture, Duration},
+};
+use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Component, Debug, Default)]
 pub struct FacingSpriteMarker;
+/// Offset from the parent, in camera space. The z part doesn't move the sprite on screen,
+/// it only nudges it towards (positive) or away from the camera when sorting.
 #[derive(Component, Debug)]
 pub struct SpriteOffset(pub Vec3);
 
+/// Which layer a facing sprite is drawn in. Each layer is drawn entirely on top of the ones before it,
+/// and sprites within a layer are sorted by how close they are to the camera on the ground.
+/// Sprites without one are on the Ground layer.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SpriteLayer {
+	/// Scenery at the back of the room
+	Background,
+	Shadow,
+	/// Anything standing or lying on the ground
+	Ground,
+	/// Projectiles and other things flying around
+	Air,
+	/// Where the lighting gets multiplied onto everything before it
+	Lighting,
+	/// Things that should never be covered up, like damage numbers
+	Overlay,
+}
+
+impl Default for SpriteLayer {
+	fn default() -> Self {
+		SpriteLayer::Ground
+	}
+}
+
 /// Sets up system for making sprites face the camera properly.
 /// Note that they need a FacingSpriteMarker for this.
 pub struct FacingSpritePlugin;
@@ -17,28 +51,64 @@ impl Plugin for FacingSpritePlugin {
 				CoreStage::PostUpdate,
 				facing_sprite_update.before(transform_propagate_system),
 			)
-			.add_system(simple_animation_update)
-			.add_system(hover_update)
-			.add_system_to_stage(CoreStage::PreUpdate, pause_animation_timers);
+			.add_system(simple_animation_update.with_run_criteria(game_state::is_world_running))
+			.add_asset::<AnimationSet>()
+			.init_asset_loader::<AnimationSetLoader>()
+			.add_event::<AnimationEvent>()
+			.add_system(update_animators.with_run_criteria(game_state::is_world_running))
+			.add_system(hover_update.with_run_criteria(game_state::is_world_running))
+			.add_system(attach_shadows)
+			.add_system(
+				update_shadows
+					.with_run_criteria(game_state::is_world_running)
+					.after(hover_update)
+					.after(update_sprite_tints)
+			)
+			.add_system(update_sprite_tints.with_run_criteria(game_state::is_world_running));
     }
 }
 
 
 // Shadows
-pub struct ShadowTexture(Handle<TextureAtlas>);
+/// Goes on the root entity of anything with a shadow; the shadow itself is added as a child automatically.
+/// The shadow shrinks and fades as the entity's sprite rises above rest_height.
+#[derive(Component, Debug)]
+pub struct CastsShadow {
+	/// Frame in the shadow sheet, from smallest to biggest
+	pub size: usize,
+	/// Sprite offset height at which the shadow is full size
+	pub rest_height: f32,
+}
+impl CastsShadow {
+	pub fn new(size: usize, rest_height: f32) -> Self {
+		Self {
+			size,
+			rest_height,
+		}
+	}
+}
+
+#[derive(Component, Debug, Default)]
+struct ShadowMarker;
+
+struct ShadowTexture(Handle<TextureAtlas>);
 #[derive(Bundle)]
-pub struct ShadowTextureBundle {
+struct ShadowTextureBundle {
 	marker: FacingSpriteMarker,
+	shadow_marker: ShadowMarker,
 	sprite_offset: SpriteOffset,
+	layer: SpriteLayer,
 	#[bundle]
 	sprite_bundle: SpriteSheetBundle,
 }
 
 impl ShadowTexture {
-	pub fn get_shadow_bundle(&self, index: usize) -> ShadowTextureBundle {
+	fn get_shadow_bundle(&self, index: usize) -> ShadowTextureBundle {
 		ShadowTextureBundle {
 			marker: FacingSpriteMarker,
-			sprite_offset: SpriteOffset(Vec3::new(0.0, 0.0, -16.0)),
+			shadow_marker: ShadowMarker,
+			sprite_offset: SpriteOffset(Vec3::ZERO),
+			layer: SpriteLayer::Shadow,
 			sprite_bundle: SpriteSheetBundle {
 				texture_atlas: self.0.clone(),
 				sprite: TextureAtlasSprite {
@@ -67,32 +137,265 @@ fn shadow_setup(
 	commands.insert_resource(ShadowTexture(texture_atlas));
 }
 
+fn attach_shadows(
+	mut commands: Commands,
+	query: Query<(Entity, &CastsShadow), Added<CastsShadow>>,
+	shadow_texture: Res<ShadowTexture>,
+) {
+	for (e, casts_shadow) in query.iter() {
+		commands.entity(e).with_children(|parent| {
+			parent.spawn_bundle(shadow_texture.get_shadow_bundle(casts_shadow.size));
+		});
+	}
+}
+
+/// Height above rest_height at which the shadow is half size
+const SHADOW_HALF_SIZE_HEIGHT: f32 = 24.0;
+
+pub fn update_shadows(
+	caster_query: Query<(&CastsShadow, &Children)>,
+	owner_query: Query<(&SpriteOffset, Option<&TextureAtlasSprite>), (With<FacingSpriteMarker>, Without<ShadowMarker>)>,
+	mut shadow_query: Query<(&mut Transform, &mut TextureAtlasSprite), With<ShadowMarker>>,
+) {
+	for (casts_shadow, children) in caster_query.iter() {
+		// The caster's own sprite is its first child that isn't a shadow
+		let (height, owner_alpha) = match children.iter().find_map(|child| owner_query.get(*child).ok()) {
+			Some((offset, maybe_sprite)) => (offset.0.y, maybe_sprite.map_or(1.0, |sprite| sprite.color.a())),
+			None => continue,
+		};
+		let rise = (height - casts_shadow.rest_height).max(0.0);
+		let factor = 1.0 / (1.0 + rise / SHADOW_HALF_SIZE_HEIGHT);
+		
+		for child in children.iter() {
+			if let Ok((mut transform, mut shadow_sprite)) = shadow_query.get_mut(*child) {
+				transform.scale = Vec3::new(factor, factor, 1.0);
+				// Fading sprites take their shadow with them
+				shadow_sprite.color.set_a(factor * owner_alpha);
+			}
+		}
+	}
+}
+
+// Tinting
+/// Where a tint comes from. Tints are blended in this order, so later ones win.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TintSource {
+	/// Colour of the element that last hit it
+	Element,
+	LowHealth,
+	/// Flickering while invulnerable
+	Intangible,
+	Hit,
+	Death,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TintEffect {
+	/// Mixes in a colour, by an amount from 0 to 1
+	Mix(Color, f32),
+	/// Multiplies the opacity
+	Opacity(f32),
+}
+
+/// How strongly a tint applies over time
+#[derive(Debug, Clone, Copy)]
+pub enum TintEnvelope {
+	/// From full strength to nothing over the timer
+	FadeOut,
+	/// From nothing to full strength over the timer, then stays there
+	FadeIn,
+	/// On and off with this period, in seconds
+	Pulse(f32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Tint {
+	pub source: TintSource,
+	pub effect: TintEffect,
+	pub envelope: TintEnvelope,
+	/// Removed when this finishes (except for FadeIn). Without one, it stays until removed.
+	pub timer: Option<Timer>,
+	time_elapsed: f32,
+}
+
+impl Tint {
+	pub fn new(source: TintSource, effect: TintEffect, envelope: TintEnvelope) -> Self {
+		Self {
+			source,
+			effect,
+			envelope,
+			timer: None,
+			time_elapsed: 0.0,
+		}
+	}
+	
+	pub fn with_duration(mut self, seconds: f32) -> Self {
+		self.timer = Some(Timer::from_seconds(seconds, false));
+		self
+	}
+	
+	fn get_strength(&self) -> f32 {
+		let progress = self.timer.as_ref().map_or(0.0, |timer| timer.percent());
+		match self.envelope {
+			TintEnvelope::FadeOut => 1.0 - progress,
+			TintEnvelope::FadeIn => progress,
+			TintEnvelope::Pulse(period) => 0.5 - 0.5 * (self.time_elapsed / period * std::f32::consts::TAU).cos(),
+		}
+	}
+	
+	fn is_finished(&self) -> bool {
+		match self.envelope {
+			TintEnvelope::FadeIn => false,
+			_ => self.timer.as_ref().map_or(false, |timer| timer.finished()),
+		}
+	}
+}
+
+/// How long things flash when they get hit, in seconds
+pub const HIT_FLASH_TIME: f32 = 0.15;
+
+/// Goes on the root entity; colours its sprite (but not its shadow) from a stack of tints,
+/// so that different effects can overlap without fighting over the sprite's colour.
+#[derive(Component, Debug)]
+pub struct SpriteTint {
+	/// The sprite's own colour, before any tints
+	base: Color,
+	tints: Vec<Tint>,
+}
+
+impl SpriteTint {
+	pub fn new(base: Color) -> Self {
+		Self {
+			base,
+			tints: Vec::new(),
+		}
+	}
+	
+	/// Adds a tint, replacing any from the same source
+	pub fn push(&mut self, tint: Tint) {
+		self.remove(tint.source);
+		let index = self.tints.iter()
+			.position(|other| other.source > tint.source)
+			.unwrap_or(self.tints.len());
+		self.tints.insert(index, tint);
+	}
+	
+	pub fn remove(&mut self, source: TintSource) {
+		self.tints.retain(|tint| tint.source != source);
+	}
+	
+	pub fn has(&self, source: TintSource) -> bool {
+		self.tints.iter().any(|tint| tint.source == source)
+	}
+	
+	fn get_color(&self) -> Color {
+		let mut color = self.base;
+		for tint in self.tints.iter() {
+			let strength = tint.get_strength();
+			match tint.effect {
+				TintEffect::Mix(mix_color, amount) => {
+					let t = amount * strength;
+					color = Color::rgba(
+						color.r() + (mix_color.r() - color.r()) * t,
+						color.g() + (mix_color.g() - color.g()) * t,
+						color.b() + (mix_color.b() - color.b()) * t,
+						color.a(),
+					);
+				},
+				TintEffect::Opacity(opacity) => {
+					let a = color.a() * (1.0 + (opacity - 1.0) * strength);
+					color.set_a(a);
+				},
+			}
+		}
+		color
+	}
+}
+
+pub fn update_sprite_tints(
+	mut tint_query: Query<(&mut SpriteTint, &Children)>,
+	mut sprite_query: Query<&mut TextureAtlasSprite, (With<FacingSpriteMarker>, Without<ShadowMarker>)>,
+	time: Res<Time>,
+) {
+	for (mut sprite_tint, children) in tint_query.iter_mut() {
+		for tint in sprite_tint.tints.iter_mut() {
+			tint.time_elapsed += time.delta_seconds();
+			if let Some(timer) = tint.timer.as_mut() {
+				timer.tick(time.delta());
+			}
+		}
+		sprite_tint.tints.retain(|tint| !tint.is_finished());
+		
+		let color = sprite_tint.get_color();
+		// Same as shadows, only the first sprite is the entity's own
+		for child in children.iter() {
+			if let Ok(mut sprite) = sprite_query.get_mut(*child) {
+				sprite.color = color;
+				break;
+			}
+		}
+	}
+}
+
+// Depth sorting
+/// Where sorting starts, as a world z offset from the camera
+const SORT_BASE_DEPTH: f32 = -600.0;
+/// World z between each layer
+const LAYER_SPACING: f32 = 100.0;
+/// How much the ground depth counts for within a layer.
+/// Small enough that a layer never spills into the next one, see MAX_SORTED_GROUND_DEPTH
+const GROUND_DEPTH_SCALE: f32 = 0.04;
+const MAX_SORTED_GROUND_DEPTH: f32 = 1000.0;
+
+/// The world z to sort a sprite at, relative to the camera; bigger is drawn on top.
+/// Bevy draws 2d sprites in order of world z, so this is what decides the draw order.
+/// relative_ground_z is how far in front of the camera the sprite is on the ground (also in world z).
+pub fn get_sort_depth(layer: SpriteLayer, relative_ground_z: f32) -> f32 {
+	let ground_depth = relative_ground_z.clamp(-MAX_SORTED_GROUND_DEPTH, MAX_SORTED_GROUND_DEPTH);
+	SORT_BASE_DEPTH + layer as i32 as f32 * LAYER_SPACING + ground_depth * GROUND_DEPTH_SCALE
+}
+
 // Make sprites look nice in our sort-of-3d environment
 pub fn facing_sprite_update(
     parent_query: Query<&Transform, (Without<FacingSpriteMarker>, Without<Camera>)>,
     mut sprite_query: Query<
-        (&mut Transform, &Parent, Option<&SpriteOffset>),
+        (&mut Transform, &Parent, Option<&SpriteOffset>, Option<&SpriteLayer>),
         (With<FacingSpriteMarker>, Without<Camera>),
     >,
     camera_query: Query<&Transform, (With<Camera>, Without<FacingSpriteMarker>)>,
 ) {
     let camera_transform = camera_query.single();
     let camera_inverse = Transform::from_matrix(camera_transform.compute_matrix().inverse());
+    // How much world z changes when moving along the camera's z
+    let camera_z_to_world_z = (camera_transform.rotation * Vec3::Z).z;
 
-    for (mut sprite_transform, parent, maybe_offset) in sprite_query.iter_mut() {
+    for (mut sprite_transform, parent, maybe_offset, maybe_layer) in sprite_query.iter_mut() {
         if let Ok(parent_transform) = parent_query.get(parent.get()) {
             let parent_position = parent_transform.translation;
             let sprite_offset = match maybe_offset {
                 Some(SpriteOffset(o)) => *o,
                 None => Vec3::ZERO,
             };
+            let layer = maybe_layer.copied().unwrap_or_default();
 
             // First we need to transform everything w.r.t the camera
             let parent_camera_loc =
                 camera_inverse * (parent_position + camera_transform.rotation * sprite_offset);
 
             // Then, we want to set the sprite to be pixel-aligned
-            let target_position = parent_camera_loc.round();
+            let mut target_position = parent_camera_loc.round();
+
+            // Then slide it along the view direction until it's at the right depth for sorting.
+            // The view is orthographic, so this doesn't move it on screen.
+            // Hovering doesn't count towards depth, only the position on the ground does.
+            if camera_z_to_world_z.abs() > f32::EPSILON {
+                let sort_z = camera_transform.translation.z + get_sort_depth(
+                    layer,
+                    parent_position.z + sprite_offset.z - camera_transform.translation.z,
+                );
+                let flat_world_z = (*camera_transform * Vec3::new(target_position.x, target_position.y, 0.0)).z;
+                target_position.z = (sort_z - flat_world_z) / camera_z_to_world_z;
+            }
 
             // Then we adjust sprite positioning as needed
             sprite_transform.rotation = camera_transform.rotation;
@@ -127,12 +430,7 @@ fn hover_update(
         &mut SpriteHover,
         &mut SpriteOffset
     )>,
-    spell_ui_active: Res<ui::SpellUiActive>,
 ) {
-	if spell_ui_active.0 {
-		return;
-	}
-	
 	for (mut hover, mut offset) in query.iter_mut() {
 		let prev_sine = (hover.time_elapsed.as_secs_f32() * hover.frequency * std::f32::consts::TAU).sin();
 		hover.time_elapsed += time.delta();
@@ -213,13 +511,8 @@ fn simple_animation_update(
         &mut TextureAtlasSprite,
         &Handle<TextureAtlas>,
     )>,
-    spell_ui_active: Res<ui::SpellUiActive>,
     texture_atlases: Res<Assets<TextureAtlas>>,
 ) {
-    if spell_ui_active.0 {
-        return;
-    }
-
     for (marker, mut timer, mut sprite, handle) in query.iter_mut() {
         timer.tick(time.delta());
         if timer.just_finished() {
@@ -239,17 +532,384 @@ fn simple_animation_update(
     }
 }
 
-fn pause_animation_timers(
-    mut query: Query<&mut AnimationTimer>,
-    spell_ui_active: Res<ui::SpellUiActive>,
+// Animation state machine ///////////////
+/// What a clip does once it reaches its last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LoopMode {
+	Loop,
+	/// Plays once and holds the last frame until something else is requested
+	Once,
+}
+
+/// A run of frames in a texture atlas
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnimationClip {
+	/// Atlas index of the first frame
+	start: usize,
+	length: usize,
+	fps: f32,
+	mode: LoopMode,
+	#[serde(default)]
+	reversed: bool,
+	/// Clips with a higher priority cut off the current one straight away.
+	/// Otherwise the switch waits for the current frame to end, or for the whole clip if it only plays once.
+	#[serde(default)]
+	priority: i32,
+	/// (frame within the clip, name), sent as an AnimationEvent when that frame comes up
+	#[serde(default)]
+	events: Vec<(usize, String)>,
+}
+
+/// All the clips for one sprite sheet, loaded from a .anim.ron file
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "6f1d2c8e-3b4a-4e59-9a7d-0c5e8f2b1a63"]
+pub struct AnimationSet {
+	/// The clip to start off with
+	initial: String,
+	clips: HashMap<String, AnimationClip>,
+}
+
+impl AnimationSet {
+	/// Catches clips that the animator can't play, so they fail to load instead of misbehaving later
+	fn validate(&self) -> Result<(), String> {
+		if !self.clips.contains_key(&self.initial) {
+			return Err(format!("initial clip \"{}\" doesn't exist", self.initial));
+		}
+
+		for (name, clip) in self.clips.iter() {
+			if clip.length == 0 {
+				return Err(format!("clip \"{}\" has no frames", name));
+			}
+			if !(clip.fps.is_finite() && clip.fps > 0.0) {
+				return Err(format!("clip \"{}\" should have more than 0 fps, but has {}", name, clip.fps));
+			}
+			if let Some((frame, event)) = clip.events.iter().find(|(frame, _)| *frame >= clip.length) {
+				return Err(format!("clip \"{}\" has event \"{}\" on frame {}, past its last frame", name, event, frame));
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Default)]
+struct AnimationSetLoader;
+
+impl AssetLoader for AnimationSetLoader {
+	fn load<'a>(
+		&'a self,
+		bytes: &'a [u8],
+		load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+		Box::pin(async move {
+			let animation_set = ron::de::from_bytes::<AnimationSet>(bytes)?;
+			animation_set.validate().map_err(bevy::asset::Error::msg)?;
+			load_context.set_default_asset(LoadedAsset::new(animation_set));
+			Ok(())
+		})
+	}
+	
+	fn extensions(&self) -> &[&str] {
+		&["anim.ron"]
+	}
+}
+
+/// Event for when an animation reaches a frame with an event on it
+pub struct AnimationEvent {
+	pub entity: Entity,
+	pub name: String,
+}
+
+/// Plays clips from an AnimationSet on a TextureAtlasSprite.
+/// Game code drives it with request (for ongoing states) and trigger (for one-shots).
+#[derive(Component, Debug)]
+pub struct Animator {
+	animations: Handle<AnimationSet>,
+	/// None until the animation set has loaded
+	clip: Option<String>,
+	frame: usize,
+	timer: Timer,
+	finished: bool,
+	requested: Option<String>,
+	triggered: Option<String>,
+	/// Plays every clip backwards, for mirrored versions of a sprite
+	reversed: bool,
+}
+
+impl Animator {
+	pub fn new(animations: Handle<AnimationSet>) -> Self {
+		Self {
+			animations,
+			clip: None,
+			frame: 0,
+			timer: Timer::from_seconds(1.0, true),
+			finished: false,
+			requested: None,
+			triggered: None,
+			reversed: false,
+		}
+	}
+	
+	pub fn with_reversed(mut self, reversed: bool) -> Self {
+		self.reversed = reversed;
+		self
+	}
+	
+	/// Asks for the clip for an ongoing state; meant to be called every frame.
+	/// See AnimationClip::priority for when it actually switches over.
+	pub fn request(&mut self, clip: &str) {
+		if self.requested.as_deref() != Some(clip) {
+			self.requested = Some(clip.to_string());
+		}
+	}
+	
+	/// Plays a clip right away, unless the current one has a higher priority.
+	/// Once it's done, it goes back to whatever was requested.
+	pub fn trigger(&mut self, clip: &str) {
+		self.triggered = Some(clip.to_string());
+	}
+	
+	fn start_clip(&mut self, name: String, clip: &AnimationClip) {
+		self.clip = Some(name);
+		self.frame = 0;
+		self.finished = false;
+		self.timer = Timer::from_seconds(1.0 / clip.fps, true);
+	}
+	
+	fn get_atlas_index(&self, clip: &AnimationClip) -> usize {
+		if clip.reversed != self.reversed {
+			clip.start + clip.length - self.frame - 1
+		} else {
+			clip.start + self.frame
+		}
+	}
+}
+
+pub fn update_animators(
+	mut query: Query<(Entity, &mut Animator, &mut TextureAtlasSprite)>,
+	animation_sets: Res<Assets<AnimationSet>>,
+	mut animation_events: EventWriter<AnimationEvent>,
+	time: Res<Time>,
 ) {
-    if spell_ui_active.0 {
-        for mut timer in query.iter_mut() {
-            timer.pause();
-        }
-    } else {
-        for mut timer in query.iter_mut() {
-            timer.unpause();
-        }
-    }
+	for (e, mut animator, mut sprite) in query.iter_mut() {
+		let animation_set = match animation_sets.get(&animator.animations) {
+			Some(animation_set) => animation_set,
+			None => continue,
+		};
+		
+		let mut next_clip = None;
+		let current = animator.clip.as_ref().and_then(|name| animation_set.clips.get(name));
+		match current {
+			None => next_clip = Some(animation_set.initial.clone()),
+			Some(current_clip) => {
+				animator.timer.tick(time.delta());
+				let frame_ended = animator.timer.just_finished();
+				let can_leave = frame_ended && (current_clip.mode == LoopMode::Loop || animator.finished);
+				
+				// One-shots take over unless something more important is playing
+				if let Some(triggered) = animator.triggered.take() {
+					if animation_set.clips.get(&triggered).map_or(false, |clip| clip.priority >= current_clip.priority) {
+						next_clip = Some(triggered);
+					}
+				}
+				// Then the ongoing state
+				if next_clip.is_none() {
+					if let Some(requested) = animator.requested.clone().filter(|name| Some(name) != animator.clip.as_ref()) {
+						if let Some(requested_clip) = animation_set.clips.get(&requested) {
+							if requested_clip.priority > current_clip.priority || can_leave {
+								next_clip = Some(requested);
+							}
+						}
+					}
+				}
+				// Otherwise just move along
+				if next_clip.is_none() && frame_ended && !animator.finished {
+					if animator.frame + 1 < current_clip.length {
+						animator.frame += 1;
+					} else if current_clip.mode == LoopMode::Loop {
+						animator.frame = 0;
+					} else {
+						animator.finished = true;
+						continue;
+					}
+					sprite.index = animator.get_atlas_index(current_clip);
+					send_frame_events(e, animator.frame, current_clip, &mut animation_events);
+				}
+			}
+		}
+		
+		if let Some(name) = next_clip {
+			if let Some(clip) = animation_set.clips.get(&name) {
+				animator.start_clip(name, clip);
+				sprite.index = animator.get_atlas_index(clip);
+				send_frame_events(e, 0, clip, &mut animation_events);
+			}
+		}
+	}
+}
+
+/// A facing sprite driven by an Animator
+#[derive(Bundle)]
+pub struct AnimatedSpriteBundle {
+	#[bundle]
+	sprite_sheet: SpriteSheetBundle,
+	offset: SpriteOffset,
+	facing_marker: FacingSpriteMarker,
+	animator: Animator,
+}
+impl AnimatedSpriteBundle {
+	pub fn new(
+		texture_atlas: Handle<TextureAtlas>,
+		animations: Handle<AnimationSet>,
+		y_offset: f32,
+		reversed: bool,
+	) -> Self {
+		Self {
+			sprite_sheet: SpriteSheetBundle {
+				texture_atlas,
+				..default()
+			},
+			offset: SpriteOffset(Vec3::new(0.0, y_offset, 0.0)),
+			facing_marker: FacingSpriteMarker,
+			animator: Animator::new(animations).with_reversed(reversed),
+		}
+	}
+}
+
+fn send_frame_events(
+	entity: Entity,
+	frame: usize,
+	clip: &AnimationClip,
+	animation_events: &mut EventWriter<AnimationEvent>,
+) {
+	for (event_frame, name) in clip.events.iter() {
+		if *event_frame == frame {
+			animation_events.send(AnimationEvent {
+				entity,
+				name: name.clone(),
+			});
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const LAYERS: [SpriteLayer; 6] = [
+		SpriteLayer::Background,
+		SpriteLayer::Shadow,
+		SpriteLayer::Ground,
+		SpriteLayer::Air,
+		SpriteLayer::Lighting,
+		SpriteLayer::Overlay,
+	];
+
+	#[test]
+	fn layers_sort_in_order_at_any_depth() {
+		let depths = [-MAX_SORTED_GROUND_DEPTH, -250.0, 0.0, 250.0, MAX_SORTED_GROUND_DEPTH];
+		for pair in LAYERS.windows(2) {
+			for lower_depth in depths {
+				for upper_depth in depths {
+					assert!(
+						get_sort_depth(pair[0], lower_depth) < get_sort_depth(pair[1], upper_depth),
+						"{:?} at {} should be below {:?} at {}", pair[0], lower_depth, pair[1], upper_depth
+					);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn nearer_sorts_above_within_a_layer() {
+		for layer in LAYERS {
+			assert!(get_sort_depth(layer, -200.0) < get_sort_depth(layer, -150.0));
+		}
+	}
+
+	#[test]
+	fn clamped_depths_stay_in_their_layer() {
+		for pair in LAYERS.windows(2) {
+			assert!(get_sort_depth(pair[0], f32::MAX) < get_sort_depth(pair[1], f32::MIN));
+			assert_eq!(get_sort_depth(pair[0], 1e6), get_sort_depth(pair[0], MAX_SORTED_GROUND_DEPTH));
+		}
+	}
+
+	/// Runs facing_sprite_update on sprites at these positions and returns the world z they end up at
+	fn sorted_world_z(sprites: &[(Vec3, Vec3, SpriteLayer)]) -> Vec<f32> {
+		let mut world = World::new();
+		world.spawn()
+			.insert(Camera::default())
+			.insert(Transform::from_translation(crate::player::CAMERA_OFFSET).looking_at(Vec3::ZERO, Vec3::Y));
+
+		let children: Vec<Entity> = sprites.iter()
+			.map(|(position, offset, layer)| {
+				let child = world.spawn()
+					.insert(Transform::default())
+					.insert(FacingSpriteMarker)
+					.insert(SpriteOffset(*offset))
+					.insert(*layer)
+					.id();
+				world.spawn()
+					.insert(Transform::from_translation(*position))
+					.push_children(&[child]);
+				child
+			})
+			.collect();
+
+		let mut stage = SystemStage::single_threaded();
+		stage.add_system(facing_sprite_update);
+		stage.run(&mut world);
+
+		children.iter()
+			.zip(sprites)
+			.map(|(child, (position, _, _))| position.z + world.get::<Transform>(*child).unwrap().translation.z)
+			.collect()
+	}
+
+	#[test]
+	fn hover_does_not_change_order() {
+		let z = sorted_world_z(&[
+			(Vec3::new(0.0, 0.0, 0.0), Vec3::ZERO, SpriteLayer::Ground),
+			(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 30.0, 0.0), SpriteLayer::Ground),
+			(Vec3::new(20.0, 0.0, 5.0), Vec3::ZERO, SpriteLayer::Ground),
+		]);
+		assert!((z[0] - z[1]).abs() < 0.01, "hovering moved the sprite from {} to {}", z[0], z[1]);
+		assert!(z[1] < z[2], "the nearer sprite should be on top of the hovering one");
+	}
+
+	#[test]
+	fn layer_beats_distance() {
+		let z = sorted_world_z(&[
+			(Vec3::new(0.0, 0.0, 100.0), Vec3::ZERO, SpriteLayer::Shadow),
+			(Vec3::new(0.0, 0.0, -100.0), Vec3::ZERO, SpriteLayer::Ground),
+			(Vec3::new(0.0, 0.0, -200.0), Vec3::new(0.0, 40.0, 0.0), SpriteLayer::Air),
+		]);
+		assert!(z[0] < z[1] && z[1] < z[2], "sprites sorted as {:?}", z);
+	}
+
+	#[test]
+	fn shipped_animation_sets_are_valid() {
+		for source in [
+			include_str!("../assets/animations/player.anim.ron"),
+			include_str!("../assets/animations/eye.anim.ron"),
+			include_str!("../assets/animations/flame.anim.ron"),
+			include_str!("../assets/animations/spiky.anim.ron"),
+		] {
+			let animation_set = ron::de::from_str::<AnimationSet>(source).unwrap();
+			assert_eq!(animation_set.validate(), Ok(()));
+		}
+	}
+
+	#[test]
+	fn broken_animation_sets_are_rejected() {
+		for source in [
+			r#"(initial: "idle", clips: {"idle": (start: 0, length: 0, fps: 4.0, mode: Loop)})"#,
+			r#"(initial: "idle", clips: {"idle": (start: 0, length: 2, fps: 0.0, mode: Loop)})"#,
+			r#"(initial: "walk", clips: {"idle": (start: 0, length: 2, fps: 4.0, mode: Loop)})"#,
+			r#"(initial: "idle", clips: {"idle": (start: 0, length: 2, fps: 4.0, mode: Loop, events: [(2, "step")])})"#,
+		] {
+			let animation_set = ron::de::from_str::<AnimationSet>(source).unwrap();
+			assert!(animation_set.validate().is_err(), "{} should be rejected", source);
+		}
+	}
 }
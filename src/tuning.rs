@@ -0,0 +1,553 @@
+use super::spells::{SpellElement, SpellShape, ALL_ELEMENTS};
+use bevy::{
+	prelude::*,
+	asset::{AssetLoader, LoadContext, LoadedAsset},
+	reflect::TypeUuid,
+	utils::BoxedFuture,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Balance numbers for spells, the player and enemy AI, loaded from balance.tuning.ron.
+/// With the hot-reload feature the file is watched, so changes show up while the game is running.
+/// Enemies copy their AI numbers when they spawn though, so changes to those only affect enemies spawned afterwards.
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_asset::<Tuning>()
+			.init_asset_loader::<TuningLoader>()
+			.insert_resource(Tuning::default())
+			.add_startup_system(load_tuning)
+			.add_system(apply_tuning);
+	}
+}
+
+const TUNING_PATH: &str = "balance.tuning.ron";
+
+/// Resource
+/// The numbers currently in use. Starts off with the compiled-in values,
+/// and gets replaced every time the file is loaded and passes validation.
+#[derive(Debug, Clone, Default, Deserialize, TypeUuid)]
+#[uuid = "a4e7c2d9-5f18-4b3e-9c6a-1d8f0e2b7a45"]
+pub struct Tuning {
+	pub spells: SpellTuning,
+	pub player: PlayerTuning,
+	pub ai: AITuning,
+}
+
+impl Tuning {
+	/// Catches mistakes that would otherwise only show up as odd behaviour (or panics) in game
+	fn validate(&self) -> Result<(), String> {
+		self.spells.validate()?;
+		self.player.validate()?;
+		self.ai.validate()
+	}
+}
+
+fn require_non_negative(name: &str, value: f32) -> Result<(), String> {
+	if value.is_finite() && value >= 0.0 {
+		Ok(())
+	} else {
+		Err(format!("{} should be at least 0, but is {}", name, value))
+	}
+}
+
+fn require_positive(name: &str, value: f32) -> Result<(), String> {
+	if value.is_finite() && value > 0.0 {
+		Ok(())
+	} else {
+		Err(format!("{} should be more than 0, but is {}", name, value))
+	}
+}
+
+// Spells ///////////////
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpellTuning {
+	/// Mana per rune in a layer
+	pub rune_cost: f32,
+	pub base_damage: f32,
+	pub base_knockback: f32,
+	pub elements: HashMap<SpellElement, ElementTuning>,
+	pub shapes: HashMap<SpellShape, ShapeTuning>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ElementTuning {
+	pub speed: f32,
+	pub damage: f32,
+	pub knockback: f32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ShapeTuning {
+	/// Applies to the current layer
+	pub damage: f32,
+	/// Applies to the layer below
+	pub cost: f32,
+	/// Applies to the layer below
+	pub power: f32,
+	/// Applies to the current layer
+	pub speed: f32,
+	/// Applies to the current layer, on top of base_knockback
+	pub knockback: f32,
+}
+
+const ALL_SHAPES: [SpellShape; 5] = [
+	SpellShape::NoShape,
+	SpellShape::Orb,
+	SpellShape::Line,
+	SpellShape::Burst,
+	SpellShape::Scatter,
+];
+
+impl SpellTuning {
+	pub fn element(&self, element: SpellElement) -> ElementTuning {
+		self.elements[&element]
+	}
+
+	pub fn shape(&self, shape: SpellShape) -> ShapeTuning {
+		self.shapes[&shape]
+	}
+
+	fn validate(&self) -> Result<(), String> {
+		require_non_negative("spells.rune_cost", self.rune_cost)?;
+		require_non_negative("spells.base_damage", self.base_damage)?;
+		require_non_negative("spells.base_knockback", self.base_knockback)?;
+
+		// Lookups assume every element and shape is there
+		for element in ALL_ELEMENTS {
+			let tuning = self.elements.get(&element)
+				.ok_or_else(|| format!("spells.elements is missing {:?}", element))?;
+			require_non_negative(&format!("spells.elements.{:?}.speed", element), tuning.speed)?;
+			require_non_negative(&format!("spells.elements.{:?}.damage", element), tuning.damage)?;
+			require_non_negative(&format!("spells.elements.{:?}.knockback", element), tuning.knockback)?;
+		}
+		for shape in ALL_SHAPES {
+			let tuning = self.shapes.get(&shape)
+				.ok_or_else(|| format!("spells.shapes is missing {:?}", shape))?;
+			require_non_negative(&format!("spells.shapes.{:?}.damage", shape), tuning.damage)?;
+			require_non_negative(&format!("spells.shapes.{:?}.cost", shape), tuning.cost)?;
+			require_non_negative(&format!("spells.shapes.{:?}.power", shape), tuning.power)?;
+			require_non_negative(&format!("spells.shapes.{:?}.speed", shape), tuning.speed)?;
+			require_non_negative(&format!("spells.shapes.{:?}.knockback", shape), tuning.knockback)?;
+		}
+		Ok(())
+	}
+}
+
+impl Default for SpellTuning {
+	fn default() -> Self {
+		let element = |speed, damage, knockback| ElementTuning {
+			speed,
+			damage,
+			knockback,
+		};
+		let shape = |damage, cost, power, speed, knockback| ShapeTuning {
+			damage,
+			cost,
+			power,
+			speed,
+			knockback,
+		};
+
+		Self {
+			rune_cost: 8.0,
+			base_damage: 5.0,
+			base_knockback: 50.0,
+			elements: HashMap::from([
+				(SpellElement::Neutral, element(1.0, 0.0, 0.1)),
+				(SpellElement::Fire, element(1.0, 1.0, 0.7)),
+				(SpellElement::Water, element(0.7, 0.8, 1.3)),
+				(SpellElement::Earth, element(1.4, 0.8, 1.0)),
+				(SpellElement::Air, element(1.2, 0.7, 1.5)),
+				(SpellElement::Metal, element(1.5, 1.4, 1.0)),
+				(SpellElement::Plant, element(0.85, 0.8, 2.0)),
+				(SpellElement::Electric, element(1.8, 1.8, 0.1)),
+				(SpellElement::Ice, element(1.0, 1.4, 1.5)),
+				(SpellElement::Light, element(2.0, 5.0, 0.4)),
+			]),
+			shapes: HashMap::from([
+				(SpellShape::NoShape, shape(2.0, 1.0, 1.5, 0.0, 1.2)),
+				(SpellShape::Orb, shape(1.0, 1.1, 1.0, 90.0, 1.0)),
+				(SpellShape::Line, shape(1.0, 1.3, 0.6, 110.0, 1.0)),
+				(SpellShape::Burst, shape(0.7, 1.2, 0.6, 130.0, 1.0)),
+				(SpellShape::Scatter, shape(0.5, 1.3, 0.3, 130.0, 1.0)),
+			]),
+		}
+	}
+}
+
+// Player ///////////////
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerTuning {
+	pub speed: f32,
+	pub run_speed_multiplier: f32,
+	pub accel_forward: f32,
+	pub accel_neutral: f32,
+	pub accel_decel: f32,
+	pub knockback_speed: f32,
+	/// How much a heart pickup restores
+	pub heart_pickup_health: i32,
+	/// How much a mana orb pickup restores
+	pub mana_orb_pickup_mana: i32,
+	/// Per second
+	pub base_mana_regen: f32,
+	/// Regen speeds up with recently spent mana; higher means it speeds up less
+	pub mana_regen_defactor: f32,
+	/// The UI draws health in hearts of this much each
+	pub health_per_heart: i32,
+	/// The UI draws mana in orbs of this much each
+	pub mana_per_orb: i32,
+	pub max_stamina: f32,
+	/// Per second
+	pub stamina_regen: f32,
+	/// How long after using stamina before it starts coming back
+	pub stamina_regen_delay: f32,
+	/// Per second of running
+	pub run_stamina_cost: f32,
+	pub dodge_stamina_cost: f32,
+	pub dodge_speed: f32,
+	pub dodge_time: f32,
+}
+
+impl PlayerTuning {
+	fn validate(&self) -> Result<(), String> {
+		require_positive("player.speed", self.speed)?;
+		require_positive("player.run_speed_multiplier", self.run_speed_multiplier)?;
+		require_positive("player.accel_forward", self.accel_forward)?;
+		require_positive("player.accel_neutral", self.accel_neutral)?;
+		require_positive("player.accel_decel", self.accel_decel)?;
+		require_non_negative("player.knockback_speed", self.knockback_speed)?;
+		require_positive("player.heart_pickup_health", self.heart_pickup_health as f32)?;
+		require_positive("player.mana_orb_pickup_mana", self.mana_orb_pickup_mana as f32)?;
+		require_non_negative("player.base_mana_regen", self.base_mana_regen)?;
+		require_positive("player.mana_regen_defactor", self.mana_regen_defactor)?;
+		require_positive("player.health_per_heart", self.health_per_heart as f32)?;
+		require_positive("player.mana_per_orb", self.mana_per_orb as f32)?;
+		require_positive("player.max_stamina", self.max_stamina)?;
+		require_non_negative("player.stamina_regen", self.stamina_regen)?;
+		require_non_negative("player.stamina_regen_delay", self.stamina_regen_delay)?;
+		require_non_negative("player.run_stamina_cost", self.run_stamina_cost)?;
+		require_non_negative("player.dodge_stamina_cost", self.dodge_stamina_cost)?;
+		if self.dodge_stamina_cost > self.max_stamina {
+			return Err(format!(
+				"player.dodge_stamina_cost ({}) is more than player.max_stamina ({}), so dodging would be impossible",
+				self.dodge_stamina_cost, self.max_stamina
+			));
+		}
+		require_non_negative("player.dodge_speed", self.dodge_speed)?;
+		require_positive("player.dodge_time", self.dodge_time)
+	}
+}
+
+impl Default for PlayerTuning {
+	fn default() -> Self {
+		Self {
+			speed: 70.0,
+			run_speed_multiplier: 1.6,
+			accel_forward: 560.0,
+			accel_neutral: 360.0,
+			accel_decel: 480.0,
+			knockback_speed: 50.0,
+			heart_pickup_health: 4,
+			mana_orb_pickup_mana: 20,
+			base_mana_regen: 5.0,
+			mana_regen_defactor: 10.0,
+			health_per_heart: 4,
+			mana_per_orb: 20,
+			max_stamina: 100.0,
+			stamina_regen: 40.0,
+			stamina_regen_delay: 0.5,
+			run_stamina_cost: 30.0,
+			dodge_stamina_cost: 35.0,
+			dodge_speed: 200.0,
+			dodge_time: 0.25,
+		}
+	}
+}
+
+// AI ///////////////
+/// What each kind of AI starts off with. Rooms can still override some of it for particular enemies.
+/// Times are in seconds and angles in degrees.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AITuning {
+	pub periodic_charge: PeriodicChargeTuning,
+	pub rotate_around: RotateAroundTuning,
+	pub shielded: ShieldedTuning,
+	pub teleporter: TeleporterTuning,
+	pub summoner: SummonerTuning,
+	pub turret: TurretTuning,
+}
+
+impl AITuning {
+	fn validate(&self) -> Result<(), String> {
+		let charge = &self.periodic_charge;
+		require_positive("ai.periodic_charge.charge_period", charge.charge_period)?;
+		require_non_negative("ai.periodic_charge.speed", charge.speed)?;
+		require_non_negative("ai.periodic_charge.max_dev_angle", charge.max_dev_angle)?;
+
+		let rotate = &self.rotate_around;
+		require_non_negative("ai.rotate_around.rotate_max_speed", rotate.rotate_max_speed)?;
+		require_positive("ai.rotate_around.rotate_period", rotate.rotate_period)?;
+		require_non_negative("ai.rotate_around.rotate_dist", rotate.rotate_dist)?;
+		require_non_negative("ai.rotate_around.charge_speed", rotate.charge_speed)?;
+		require_non_negative("ai.rotate_around.charge_decide_rate", rotate.charge_decide_rate)?;
+		require_positive("ai.rotate_around.charge_time", rotate.charge_time)?;
+
+		let shielded = &self.shielded;
+		require_non_negative("ai.shielded.walk_speed", shielded.walk_speed)?;
+		require_non_negative("ai.shielded.turn_rate", shielded.turn_rate)?;
+		require_non_negative("ai.shielded.shield_half_angle", shielded.shield_half_angle)?;
+
+		let teleporter = &self.teleporter;
+		require_positive("ai.teleporter.blink_period", teleporter.blink_period)?;
+		require_positive("ai.teleporter.windup_time", teleporter.windup_time)?;
+		require_non_negative("ai.teleporter.blink_distance", teleporter.blink_distance)?;
+		require_non_negative("ai.teleporter.lunge_speed", teleporter.lunge_speed)?;
+
+		let summoner = &self.summoner;
+		require_positive("ai.summoner.summon_period", summoner.summon_period)?;
+		require_non_negative("ai.summoner.keep_away_dist", summoner.keep_away_dist)?;
+		require_non_negative("ai.summoner.move_speed", summoner.move_speed)?;
+		require_positive("ai.summoner.minion_health", summoner.minion_health as f32)?;
+
+		let turret = &self.turret;
+		require_positive("ai.turret.burst_period", turret.burst_period)?;
+		require_positive("ai.turret.shot_period", turret.shot_period)?;
+		require_non_negative("ai.turret.spread", turret.spread)?;
+		require_non_negative("ai.turret.shot_speed", turret.shot_speed)
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PeriodicChargeTuning {
+	pub charge_period: f32,
+	pub speed: f32,
+	pub max_dev_angle: f32,
+}
+
+impl Default for PeriodicChargeTuning {
+	fn default() -> Self {
+		Self {
+			charge_period: 1.0,
+			speed: 160.0,
+			max_dev_angle: 22.5,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RotateAroundTuning {
+	pub rotate_max_speed: f32,
+	/// How long it takes to go all the way around the player
+	pub rotate_period: f32,
+	pub rotate_dist: f32,
+	pub charge_speed: f32,
+	/// Chance per second of deciding to charge
+	pub charge_decide_rate: f32,
+	pub charge_time: f32,
+}
+
+impl Default for RotateAroundTuning {
+	fn default() -> Self {
+		Self {
+			rotate_max_speed: 100.0,
+			rotate_period: 6.0,
+			rotate_dist: 80.0,
+			charge_speed: 160.0,
+			charge_decide_rate: 0.25,
+			charge_time: 2.0 * 80.0 / 140.0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShieldedTuning {
+	pub walk_speed: f32,
+	/// Per second
+	pub turn_rate: f32,
+	pub shield_half_angle: f32,
+}
+
+impl Default for ShieldedTuning {
+	fn default() -> Self {
+		Self {
+			walk_speed: 30.0,
+			turn_rate: 90.0,
+			shield_half_angle: 60.0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TeleporterTuning {
+	pub blink_period: f32,
+	pub windup_time: f32,
+	pub blink_distance: f32,
+	pub lunge_speed: f32,
+}
+
+impl Default for TeleporterTuning {
+	fn default() -> Self {
+		Self {
+			blink_period: 2.5,
+			windup_time: 0.5,
+			blink_distance: 60.0,
+			lunge_speed: 150.0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SummonerTuning {
+	pub summon_period: f32,
+	pub summon_count: u32,
+	pub max_minions: usize,
+	pub keep_away_dist: f32,
+	pub move_speed: f32,
+	pub minion_health: i32,
+	pub minion_damage: i32,
+}
+
+impl Default for SummonerTuning {
+	fn default() -> Self {
+		Self {
+			summon_period: 4.0,
+			summon_count: 2,
+			max_minions: 4,
+			keep_away_dist: 90.0,
+			move_speed: 40.0,
+			minion_health: 15,
+			minion_damage: 1,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TurretTuning {
+	pub burst_period: f32,
+	pub shot_period: f32,
+	pub shots_per_burst: u32,
+	/// Most a shot can be off from aiming straight at the player
+	pub spread: f32,
+	pub shot_speed: f32,
+	pub shot_damage: i32,
+}
+
+impl Default for TurretTuning {
+	fn default() -> Self {
+		Self {
+			burst_period: 2.5,
+			shot_period: 0.15,
+			shots_per_burst: 3,
+			spread: 11.25,
+			shot_speed: 110.0,
+			shot_damage: 1,
+		}
+	}
+}
+
+// Loading ///////////////
+#[derive(Default)]
+struct TuningLoader;
+
+impl AssetLoader for TuningLoader {
+	fn load<'a>(
+		&'a self,
+		bytes: &'a [u8],
+		load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+		Box::pin(async move {
+			let tuning = ron::de::from_bytes::<Tuning>(bytes)?;
+			// A bad file gets logged by the asset server, and the previous numbers stay in use
+			tuning.validate().map_err(bevy::asset::Error::msg)?;
+			load_context.set_default_asset(LoadedAsset::new(tuning));
+			Ok(())
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["tuning.ron"]
+	}
+}
+
+/// Keeps the file loaded so it can be reloaded
+struct TuningHandle(Handle<Tuning>);
+
+fn load_tuning(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	commands.insert_resource(TuningHandle(asset_server.load(TUNING_PATH)));
+}
+
+pub fn apply_tuning(
+	mut asset_events: EventReader<AssetEvent<Tuning>>,
+	tuning_assets: Res<Assets<Tuning>>,
+	tuning_handle: Res<TuningHandle>,
+	mut tuning: ResMut<Tuning>,
+) {
+	for event in asset_events.iter() {
+		match event {
+			AssetEvent::Created { handle } | AssetEvent::Modified { handle } if *handle == tuning_handle.0 => {
+				if let Some(loaded) = tuning_assets.get(handle) {
+					*tuning = loaded.clone();
+					info!("loaded tuning from {}", TUNING_PATH);
+				}
+			},
+			_ => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn shipped_tuning() -> Tuning {
+		ron::de::from_bytes::<Tuning>(include_bytes!("../assets/balance.tuning.ron")).unwrap()
+	}
+
+	fn assert_close(name: &str, shipped: f32, default: f32) {
+		assert!((shipped - default).abs() < 1e-4, "{} is {} in the file but {} by default", name, shipped, default);
+	}
+
+	#[test]
+	fn shipped_tuning_is_valid() {
+		assert_eq!(shipped_tuning().validate(), Ok(()));
+		assert_eq!(Tuning::default().validate(), Ok(()));
+	}
+
+	#[test]
+	fn shipped_tuning_matches_defaults() {
+		let shipped = shipped_tuning();
+		let default = Tuning::default();
+
+		assert_close("spells.rune_cost", shipped.spells.rune_cost, default.spells.rune_cost);
+		for element in ALL_ELEMENTS {
+			let (a, b) = (shipped.spells.element(element), default.spells.element(element));
+			assert_close(&format!("{:?}.speed", element), a.speed, b.speed);
+			assert_close(&format!("{:?}.damage", element), a.damage, b.damage);
+			assert_close(&format!("{:?}.knockback", element), a.knockback, b.knockback);
+		}
+		for shape in ALL_SHAPES {
+			let (a, b) = (shipped.spells.shape(shape), default.spells.shape(shape));
+			assert_close(&format!("{:?}.cost", shape), a.cost, b.cost);
+			assert_close(&format!("{:?}.power", shape), a.power, b.power);
+			assert_close(&format!("{:?}.speed", shape), a.speed, b.speed);
+		}
+
+		assert_close("player.speed", shipped.player.speed, default.player.speed);
+		assert_close("player.max_stamina", shipped.player.max_stamina, default.player.max_stamina);
+		assert_close("player.dodge_time", shipped.player.dodge_time, default.player.dodge_time);
+		assert_eq!(shipped.player.health_per_heart, default.player.health_per_heart);
+		assert_eq!(shipped.player.mana_per_orb, default.player.mana_per_orb);
+
+		assert_close("ai.rotate_around.charge_time", shipped.ai.rotate_around.charge_time, default.ai.rotate_around.charge_time);
+		assert_close("ai.teleporter.blink_period", shipped.ai.teleporter.blink_period, default.ai.teleporter.blink_period);
+		assert_eq!(shipped.ai.summoner.max_minions, default.ai.summoner.max_minions);
+		assert_eq!(shipped.ai.turret.shots_per_burst, default.ai.turret.shots_per_burst);
+	}
+}
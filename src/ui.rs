@@ -1,21 +1,28 @@
-use super::{player, spells, levels};
-use bevy::{prelude::*, utils::HashMap};
+use super::{player, spells, levels, enemy, difficulty, viewport, ray, gamepad, bindings, game_state, sound, feedback, expand_vec2};
+use bevy::{prelude::*, utils::HashMap, window::CursorMoved};
 use leafwing_input_manager::prelude::*;
 
 pub struct UIPlugin;
 
+/// Size of the area the ui is laid out in; see viewport::UiRoot
+pub const UI_WIDTH: f32 = viewport::BASE_WIDTH as f32;
+pub const UI_HEIGHT: f32 = viewport::BASE_HEIGHT as f32;
+
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         app
 			.insert_resource(AllMouseoverTargets::new())
-            .insert_resource(SpellUiActive(false))
+            .insert_resource(CurrentMenuPage(MenuPage::Title))
             .insert_resource(CurrentMouseoverTarget(None))
+            .insert_resource(UiNavSelection(None))
 			.add_event::<MessageEvent>()
             .add_startup_system(setup_spell_ui)
             .add_system(update_spell_ui_visibility)
             .add_system(toggle_spell_ui)
+			.add_system(toggle_pause.before(bindings::capture_rebind))
+			.add_system(update_menu_visibility)
 			.add_system(update_rune_ui_displays)
-			.add_system(update_spell_selection)
+			.add_system(update_spell_selection.with_run_criteria(game_state::is_in_inventory))
 			.add_system(update_selection_rune_containers.before(update_rune_ui_displays))
 			.add_system(update_inventory_rune_containers.before(update_rune_ui_displays))
 			.add_system(update_queued_rune_containers.before(update_rune_ui_displays))
@@ -23,16 +30,34 @@ impl Plugin for UIPlugin {
 			.add_startup_system(setup_player_ui)
 			.add_system(update_player_health_ui)
 			.add_system(update_player_mana_ui.after(player::update_spell_casting))
+			.add_startup_system(setup_stamina_ui)
+			.add_system(update_player_stamina_ui)
 			.add_startup_system(setup_message_ui)
 			.add_system(update_message_ui)
-			.add_system(do_message_triggers);
+			.add_system(do_message_triggers)
+			.add_startup_system(setup_difficulty_menu)
+			.add_system(update_difficulty_menu.with_run_criteria(game_state::is_in_settings_menu))
+			.add_system(update_difficulty_menu_text.after(update_difficulty_menu))
+			.add_startup_system(setup_volume_menu)
+			.add_system(update_volume_menu.with_run_criteria(game_state::is_in_settings_menu).before(update_spell_selection))
+			.add_system(update_volume_menu_text.after(update_volume_menu))
+			.add_startup_system(setup_screen_shake_toggle)
+			.add_system(update_screen_shake_toggle.with_run_criteria(game_state::is_in_settings_menu).before(update_spell_selection))
+			.add_system(update_screen_shake_toggle_text.after(update_screen_shake_toggle))
+			.add_system(update_ui_world_anchors)
+			.add_system(spawn_notice_indicators)
+			.add_system(update_notice_indicators)
+			.add_startup_system(setup_gamepad_ui)
+			.add_system(update_ui_navigation.before(update_spell_selection).before(update_difficulty_menu).before(update_volume_menu).before(update_screen_shake_toggle))
+			.add_system(update_aim_reticle.after(gamepad::update_gamepad_state))
+			.add_startup_system(setup_controls_menu)
+			.add_system(update_controls_menu.with_run_criteria(game_state::is_in_settings_menu).before(update_spell_selection).after(bindings::capture_rebind))
+			.add_system(update_controls_menu_text.after(update_controls_menu))
+			.add_startup_system(setup_menus)
+			.add_system(update_menus.before(update_spell_selection).after(toggle_pause));
     }
 }
 
-/// Resource
-/// Keeps track of whether the spell inventory UI is active.
-pub struct SpellUiActive(pub bool);
-
 /// Component for updating ui rune sprites. Recommended to put on a child entity if used with SpellSelectUi
 #[derive(Debug, Component, Deref)]
 pub struct UiRuneContainer(pub Option<spells::Rune>);
@@ -123,22 +148,27 @@ struct SpellSelectUi {
 
 fn update_spell_ui_visibility(
     mut query: Query<(&mut Visibility, &SpellSelectUi)>,
-    spell_ui_active: Res<SpellUiActive>,
+    state: Res<State<game_state::GameState>>,
 ) {
+    let inventory_open = *state.current() == game_state::GameState::Inventory;
     for (mut vis, spell_ui) in &mut query {
         // Visible if the booleans are the same value
-        vis.is_visible = spell_ui.inventory_page == spell_ui_active.0;
+        vis.is_visible = spell_ui.inventory_page == inventory_open;
     }
 }
 
 fn toggle_spell_ui(
     action_state: Query<&ActionState<player::Action>>,
-    mut spell_ui_active: ResMut<SpellUiActive>,
+    mut state: ResMut<State<game_state::GameState>>,
 ) {
     let action_state = action_state.single();
     // toggle if tab is pressed
     if action_state.just_pressed(player::Action::OpenInventory) {
-        spell_ui_active.0 = !spell_ui_active.0;
+        match state.current() {
+            game_state::GameState::Playing => game_state::set_state(&mut state, game_state::GameState::Inventory),
+            game_state::GameState::Inventory => game_state::set_state(&mut state, game_state::GameState::Playing),
+            _ => {}
+        }
     }
 }
 
@@ -182,7 +212,7 @@ fn setup_spell_ui(
 					position_type: PositionType::Absolute,
 					position: UiRect {
 						top: Val::Px(4.0),
-						left: Val::Px(640.0 - (4.0 + (1+4-i) as f32 * 44.0)),
+						left: Val::Px(UI_WIDTH - (4.0 + (1+4-i) as f32 * 44.0)),
 						..default()
 					},
 					..default()
@@ -236,7 +266,7 @@ fn setup_spell_ui(
 					position: UiRect {
 						top: Val::Px(selected_row_top),
 						// Center them horizontally
-						left: Val::Px(320.0 + (-20.0 + (i as f32 - 2.0) * 44.0)),
+						left: Val::Px(UI_WIDTH / 2.0 + (-20.0 + (i as f32 - 2.0) * 44.0)),
 						..default()
 					},
 					..default()
@@ -284,7 +314,7 @@ fn setup_spell_ui(
 		new_mouseover_targets.push(MouseoverTargetSpace {
 			target: MouseoverTarget::SpellSelectedSlot(i),
 			top: selected_row_top,
-			left: 320.0 + (-20.0 + (i as f32 - 2.0) * 44.0),
+			left: UI_WIDTH / 2.0 + (-20.0 + (i as f32 - 2.0) * 44.0),
 			width: 20.0 * 2.0,
 			height: 24.0 * 2.0,
 			source_entity: selection_slot,
@@ -304,7 +334,7 @@ fn setup_spell_ui(
 						position: UiRect {
 							top: Val::Px(inventory_row_top + row as f32 * 44.0),
 							// Center them horizontally
-							left: Val::Px(320.0 + 4.0 + (col as f32 - 2.0) * 44.0),
+							left: Val::Px(UI_WIDTH / 2.0 + 4.0 + (col as f32 - 2.0) * 44.0),
 							..default()
 						},
 						..default()
@@ -352,7 +382,7 @@ fn setup_spell_ui(
 			new_mouseover_targets.push(MouseoverTargetSpace {
 				target: MouseoverTarget::SpellInventorySlot(idx),
 				top: inventory_row_top + row as f32 * 44.0,
-				left: 320.0 + 4.0 + (col as f32 - 2.0) * 44.0,
+				left: UI_WIDTH / 2.0 + 4.0 + (col as f32 - 2.0) * 44.0,
 				width: 20.0 * 2.0,
 				height: 20.0 * 2.0,
 				source_entity: inventory_slot,
@@ -369,7 +399,7 @@ fn setup_spell_ui(
 					position_type: PositionType::Absolute,
 					position: UiRect {
 						bottom: Val::Px(8.0),
-						left: Val::Px(320.0 + 2.0 + (i as f32 - N_QUEUED_SHOW as f32 / 2.0) * 36.0),
+						left: Val::Px(UI_WIDTH / 2.0 + 2.0 + (i as f32 - N_QUEUED_SHOW as f32 / 2.0) * 36.0),
 						..default()
 					},
 					..default()
@@ -402,12 +432,15 @@ pub enum MessageSource {
 	ObtainedRune,
 	Ending,
 	Defeated,
+	Controls,
 }
 
 #[derive(Component)]
 struct MessageUI {
 	source: MessageSource,
 	has_message: bool,
+	/// The message before filling in key names, so it can be redone if they change
+	raw_message: String,
 }
 // Marker component
 #[derive(Component)]
@@ -435,8 +468,8 @@ fn setup_message_ui(
 			style: Style {
 				position_type: PositionType::Absolute,
 				position: UiRect {
-					top: Val::Px(400.0 - 68.0),
-					left: Val::Px(320.0 - 240.0),
+					top: Val::Px(UI_HEIGHT - 68.0),
+					left: Val::Px((UI_WIDTH - 480.0) / 2.0),
 					..default()
 				},
 				..default()
@@ -460,7 +493,8 @@ fn setup_message_ui(
 			parent.spawn()
 				.insert(MessageUI {
 					source: MessageSource::None,
-					has_message: false
+					has_message: false,
+					raw_message: String::new(),
 				})
 				.insert_bundle(TextBundle {
 					node: get_scaled_size(246, 20),
@@ -488,6 +522,7 @@ fn update_message_ui(
 	mut frame_query: Query<&mut Visibility, With<MessageUIFrame>>,
 	mut message_events: EventReader<MessageEvent>,
 	text_style: Res<MessageTextStyle>,
+	key_bindings: Res<bindings::KeyBindings>,
 ) {
 	let (mut text, mut message_ui_data) = text_query.single_mut();
 	let mut visibility = frame_query.single_mut();
@@ -497,9 +532,10 @@ fn update_message_ui(
 		if let Some(message) = &event.message {
 			// New message sent, always replace in this case
 			*text = Text::from_section(
-				message,
+				key_bindings.format_message(message),
 				text_style.0.clone()
 			);
+			message_ui_data.raw_message = message.clone();
 			message_ui_data.source = event.source;
 			message_ui_data.has_message = true;
 		} else if message_ui_data.has_message {
@@ -511,6 +547,13 @@ fn update_message_ui(
 		}
 	}
 	
+	if key_bindings.is_changed() && message_ui_data.has_message {
+		*text = Text::from_section(
+			key_bindings.format_message(&message_ui_data.raw_message),
+			text_style.0.clone()
+		);
+	}
+	
 	// Update visibility
 	visibility.is_visible = message_ui_data.has_message;
 }
@@ -539,7 +582,7 @@ fn do_message_triggers(
 	mut message_events: EventWriter<MessageEvent>,
 	// Needs a bunch of random arguments to be able to check all of the triggers
 	time: Res<Time>,
-    spell_ui_active: Res<SpellUiActive>,
+	state: Res<State<game_state::GameState>>,
 	player_query: Query<(&ActionState<player::Action>, &player::PlayerHasStaff), With<player::Player>>,
 	spell_query: Query<(), With<spells::SpellMarker>>,
 	gate_query: Query<(), With<levels::GateMarker>>,
@@ -550,11 +593,13 @@ fn do_message_triggers(
 	for (e, mut trigger) in query.iter_mut() {
 		let is_activated = match &mut trigger.trigger_type {
 			MessageTriggerType::OnTimer(ref mut timer) => {
-				timer.tick(time.delta());
+				if state.current().is_world_running() {
+					timer.tick(time.delta());
+				}
 				timer.just_finished()
 			},
 			MessageTriggerType::OnSpellUi(open) => {
-				*open == spell_ui_active.0
+				*open == (*state.current() == game_state::GameState::Inventory)
 			},
 			MessageTriggerType::OnMove => {
 				action_state.just_pressed(player::Action::Left)
@@ -654,6 +699,57 @@ fn setup_player_ui(
 	}
 }
 
+// Stamina is a plain bar rather than icons
+#[derive(Component, Debug)]
+struct PlayerStaminaUiFill;
+const STAMINA_BAR_WIDTH: f32 = 76.0;
+
+fn setup_stamina_ui(mut commands: Commands) {
+	commands
+		.spawn_bundle(NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					top: Val::Px(44.0),
+					left: Val::Px(4.0),
+					..default()
+				},
+				size: Size::new(Val::Px(STAMINA_BAR_WIDTH + 2.0), Val::Px(6.0)),
+				..default()
+			},
+			color: UiColor(Color::hex("253A5E").unwrap()),
+			..default()
+		})
+		.with_children(|parent| {
+			parent
+				.spawn_bundle(NodeBundle {
+					style: Style {
+						position_type: PositionType::Absolute,
+						position: UiRect {
+							top: Val::Px(1.0),
+							left: Val::Px(1.0),
+							..default()
+						},
+						size: Size::new(Val::Px(STAMINA_BAR_WIDTH), Val::Px(4.0)),
+						..default()
+					},
+					color: UiColor(Color::hex("A8CA58").unwrap()),
+					..default()
+				})
+				.insert(PlayerStaminaUiFill);
+		});
+}
+
+fn update_player_stamina_ui(
+	mut ui_query: Query<&mut Style, With<PlayerStaminaUiFill>>,
+	player_query: Query<&player::PlayerStamina, (With<player::Player>, Changed<player::PlayerStamina>)>,
+) {
+	if let Ok(stamina) = player_query.get_single() {
+		let mut style = ui_query.single_mut();
+		style.size.width = Val::Px((STAMINA_BAR_WIDTH * stamina.stamina / stamina.max_stamina).round());
+	}
+}
+
 // probably could be genericized, but w/e
 #[derive(Component, Debug)]
 struct PlayerHealthUi(usize);
@@ -672,7 +768,7 @@ fn update_player_health_ui(
 	let player_health = player_query.single();
 	let n_hearts = player_health.get_heart_count();
 	let n_full_hearts = player_health.get_filled_heart_count();
-	let last_heart_state = player_health.get_last_heart_state();
+	let last_heart_state = player_health.get_last_heart_state(5);
 	
 	for (index_marker, mut image, mut visibility) in ui_query.iter_mut() {
 		let idx = index_marker.0;
@@ -743,18 +839,608 @@ fn update_player_mana_ui(
 }
 
 
+// Difficulty menu; lives on the settings page /////////////
+#[derive(Component, Debug)]
+struct DifficultyPresetText(difficulty::DifficultyPreset);
+#[derive(Component, Debug)]
+struct DifficultyValueText(difficulty::DifficultySetting);
+#[derive(Component, Debug)]
+struct DifficultyTitleText;
+
+const DIFFICULTY_MENU_LEFT: f32 = 16.0;
+const DIFFICULTY_MENU_TOP: f32 = 80.0;
+const DIFFICULTY_ROW_HEIGHT: f32 = 20.0;
+
+fn setup_difficulty_menu(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut all_mouseover_targets: ResMut<AllMouseoverTargets>,
+) {
+	let text_style = TextStyle {
+		font: asset_server.load("font/Mechanical-g5Y5.otf"),
+		font_size: 10.0,
+		color: Color::hex("B8EEEB").unwrap(),
+	};
+	let title = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, "Difficulty", DIFFICULTY_MENU_TOP, DIFFICULTY_MENU_LEFT);
+	commands.entity(title).insert(DifficultyTitleText);
+	
+	// Presets
+	let preset_top = DIFFICULTY_MENU_TOP + DIFFICULTY_ROW_HEIGHT;
+	for (i, preset) in difficulty::DifficultyPreset::SELECTABLE.iter().enumerate() {
+		let left = DIFFICULTY_MENU_LEFT + i as f32 * 48.0;
+		let preset_text = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, preset.name(), preset_top, left);
+		commands.entity(preset_text).insert(DifficultyPresetText(*preset));
+		
+		all_mouseover_targets.0.push(MouseoverTargetSpace {
+			target: MouseoverTarget::DifficultyPreset(*preset),
+			top: preset_top,
+			left,
+			width: 44.0,
+			height: 16.0,
+			source_entity: preset_text,
+		});
+	}
+	
+	// Custom sliders
+	for (i, setting) in difficulty::DifficultySetting::ALL.iter().enumerate() {
+		let top = preset_top + (i + 1) as f32 * DIFFICULTY_ROW_HEIGHT;
+		
+		spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, setting.name(), top, DIFFICULTY_MENU_LEFT);
+		let value_text = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, "", top, DIFFICULTY_MENU_LEFT + 84.0);
+		commands.entity(value_text).insert(DifficultyValueText(*setting));
+		
+		for (increase, label, left) in [(false, "-", 120.0), (true, "+", 136.0)] {
+			let button = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, label, top, DIFFICULTY_MENU_LEFT + left);
+			all_mouseover_targets.0.push(MouseoverTargetSpace {
+				target: MouseoverTarget::DifficultySlider(*setting, increase),
+				top,
+				left: DIFFICULTY_MENU_LEFT + left,
+				width: 14.0,
+				height: 16.0,
+				source_entity: button,
+			});
+		}
+	}
+}
+
+/// Every element of the menus is just some text
+fn spawn_menu_text(
+	commands: &mut Commands,
+	text_style: &TextStyle,
+	page: MenuPage,
+	text: &str,
+	top: f32,
+	left: f32,
+) -> Entity {
+	commands
+		.spawn_bundle(TextBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					top: Val::Px(top),
+					left: Val::Px(left),
+					..default()
+				},
+				..default()
+			},
+			text: Text::from_section(text, text_style.clone()),
+			..default()
+		})
+		.insert(page)
+		.insert(viewport::InMenuLayer)
+		.id()
+}
+
+fn update_difficulty_menu(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mouseover_target: Res<CurrentMouseoverTarget>,
+	mut difficulty: ResMut<difficulty::Difficulty>,
+) {
+	if !action_query.single().just_pressed(player::Action::CastSpell) {
+		return;
+	}
+	
+	match mouseover_target.0 {
+		Some((MouseoverTarget::DifficultyPreset(preset), _)) => {
+			*difficulty = difficulty::Difficulty::from_preset(preset);
+		}
+		Some((MouseoverTarget::DifficultySlider(setting, increase), _)) => {
+			difficulty.step(setting, increase);
+		}
+		_ => {}
+	}
+}
+
+fn update_difficulty_menu_text(
+	mut preset_query: Query<(&DifficultyPresetText, &mut Text)>,
+	mut value_query: Query<(&DifficultyValueText, &mut Text), Without<DifficultyPresetText>>,
+	mut title_query: Query<&mut Text, (With<DifficultyTitleText>, Without<DifficultyPresetText>, Without<DifficultyValueText>)>,
+	difficulty: Res<difficulty::Difficulty>,
+) {
+	if !difficulty.is_changed() {
+		return;
+	}
+	
+	for (preset_text, mut text) in preset_query.iter_mut() {
+		// Highlight the selected preset
+		text.sections[0].style.color = if preset_text.0 == difficulty.preset {
+			Color::hex("F2F5E6").unwrap()
+		} else {
+			Color::hex("577277").unwrap()
+		};
+	}
+	for (value_text, mut text) in value_query.iter_mut() {
+		text.sections[0].value = format!("x{:.2}", difficulty.get(value_text.0));
+	}
+	for mut text in title_query.iter_mut() {
+		text.sections[0].value = format!("Difficulty: {}", difficulty.preset.name());
+	}
+}
+
+// Volume menu; under the difficulty menu ///////////////
+#[derive(Component, Debug)]
+struct VolumeValueText(sound::VolumeChannel);
+
+const VOLUME_MENU_TOP: f32 = 228.0;
+
+fn setup_volume_menu(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut all_mouseover_targets: ResMut<AllMouseoverTargets>,
+) {
+	let text_style = TextStyle {
+		font: asset_server.load("font/Mechanical-g5Y5.otf"),
+		font_size: 10.0,
+		color: Color::hex("B8EEEB").unwrap(),
+	};
+	spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, "Volume", VOLUME_MENU_TOP, DIFFICULTY_MENU_LEFT);
+	
+	for (i, channel) in sound::VolumeChannel::ALL.iter().enumerate() {
+		let top = VOLUME_MENU_TOP + (i + 1) as f32 * DIFFICULTY_ROW_HEIGHT;
+		
+		spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, channel.name(), top, DIFFICULTY_MENU_LEFT);
+		let value_text = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, "", top, DIFFICULTY_MENU_LEFT + 84.0);
+		commands.entity(value_text).insert(VolumeValueText(*channel));
+		
+		for (increase, label, left) in [(false, "-", 120.0), (true, "+", 136.0)] {
+			let button = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, label, top, DIFFICULTY_MENU_LEFT + left);
+			all_mouseover_targets.0.push(MouseoverTargetSpace {
+				target: MouseoverTarget::Volume(*channel, increase),
+				top,
+				left: DIFFICULTY_MENU_LEFT + left,
+				width: 14.0,
+				height: 16.0,
+				source_entity: button,
+			});
+		}
+	}
+}
+
+fn update_volume_menu(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mouseover_target: Res<CurrentMouseoverTarget>,
+	mut volume: ResMut<sound::VolumeSettings>,
+) {
+	if !action_query.single().just_pressed(player::Action::CastSpell) {
+		return;
+	}
+	
+	if let Some((MouseoverTarget::Volume(channel, increase), _)) = mouseover_target.0 {
+		volume.step(channel, increase);
+	}
+}
+
+fn update_volume_menu_text(
+	mut value_query: Query<(&VolumeValueText, &mut Text)>,
+	volume: Res<sound::VolumeSettings>,
+) {
+	if !volume.is_changed() {
+		return;
+	}
+	
+	for (value_text, mut text) in value_query.iter_mut() {
+		text.sections[0].value = format!("{:.0}%", volume.get(value_text.0) * 100.0);
+	}
+}
+
+// Accessibility toggle for screen shake, under the volume menu
+#[derive(Component, Debug)]
+struct ScreenShakeText;
+
+const SCREEN_SHAKE_TOGGLE_TOP: f32 = VOLUME_MENU_TOP + 3.0 * DIFFICULTY_ROW_HEIGHT;
+
+fn setup_screen_shake_toggle(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut all_mouseover_targets: ResMut<AllMouseoverTargets>,
+) {
+	let text_style = TextStyle {
+		font: asset_server.load("font/Mechanical-g5Y5.otf"),
+		font_size: 10.0,
+		color: Color::hex("B8EEEB").unwrap(),
+	};
+	let text = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, "", SCREEN_SHAKE_TOGGLE_TOP, DIFFICULTY_MENU_LEFT);
+	commands.entity(text).insert(ScreenShakeText);
+	all_mouseover_targets.0.push(MouseoverTargetSpace {
+		target: MouseoverTarget::ToggleScreenShake,
+		top: SCREEN_SHAKE_TOGGLE_TOP,
+		left: DIFFICULTY_MENU_LEFT,
+		width: MENU_ITEM_WIDTH,
+		height: 16.0,
+		source_entity: text,
+	});
+}
+
+fn update_screen_shake_toggle(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mouseover_target: Res<CurrentMouseoverTarget>,
+	mut feedback_settings: ResMut<feedback::FeedbackSettings>,
+) {
+	if !action_query.single().just_pressed(player::Action::CastSpell) {
+		return;
+	}
+	
+	if let Some((MouseoverTarget::ToggleScreenShake, _)) = mouseover_target.0 {
+		feedback_settings.screen_shake = !feedback_settings.screen_shake;
+	}
+}
+
+fn update_screen_shake_toggle_text(
+	mut text_query: Query<&mut Text, With<ScreenShakeText>>,
+	feedback_settings: Res<feedback::FeedbackSettings>,
+) {
+	if !feedback_settings.is_changed() {
+		return;
+	}
+	
+	for mut text in text_query.iter_mut() {
+		text.sections[0].value = format!("Screen shake: {}", if feedback_settings.screen_shake { "On" } else { "Off" });
+	}
+}
+
+// Controls menu; also on the settings page ///////////////
+#[derive(Component, Debug)]
+struct BindingText(player::Action);
+
+const CONTROLS_MENU_LEFT: f32 = UI_WIDTH - 176.0;
+const CONTROLS_MENU_TOP: f32 = 80.0;
+const CONTROLS_ROW_HEIGHT: f32 = 14.0;
+const REBINDABLE_ACTIONS: [player::Action; 16] = [
+	player::Action::Up,
+	player::Action::Down,
+	player::Action::Left,
+	player::Action::Right,
+	player::Action::Run,
+	player::Action::Dodge,
+	player::Action::CastSpell,
+	player::Action::CancelSpell,
+	player::Action::OpenInventory,
+	player::Action::Pause,
+	player::Action::SpellComp0,
+	player::Action::SpellComp1,
+	player::Action::SpellComp2,
+	player::Action::SpellComp3,
+	player::Action::SpellComp4,
+	player::Action::ToggleFullscreen,
+];
+
+fn setup_controls_menu(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut all_mouseover_targets: ResMut<AllMouseoverTargets>,
+) {
+	let text_style = TextStyle {
+		font: asset_server.load("font/Mechanical-g5Y5.otf"),
+		font_size: 10.0,
+		color: Color::hex("B8EEEB").unwrap(),
+	};
+	
+	spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, "Controls", CONTROLS_MENU_TOP, CONTROLS_MENU_LEFT);
+	
+	for (i, action) in REBINDABLE_ACTIONS.iter().enumerate() {
+		let top = CONTROLS_MENU_TOP + (i + 1) as f32 * CONTROLS_ROW_HEIGHT;
+		let label = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, bindings::get_action_name(*action), top, CONTROLS_MENU_LEFT);
+		let binding_text = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, "", top, CONTROLS_MENU_LEFT + 80.0);
+		commands.entity(binding_text).insert(BindingText(*action));
+		
+		all_mouseover_targets.0.push(MouseoverTargetSpace {
+			target: MouseoverTarget::Rebind(*action),
+			top,
+			left: CONTROLS_MENU_LEFT,
+			width: 160.0,
+			height: CONTROLS_ROW_HEIGHT,
+			source_entity: label,
+		});
+	}
+	
+	let reset_top = CONTROLS_MENU_TOP + (REBINDABLE_ACTIONS.len() + 1) as f32 * CONTROLS_ROW_HEIGHT;
+	let reset = spawn_menu_text(&mut commands, &text_style, MenuPage::Settings, "Reset to defaults", reset_top, CONTROLS_MENU_LEFT);
+	all_mouseover_targets.0.push(MouseoverTargetSpace {
+		target: MouseoverTarget::ResetBindings,
+		top: reset_top,
+		left: CONTROLS_MENU_LEFT,
+		width: 160.0,
+		height: CONTROLS_ROW_HEIGHT,
+		source_entity: reset,
+	});
+}
+
+fn update_controls_menu(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mouseover_target: Res<CurrentMouseoverTarget>,
+	mut key_bindings: ResMut<bindings::KeyBindings>,
+	mut rebinding: ResMut<bindings::RebindingAction>,
+) {
+	if rebinding.0.is_some() || !action_query.single().just_pressed(player::Action::CastSpell) {
+		return;
+	}
+	
+	match mouseover_target.0 {
+		Some((MouseoverTarget::Rebind(action), _)) => {
+			rebinding.0 = Some(action);
+		}
+		Some((MouseoverTarget::ResetBindings, _)) => {
+			*key_bindings = bindings::KeyBindings::default();
+			key_bindings.save();
+		}
+		_ => {}
+	}
+}
+
+fn update_controls_menu_text(
+	mut query: Query<(&BindingText, &mut Text)>,
+	key_bindings: Res<bindings::KeyBindings>,
+	rebinding: Res<bindings::RebindingAction>,
+) {
+	if !key_bindings.is_changed() && !rebinding.is_changed() {
+		return;
+	}
+	
+	for (binding_text, mut text) in query.iter_mut() {
+		text.sections[0].value = if rebinding.0 == Some(binding_text.0) {
+			"press a key...".to_string()
+		} else {
+			key_bindings.get_primary_binding(binding_text.0)
+				.map_or("-".to_string(), |binding| binding.get_display_name())
+		};
+	}
+}
+
+// Title, pause and game over menus ///////////////
+/// Marks which menu page some ui is on; it's only shown while that page is open
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuPage {
+	Title,
+	Pause,
+	Settings,
+	GameOver,
+}
+
+/// Resource
+/// Which page is shown while in a menu state
+struct CurrentMenuPage(MenuPage);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+	NewGame,
+	Continue,
+	Quit,
+	Resume,
+	Settings,
+	RestartRoom,
+	QuitToTitle,
+	TryAgain,
+	Back,
+}
+
+const MENU_TOP: f32 = 140.0;
+const MENU_ROW_HEIGHT: f32 = 20.0;
+const MENU_ITEM_WIDTH: f32 = 96.0;
+const TITLE_BACKGROUND_COLOR: &str = "090A14";
+/// Darkens the game behind the pause and game over menus
+const MENU_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+
+fn setup_menus(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut all_mouseover_targets: ResMut<AllMouseoverTargets>,
+) {
+	let text_style = TextStyle {
+		font: asset_server.load("font/Mechanical-g5Y5.otf"),
+		font_size: 10.0,
+		color: Color::hex("B8EEEB").unwrap(),
+	};
+	let left = (UI_WIDTH - MENU_ITEM_WIDTH) / 2.0;
+	
+	let mut spawn_page = |commands: &mut Commands, page: MenuPage, title: &str, items: &[(MenuItem, &str)]| {
+		spawn_menu_text(commands, &text_style, page, title, MENU_TOP, left);
+		for (i, (item, label)) in items.iter().enumerate() {
+			let top = MENU_TOP + (i + 1) as f32 * MENU_ROW_HEIGHT;
+			spawn_menu_item(commands, &mut all_mouseover_targets, &text_style, page, *item, label, top, left);
+		}
+	};
+	
+	let mut title_items = vec![
+		(MenuItem::NewGame, "New game"),
+		(MenuItem::Continue, "Continue"),
+		(MenuItem::Settings, "Settings"),
+	];
+	// Browsers don't let pages close themselves
+	if cfg!(not(target_arch = "wasm32")) {
+		title_items.push((MenuItem::Quit, "Quit"));
+	}
+	spawn_page(&mut commands, MenuPage::Title, "Tower of the Moon", &title_items);
+	spawn_page(&mut commands, MenuPage::Pause, "Paused", &[
+		(MenuItem::Resume, "Resume"),
+		(MenuItem::Settings, "Settings"),
+		(MenuItem::RestartRoom, "Restart room"),
+		(MenuItem::QuitToTitle, "Quit to title"),
+	]);
+	spawn_page(&mut commands, MenuPage::GameOver, "Game over", &[
+		(MenuItem::TryAgain, "Try again"),
+		(MenuItem::QuitToTitle, "Quit to title"),
+	]);
+	
+	// The settings page is the difficulty and controls menus, plus a way back
+	spawn_menu_item(&mut commands, &mut all_mouseover_targets, &text_style, MenuPage::Settings, MenuItem::Back, "Back", 316.0, DIFFICULTY_MENU_LEFT);
+}
+
+fn spawn_menu_item(
+	commands: &mut Commands,
+	all_mouseover_targets: &mut AllMouseoverTargets,
+	text_style: &TextStyle,
+	page: MenuPage,
+	item: MenuItem,
+	label: &str,
+	top: f32,
+	left: f32,
+) {
+	let text = spawn_menu_text(commands, text_style, page, label, top, left);
+	commands.entity(text).insert(item);
+	all_mouseover_targets.0.push(MouseoverTargetSpace {
+		target: MouseoverTarget::MenuItem(item),
+		top,
+		left,
+		width: MENU_ITEM_WIDTH,
+		height: 16.0,
+		source_entity: text,
+	});
+}
+
+/// The page to show when first going into a state
+fn get_main_menu_page(state: game_state::GameState) -> Option<MenuPage> {
+	match state {
+		game_state::GameState::Title => Some(MenuPage::Title),
+		game_state::GameState::Paused => Some(MenuPage::Pause),
+		game_state::GameState::GameOver => Some(MenuPage::GameOver),
+		_ => None,
+	}
+}
+
+fn update_menu_visibility(
+	mut query: Query<(&mut Visibility, &MenuPage, Option<&MenuItem>)>,
+	mut layer_query: Query<&mut UiColor, With<viewport::MenuLayer>>,
+	state: Res<State<game_state::GameState>>,
+	mut current_page: ResMut<CurrentMenuPage>,
+	current_room: Res<levels::CurrentRoom>,
+) {
+	if state.is_changed() {
+		if let Some(page) = get_main_menu_page(*state.current()) {
+			current_page.0 = page;
+		}
+	}
+	
+	let open_page = get_main_menu_page(*state.current()).map(|_| current_page.0);
+	for (mut visibility, page, maybe_item) in query.iter_mut() {
+		// Only continue if there's something to continue
+		let is_available = maybe_item != Some(&MenuItem::Continue) || current_room.0.is_some();
+		visibility.is_visible = open_page == Some(*page) && is_available;
+	}
+	
+	// Cover up the game when it isn't going
+	layer_query.single_mut().0 = match state.current() {
+		game_state::GameState::Title => Color::hex(TITLE_BACKGROUND_COLOR).unwrap(),
+		game_state::GameState::Paused | game_state::GameState::GameOver => MENU_BACKGROUND_COLOR,
+		_ => Color::NONE,
+	};
+}
+
+fn toggle_pause(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mut state: ResMut<State<game_state::GameState>>,
+	mut current_page: ResMut<CurrentMenuPage>,
+	rebinding: Res<bindings::RebindingAction>,
+) {
+	// Escape cancels rebinding instead
+	if rebinding.0.is_some() || !action_query.single().just_pressed(player::Action::Pause) {
+		return;
+	}
+	
+	match state.current() {
+		game_state::GameState::Playing | game_state::GameState::Inventory => {
+			game_state::set_state(&mut state, game_state::GameState::Paused);
+		}
+		// Go back a page first
+		game_state::GameState::Paused | game_state::GameState::Title if current_page.0 == MenuPage::Settings => {
+			current_page.0 = get_main_menu_page(*state.current()).unwrap();
+		}
+		game_state::GameState::Paused => {
+			game_state::set_state(&mut state, game_state::GameState::Playing);
+		}
+		_ => {}
+	}
+}
+
+fn update_menus(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mouseover_target: Res<CurrentMouseoverTarget>,
+	mut state: ResMut<State<game_state::GameState>>,
+	mut current_page: ResMut<CurrentMenuPage>,
+	mut transition_events: EventWriter<levels::RoomTransitionEvent>,
+	mut new_game_events: EventWriter<game_state::NewGameEvent>,
+	mut message_events: EventWriter<MessageEvent>,
+	mut exit_events: EventWriter<bevy::app::AppExit>,
+) {
+	if !action_query.single().just_pressed(player::Action::CastSpell) {
+		return;
+	}
+	
+	let item = match mouseover_target.0 {
+		Some((MouseoverTarget::MenuItem(item), _)) => item,
+		_ => return,
+	};
+	match item {
+		MenuItem::NewGame => {
+			new_game_events.send(game_state::NewGameEvent);
+			game_state::set_state(&mut state, game_state::GameState::Playing);
+		}
+		MenuItem::Continue | MenuItem::RestartRoom => {
+			transition_events.send(levels::RoomTransitionEvent(levels::DestinationRoom::SameRoom));
+			game_state::set_state(&mut state, game_state::GameState::Playing);
+		}
+		MenuItem::Quit => {
+			exit_events.send(bevy::app::AppExit);
+		}
+		MenuItem::Resume => {
+			game_state::set_state(&mut state, game_state::GameState::Playing);
+		}
+		MenuItem::Settings => {
+			current_page.0 = MenuPage::Settings;
+		}
+		MenuItem::Back => {
+			current_page.0 = get_main_menu_page(*state.current()).unwrap_or(MenuPage::Title);
+		}
+		MenuItem::TryAgain => {
+			transition_events.send(levels::RoomTransitionEvent(levels::DestinationRoom::TargetRoom {
+				target: levels::RESPAWN_ROOM,
+				respawn: true,
+			}));
+			game_state::set_state(&mut state, game_state::GameState::Playing);
+		}
+		MenuItem::QuitToTitle => {
+			// Being defeated costs the same whether or not the player tries again right away
+			if *state.current() == game_state::GameState::GameOver {
+				transition_events.send(levels::RoomTransitionEvent(levels::DestinationRoom::TargetRoom {
+					target: levels::RESPAWN_ROOM,
+					respawn: true,
+				}));
+			}
+			message_events.send(MessageEvent {
+				message: None,
+				source: MessageSource::ForceClear,
+			});
+			game_state::set_state(&mut state, game_state::GameState::Title);
+		}
+	}
+}
+
 //// Update spell selection ///////////////////////////////
 fn update_spell_selection(
 	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
 	mouseover_target: Res<CurrentMouseoverTarget>,
 	mut selected_runes: ResMut<spells::EquippedRunes>,
 	rune_inventory: Res<spells::RuneInventory>,
-    spell_ui_active: Res<SpellUiActive>,
 ) {
-	if !spell_ui_active.0 {
-		return;
-	}
-	
 	let action_state = action_query.single();
 	
 	let maybe_action_index = {
@@ -790,6 +1476,16 @@ fn update_spell_selection(
 				// Set it to the new rune
 				selected_runes.set(action_idx, Some(inventory_slot.rune));
 			}
+			// Handled in update_controls_menu
+			MouseoverTarget::Rebind(_) | MouseoverTarget::ResetBindings => {}
+			// Handled in update_difficulty_menu
+			MouseoverTarget::DifficultyPreset(_) | MouseoverTarget::DifficultySlider(_, _) => {}
+			// Handled in update_volume_menu
+			MouseoverTarget::Volume(_, _) => {}
+			// Handled in update_screen_shake_toggle
+			MouseoverTarget::ToggleScreenShake => {}
+			// Handled in update_menus
+			MouseoverTarget::MenuItem(_) => {}
 		}
 	}
 }
@@ -816,6 +1512,10 @@ struct MouseoverTargetSpace {
 }
 
 impl MouseoverTargetSpace {
+    fn get_center(&self) -> Vec2 {
+        Vec2::new(self.left + self.width / 2.0, self.top + self.height / 2.0)
+    }
+    
     fn contains(&self, pos: Vec2) -> bool {
         // mouse coordinates are inverted vertically
         pos.x >= self.left
@@ -834,68 +1534,163 @@ pub struct CurrentMouseoverTarget(pub Option<(MouseoverTarget, Entity)>);
 pub enum MouseoverTarget {
     SpellSelectedSlot(usize),
     SpellInventorySlot(usize),
+    DifficultyPreset(difficulty::DifficultyPreset),
+    /// The bool is whether this is the increase button
+    DifficultySlider(difficulty::DifficultySetting, bool),
+    /// The bool is whether this is the increase button
+    Volume(sound::VolumeChannel, bool),
+    ToggleScreenShake,
+    Rebind(player::Action),
+    ResetBindings,
+    MenuItem(MenuItem),
 }
 
-/// Gets the position of the cursor if in the primary window
-pub fn get_cursor_position(windows: Res<Windows>) -> Option<Vec2> {
-    let raw_pos = windows.get_primary()?.cursor_position()?;
-    Some(Vec2::new(raw_pos.x, 400.0 - raw_pos.y))
+/// Gets the position of the cursor in ui space (origin at the top left of the game area)
+/// if in the primary window
+pub fn get_cursor_position(windows: &Windows, game_viewport: &viewport::GameViewport) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let raw_pos = window.cursor_position()?;
+    // Window coordinates start at the bottom
+    Some(Vec2::new(raw_pos.x, window.height() - raw_pos.y) - game_viewport.get_logical_offset())
 }
 
 /// Gets the intersection of the cursor ray with the plane containing the
 /// point `plane_point` with normal `plane_normal`.
-/// Returns None if the cursor is not in the window.
+/// Returns None if the cursor is not in the window, or if the ray doesn't hit the plane.
 /// (this is here because get_cursor_position is)
-#[allow(non_snake_case)]
 pub fn get_cursor_world_position(
-    windows: Res<Windows>,
+    windows: &Windows,
     camera: &Camera,
     camera_transform: &GlobalTransform,
     plane_point: Vec3,
-    _plane_normal: Vec3,
+    plane_normal: Vec3,
 ) -> Option<Vec3> {
-    let cursor_screen_pos = get_cursor_position(windows)?;
+    let ray = get_cursor_ray(windows, camera, camera_transform)?;
+    let distance = ray.intersect_plane(plane_point, plane_normal)?;
+    Some(ray.get_point(distance))
+}
 
-    let scaled_screen_pos = Vec2::new(
-        (cursor_screen_pos.x - 320.0) / 320.0,
-        (200.0 - cursor_screen_pos.y) / 200.0,
-    );
+/// Gets the world space ray under the cursor
+pub fn get_cursor_ray(
+    windows: &Windows,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<ray::Ray> {
+    let window = windows.get_primary()?;
+    let raw_pos = window.cursor_position()?;
+    
+    // Physical position, measured from the top left
+    let cursor_physical_pos = Vec2::new(raw_pos.x, window.height() - raw_pos.y) * window.scale_factor() as f32;
+    let (viewport_pos, viewport_size) = match &camera.viewport {
+        Some(viewport) => (viewport.physical_position.as_vec2(), viewport.physical_size.as_vec2()),
+        None => (Vec2::ZERO, Vec2::new(window.physical_width() as f32, window.physical_height() as f32)),
+    };
+    
+    ray::Ray::from_viewport_position(camera, camera_transform, viewport_size, cursor_physical_pos - viewport_pos)
+}
 
-    // funny linear algebra way that has a bug that I don't have time to find
-    /*let A = camera.projection_matrix() * camera_transform.compute_matrix().inverse();
+/// Keeps a ui node positioned over something in the world.
+/// The node gets despawned along with its target.
+#[derive(Component, Debug)]
+pub struct UiWorldAnchor {
+    pub target: Entity,
+    pub offset: Vec3,
+}
 
-    let x: Vec4 = scaled_screen_pos.extend(0.0).extend(0.0);
-    let n: Vec3 = plane_normal.normalize();
-    let n_hat: Vec4 = n.extend(0.0);
-    let p: Vec3 = plane_point;
+fn update_ui_world_anchors(
+    mut commands: Commands,
+    mut query: Query<(Entity, &UiWorldAnchor, &mut Style, &mut Visibility)>,
+    target_query: Query<&GlobalTransform>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+) {
+    let (camera, camera_transform) = camera_query.single();
+    
+    for (e, anchor, mut style, mut visibility) in query.iter_mut() {
+        let target_transform = match target_query.get(anchor.target) {
+            Ok(transform) => transform,
+            Err(_) => {
+                commands.entity(e).despawn_recursive();
+                continue;
+            }
+        };
+        
+        // The ui root covers exactly the camera's viewport, so this lines up
+        match ray::world_to_viewport_position(
+            camera,
+            camera_transform,
+            Vec2::new(UI_WIDTH, UI_HEIGHT),
+            target_transform.translation() + anchor.offset,
+        ) {
+            Some(ui_pos) => {
+                style.position_type = PositionType::Absolute;
+                style.position.left = Val::Px(ui_pos.x.round());
+                style.position.top = Val::Px(ui_pos.y.round());
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+}
 
-    let rhs = A.transpose() * x + n_hat * n.dot(p);
-    let lhs = A.transpose() * A + outer_product(n_hat, n_hat);
+// Shows a "!" over enemies when they notice the player
+#[derive(Component, Debug)]
+struct NoticeIndicator(Timer);
 
-    Some((lhs.inverse() * rhs).xyz())*/
-	
-	// More hardcoded version
-	let loc_camera_transform = camera_transform.compute_transform();
-	let world_x = scaled_screen_pos.x * 160.0 + loc_camera_transform.translation.x;
-	
-	// determine how y and z are projected
-	let world_proj_matrix = camera.projection_matrix() * camera_transform.compute_matrix().inverse();
-	let proj_y = (world_proj_matrix * Vec3::Y.extend(0.0)).y;
-	let proj_z = (world_proj_matrix * Vec3::Z.extend(0.0)).y;
-	
-	let z_val = (scaled_screen_pos.y - plane_point.y * proj_y) / proj_z;
-	
-	Some(Vec3::new(world_x, plane_point.y, z_val))
+fn spawn_notice_indicators(
+    mut commands: Commands,
+    query: Query<(Entity, &enemy::AIGeneralState), Changed<enemy::AIGeneralState>>,
+    text_style: Res<MessageTextStyle>,
+) {
+    for (e, state) in query.iter() {
+        if !state.has_noticed_player() {
+            continue;
+        }
+        
+        commands
+            .spawn_bundle(TextBundle {
+                text: Text::from_section("!", text_style.0.clone()),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(UiWorldAnchor {
+                target: e,
+                offset: Vec3::Y * 36.0,
+            })
+            .insert(NoticeIndicator(Timer::from_seconds(0.6, false)));
+    }
+}
+
+fn update_notice_indicators(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut NoticeIndicator)>,
+    time: Res<Time>,
+) {
+    for (e, mut indicator) in query.iter_mut() {
+        indicator.0.tick(time.delta());
+        if indicator.0.finished() {
+            commands.entity(e).despawn_recursive();
+        }
+    }
 }
 
 // System to keep track of what the mouse is over
 fn update_cursor_ui_target(
     targets: Res<AllMouseoverTargets>,
     windows: Res<Windows>,
+    game_viewport: Res<viewport::GameViewport>,
+    nav_selection: Res<UiNavSelection>,
     mut current_target: ResMut<CurrentMouseoverTarget>,
     query: Query<&Visibility>,
 ) {
-    current_target.0 = match get_cursor_position(windows) {
+    // Things selected without the mouse take priority
+    if let Some(target) = nav_selection.0.and_then(|idx| targets.0.get(idx)) {
+        if query.get(target.source_entity).map_or(false, |visibility| visibility.is_visible) {
+            current_target.0 = Some((target.target, target.source_entity));
+            return;
+        }
+    }
+    
+    current_target.0 = match get_cursor_position(&windows, &game_viewport) {
         Some(pos) => {
             let mut result = None;
             for target in &targets.0 {
@@ -914,3 +1709,156 @@ fn update_cursor_ui_target(
         None => None,
     };
 }
+
+// Gamepad ui //////////////////////////////////////////////
+/// Resource
+/// Which mouseover target is selected using directional input, for playing without a mouse.
+struct UiNavSelection(Option<usize>);
+
+#[derive(Component, Debug)]
+struct UiNavHighlight;
+#[derive(Component, Debug)]
+struct AimReticle;
+
+const RETICLE_SIZE: f32 = 6.0;
+const RETICLE_DISTANCE: f32 = 40.0;
+
+fn setup_gamepad_ui(mut commands: Commands) {
+	commands
+		.spawn_bundle(NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				..default()
+			},
+			color: UiColor(Color::rgba(1.0, 1.0, 1.0, 0.25)),
+			visibility: Visibility { is_visible: false },
+			..default()
+		})
+		.insert(UiNavHighlight)
+		// Menus are in the menu layer, so it needs to be on top of them
+		.insert(viewport::InMenuLayer);
+	
+	commands
+		.spawn_bundle(NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				size: Size::new(Val::Px(RETICLE_SIZE), Val::Px(RETICLE_SIZE)),
+				..default()
+			},
+			color: UiColor(Color::hex("B8EEEB").unwrap()),
+			visibility: Visibility { is_visible: false },
+			..default()
+		})
+		.insert(AimReticle);
+}
+
+/// Moves the selection between the mouseover targets with the directional actions
+fn update_ui_navigation(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mut selection: ResMut<UiNavSelection>,
+	targets: Res<AllMouseoverTargets>,
+	visibility_query: Query<&Visibility, Without<UiNavHighlight>>,
+	mut highlight_query: Query<(&mut Style, &mut Visibility), With<UiNavHighlight>>,
+	state: Res<State<game_state::GameState>>,
+	mut cursor_events: EventReader<CursorMoved>,
+) {
+	let (mut highlight_style, mut highlight_visibility) = highlight_query.single_mut();
+	
+	// Going back to the mouse clears the selection
+	if !state.current().is_in_menu() || cursor_events.iter().count() > 0 {
+		selection.0 = None;
+		highlight_visibility.is_visible = false;
+		return;
+	}
+	
+	let action_state = action_query.single();
+	let mut direction = Vec2::ZERO;
+	if action_state.just_pressed(player::Action::Up) {
+		direction.y -= 1.0;
+	}
+	if action_state.just_pressed(player::Action::Down) {
+		direction.y += 1.0;
+	}
+	if action_state.just_pressed(player::Action::Left) {
+		direction.x -= 1.0;
+	}
+	if action_state.just_pressed(player::Action::Right) {
+		direction.x += 1.0;
+	}
+	
+	let is_visible = |target: &MouseoverTargetSpace| {
+		visibility_query.get(target.source_entity).map_or(false, |visibility| visibility.is_visible)
+	};
+	let current = selection.0
+		.and_then(|idx| targets.0.get(idx))
+		.filter(|target| is_visible(target));
+	
+	if direction != Vec2::ZERO {
+		selection.0 = match current {
+			// Start from the first thing on the page
+			None => targets.0.iter().position(|target| is_visible(target)),
+			Some(current) => {
+				let current_center = current.get_center();
+				// Closest target in roughly the right direction, preferring ones that are straight ahead
+				targets.0.iter()
+					.enumerate()
+					.filter(|(_, target)| is_visible(target))
+					.filter_map(|(idx, target)| {
+						let offset = target.get_center() - current_center;
+						let forward = offset.dot(direction);
+						if forward <= 1.0 {
+							return None;
+						}
+						let sideways = offset.perp_dot(direction).abs();
+						Some((idx, forward + 2.0 * sideways))
+					})
+					.min_by(|(_, a), (_, b)| a.total_cmp(b))
+					.map(|(idx, _)| idx)
+					.or(selection.0)
+			}
+		};
+	}
+	
+	// Update the highlight
+	match selection.0.and_then(|idx| targets.0.get(idx)) {
+		Some(target) => {
+			highlight_style.position.left = Val::Px(target.left);
+			highlight_style.position.top = Val::Px(target.top);
+			highlight_style.size = Size::new(Val::Px(target.width), Val::Px(target.height));
+			highlight_visibility.is_visible = true;
+		}
+		None => highlight_visibility.is_visible = false,
+	}
+}
+
+/// Shows where the right stick is aiming
+fn update_aim_reticle(
+	mut reticle_query: Query<(&mut Style, &mut Visibility), With<AimReticle>>,
+	player_query: Query<(&GlobalTransform, &player::PlayerHasStaff), With<player::Player>>,
+	camera_query: Query<(&Camera, &GlobalTransform)>,
+	gamepad_state: Res<gamepad::GamepadState>,
+	state: Res<State<game_state::GameState>>,
+) {
+	let (mut style, mut visibility) = reticle_query.single_mut();
+	let (player_transform, has_staff) = player_query.single();
+	let (camera, camera_transform) = camera_query.single();
+	
+	let maybe_aim = gamepad_state.aim.filter(|_| gamepad_state.in_use && has_staff.0 && *state.current() == game_state::GameState::Playing);
+	let maybe_ui_pos = maybe_aim.and_then(|aim| {
+		ray::world_to_viewport_position(
+			camera,
+			camera_transform,
+			Vec2::new(UI_WIDTH, UI_HEIGHT),
+			player_transform.translation() + Vec3::Y * 12.0 + expand_vec2(aim * RETICLE_DISTANCE),
+		)
+	});
+	
+	match maybe_ui_pos {
+		Some(ui_pos) => {
+			style.position.left = Val::Px((ui_pos.x - RETICLE_SIZE / 2.0).round());
+			style.position.top = Val::Px((ui_pos.y - RETICLE_SIZE / 2.0).round());
+			visibility.is_visible = true;
+		}
+		None => visibility.is_visible = false,
+	}
+}
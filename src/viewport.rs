@@ -0,0 +1,220 @@
+use super::{player, ui};
+use bevy::{
+	prelude::*,
+	render::camera::Viewport,
+	window::{WindowMode, WindowResized},
+};
+use leafwing_input_manager::prelude::*;
+
+/// Handles fitting the game into whatever size the window is.
+/// The game is always rendered at an integer multiple of its base resolution,
+/// centered in the window with bars around it.
+pub struct ViewportPlugin;
+
+impl Plugin for ViewportPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.insert_resource(GameViewport::default())
+			.add_startup_system(setup_ui_root)
+			.add_system(toggle_fullscreen)
+			.add_system(update_viewport)
+			.add_system(attach_to_ui_root);
+	}
+}
+
+/// Size of the game area, in unscaled pixels
+pub const BASE_WIDTH: u32 = 640;
+pub const BASE_HEIGHT: u32 = 400;
+
+/// Resource
+/// The area of the window the game is currently drawn into.
+#[derive(Debug, Default)]
+pub struct GameViewport {
+	pub scale: u32,
+	pub physical_position: UVec2,
+	pub physical_size: UVec2,
+	pub physical_window_size: UVec2,
+}
+
+impl GameViewport {
+	fn from_window_size(physical_window_size: UVec2) -> Self {
+		let scale = (physical_window_size.x / BASE_WIDTH)
+			.min(physical_window_size.y / BASE_HEIGHT)
+			.max(1);
+		// A window smaller than the base resolution gets all of it; the camera shrinks the view to fit
+		let physical_size = (UVec2::new(BASE_WIDTH, BASE_HEIGHT) * scale).min(physical_window_size);
+
+		Self {
+			scale,
+			physical_position: (physical_window_size - physical_size) / 2,
+			physical_size,
+			physical_window_size,
+		}
+	}
+
+	/// Where the top-left corner of the game area is, in logical (ui) pixels
+	pub fn get_logical_offset(&self) -> Vec2 {
+		self.physical_position.as_vec2() / self.scale as f32
+	}
+}
+
+fn update_viewport(
+	mut windows: ResMut<Windows>,
+	mut resize_events: EventReader<WindowResized>,
+	mut camera_query: Query<&mut Camera>,
+	mut game_viewport: ResMut<GameViewport>,
+) {
+	// Always run on the first frame, so the viewport gets set up
+	if resize_events.iter().count() == 0 && game_viewport.scale != 0 {
+		return;
+	}
+
+	let window = match windows.get_primary_mut() {
+		Some(window) => window,
+		None => return,
+	};
+	let physical_window_size = UVec2::new(window.physical_width(), window.physical_height());
+	if physical_window_size == game_viewport.physical_window_size {
+		return;
+	}
+
+	*game_viewport = GameViewport::from_window_size(physical_window_size);
+
+	// Make one logical pixel one game pixel, so the ui scales along with everything else
+	window.set_scale_factor_override(Some(game_viewport.scale as f64));
+
+	for mut camera in camera_query.iter_mut() {
+		camera.viewport = Some(Viewport {
+			physical_position: game_viewport.physical_position,
+			physical_size: game_viewport.physical_size,
+			..default()
+		});
+	}
+}
+
+fn toggle_fullscreen(
+	action_query: Query<&ActionState<player::Action>, With<player::Player>>,
+	mut windows: ResMut<Windows>,
+) {
+	if !action_query.single().just_pressed(player::Action::ToggleFullscreen) {
+		return;
+	}
+
+	if let Some(window) = windows.get_primary_mut() {
+		window.set_mode(match window.mode() {
+			WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+			_ => WindowMode::Windowed,
+		});
+	}
+}
+
+/// Marker for the node containing all other ui.
+/// Keeps the ui lined up with the game area.
+#[derive(Component)]
+pub struct UiRoot;
+/// Marker for the layer of ui drawn over everything else, which menus go in.
+/// Its color can be changed to cover up the rest of the game.
+#[derive(Component)]
+pub struct MenuLayer;
+#[derive(Component)]
+struct GameUiLayer;
+/// Marker for top level ui nodes that should go in the menu layer
+#[derive(Component)]
+pub struct InMenuLayer;
+
+fn get_full_size_node() -> NodeBundle {
+	NodeBundle {
+		style: Style {
+			position_type: PositionType::Absolute,
+			position: UiRect {
+				top: Val::Px(0.0),
+				left: Val::Px(0.0),
+				..default()
+			},
+			size: Size::new(Val::Px(ui::UI_WIDTH), Val::Px(ui::UI_HEIGHT)),
+			..default()
+		},
+		color: UiColor(Color::NONE),
+		..default()
+	}
+}
+
+fn setup_ui_root(mut commands: Commands) {
+	commands
+		.spawn_bundle(get_full_size_node())
+		.insert(UiRoot)
+		.with_children(|parent| {
+			// Later children are drawn on top
+			parent.spawn_bundle(get_full_size_node()).insert(GameUiLayer);
+			parent.spawn_bundle(get_full_size_node()).insert(MenuLayer);
+		});
+}
+
+/// Moves any top level ui nodes into the right layer of the ui root, and keeps the root positioned
+fn attach_to_ui_root(
+	mut commands: Commands,
+	mut root_query: Query<&mut Style, With<UiRoot>>,
+	game_layer_query: Query<Entity, With<GameUiLayer>>,
+	menu_layer_query: Query<Entity, With<MenuLayer>>,
+	node_query: Query<(Entity, Option<&InMenuLayer>), (With<Node>, Without<Parent>, Without<UiRoot>)>,
+	game_viewport: Res<GameViewport>,
+) {
+	let mut style = root_query.single_mut();
+	let game_layer = game_layer_query.single();
+	let menu_layer = menu_layer_query.single();
+
+	for (e, in_menu_layer) in node_query.iter() {
+		let layer = if in_menu_layer.is_some() { menu_layer } else { game_layer };
+		commands.entity(layer).add_child(e);
+	}
+
+	if game_viewport.is_changed() {
+		let offset = game_viewport.get_logical_offset();
+		style.position.left = Val::Px(offset.x);
+		style.position.top = Val::Px(offset.y);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn exact_fit_has_no_bars() {
+		let viewport = GameViewport::from_window_size(UVec2::new(1280, 800));
+		assert_eq!(viewport.scale, 2);
+		assert_eq!(viewport.physical_position, UVec2::ZERO);
+		assert_eq!(viewport.physical_size, UVec2::new(1280, 800));
+	}
+
+	#[test]
+	fn wide_window_is_letterboxed_at_the_sides() {
+		let viewport = GameViewport::from_window_size(UVec2::new(1920, 1080));
+		assert_eq!(viewport.scale, 2);
+		assert_eq!(viewport.physical_size, UVec2::new(1280, 800));
+		assert_eq!(viewport.physical_position, UVec2::new(320, 140));
+		assert_eq!(viewport.get_logical_offset(), Vec2::new(160.0, 70.0));
+	}
+
+	#[test]
+	fn tall_window_is_letterboxed_at_the_top_and_bottom() {
+		let viewport = GameViewport::from_window_size(UVec2::new(1000, 1200));
+		assert_eq!(viewport.scale, 1);
+		assert_eq!(viewport.physical_size, UVec2::new(640, 400));
+		assert_eq!(viewport.physical_position, UVec2::new(180, 400));
+	}
+
+	#[test]
+	fn small_window_is_never_overflowed() {
+		for (window_size, position, size) in [
+			(UVec2::new(320, 200), UVec2::ZERO, UVec2::new(320, 200)),
+			(UVec2::new(639, 400), UVec2::ZERO, UVec2::new(639, 400)),
+			(UVec2::new(800, 300), UVec2::new(80, 0), UVec2::new(640, 300)),
+		] {
+			let viewport = GameViewport::from_window_size(window_size);
+			assert_eq!(viewport.scale, 1);
+			assert_eq!(viewport.physical_position, position);
+			assert_eq!(viewport.physical_size, size);
+		}
+	}
+}